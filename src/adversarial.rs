@@ -0,0 +1,330 @@
+extern crate rand;
+
+use std::fmt;
+
+use rand::Rng;
+
+use crate::dataset;
+use crate::error::NnError;
+use crate::nodes_layers;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Norm {
+    //How the size of a perturbation is measured.
+    LInf, //The biggest change to any single input.
+    L2, //The length of the change as a vector.
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Attack {
+    //Ways of making adversarial examples.
+    Fgsm, //Fast gradient sign method: a single step of the full epsilon in the direction that increases the loss.
+    Pgd {steps: usize, step_size: f32, random_start: bool}, //Projected gradient descent: many small steps, each followed by pulling the result back within epsilon of the original.
+}
+
+impl Attack {
+    pub fn check(&self) -> Result<(), NnError> {
+        //Whether the settings of the attack make sense. The epsilon is checked by attack, since it isn't part of the Attack.
+        if let Attack::Pgd {steps, step_size, ..} = *self {
+            if steps == 0 {
+                return Err(NnError::Config("A PGD attack must take at least 1 step".to_string()))
+            }
+            if !step_size.is_finite() || step_size < 0.0 {
+                return Err(NnError::Config(format!("The step_size of a PGD attack must be a number of at least 0.0, but it's {:?}", step_size)))
+            }
+        }
+        Ok(())
+    }
+}
+
+pub fn attack(network: &nodes_layers::Network, inputs: &Vec<f32>, desired_outputs: &Vec<f32>, attack: Attack, norm: Norm, epsilon: f32, bounds: Option<(f32, f32)>) -> Result<Vec<f32>, NnError> {
+    //Makes an adversarial version of the inputs, changed by at most epsilon (measured with norm) in the way that most increases the network's loss.
+    //The inputs are the ones from before the network's input_scaler, and if there are bounds, each value of the result is kept within them,
+    //like (0.0, 1.0) for the images from inout::unpack_images.
+    if !epsilon.is_finite() || epsilon < 0.0 {
+        return Err(NnError::Config(format!("The epsilon of an attack must be a number of at least 0.0, but it's {:?}", epsilon)))
+    }
+    attack.check()?;
+    check_bounds(bounds)?;
+    match attack {
+        Attack::Fgsm => {
+            let gradient = network.loss_gradient(inputs, desired_outputs)?;
+            let mut adversarial = step(inputs, &gradient, norm, epsilon);
+            clamp(&mut adversarial, bounds);
+            Ok(adversarial)
+        },
+        Attack::Pgd {steps, step_size, random_start} => {
+            let mut adversarial = inputs.clone();
+            //With an epsilon of 0.0 there's no room to start anywhere but the inputs.
+            //Noise bigger than the width of the bounds would only be clamped away.
+            if random_start && epsilon > 0.0 {
+                let mut rng = rand::thread_rng();
+                let noise_size = match bounds {
+                    Some((low, high)) if high > low => epsilon.min(high - low),
+                    Some(_) => 0.0,
+                    None => epsilon,
+                };
+                if noise_size > 0.0 {
+                    let noise: Vec<f32> = inputs.iter().map(|_| rng.gen_range(-noise_size, noise_size)).collect();
+                    for num in 0..adversarial.len() {
+                        adversarial[num] += noise[num];
+                    }
+                }
+                project(&mut adversarial, inputs, norm, epsilon);
+                clamp(&mut adversarial, bounds);
+            }
+            for _ in 0..steps {
+                let gradient = network.loss_gradient(&adversarial, desired_outputs)?;
+                adversarial = step(&adversarial, &gradient, norm, step_size);
+                project(&mut adversarial, inputs, norm, epsilon);
+                clamp(&mut adversarial, bounds);
+            }
+            Ok(adversarial)
+        },
+    }
+}
+
+fn step(inputs: &[f32], gradient: &[f32], norm: Norm, size: f32) -> Vec<f32> {
+    //Moves the inputs by size in the direction of the gradient that is steepest for the norm.
+    match norm {
+        Norm::LInf => {
+            (0..inputs.len()).map(|num| inputs[num] + size * sign(gradient[num])).collect()
+        },
+        Norm::L2 => {
+            let length = gradient.iter().map(|value| value.powi(2)).sum::<f32>().sqrt();
+            if length == 0.0 {
+                return inputs.to_vec()
+            }
+            (0..inputs.len()).map(|num| inputs[num] + size * gradient[num] / length).collect()
+        },
+    }
+}
+
+fn project(adversarial: &mut Vec<f32>, original: &[f32], norm: Norm, epsilon: f32) {
+    //Pulls the adversarial inputs back to within epsilon of the original inputs.
+    match norm {
+        Norm::LInf => {
+            for num in 0..adversarial.len() {
+                adversarial[num] = adversarial[num].max(original[num] - epsilon).min(original[num] + epsilon);
+            }
+        },
+        Norm::L2 => {
+            let length = (0..adversarial.len()).map(|num| (adversarial[num] - original[num]).powi(2)).sum::<f32>().sqrt();
+            if length > epsilon {
+                for num in 0..adversarial.len() {
+                    adversarial[num] = original[num] + (adversarial[num] - original[num]) * epsilon / length;
+                }
+            }
+        },
+    }
+}
+
+fn check_bounds(bounds: Option<(f32, f32)>) -> Result<(), NnError> {
+    if let Some((low, high)) = bounds {
+        if !(low.is_finite() && high.is_finite() && low <= high) {
+            return Err(NnError::Config(format!("The bounds of an attack must be numbers with the smallest first, but they're {:?} and {:?}", low, high)))
+        }
+    }
+    Ok(())
+}
+
+fn clamp(values: &mut Vec<f32>, bounds: Option<(f32, f32)>) {
+    if let Some((low, high)) = bounds {
+        for value in values.iter_mut() {
+            *value = value.max(low).min(high);
+        }
+    }
+}
+
+fn sign(value: f32) -> f32 {
+    if value > 0.0 {1.0} else if value < 0.0 {-1.0} else {0.0}
+}
+
+#[derive(Clone, Debug)]
+pub struct RobustnessReport {
+    pub attack: Attack,
+    pub norm: Norm,
+    pub accuracies: Vec<(f32, f32)>, //(epsilon, accuracy) for each epsilon that was tried, in the order they were given.
+    pub example_count: usize,
+}
+
+impl fmt::Display for RobustnessReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "Attack: {:?}, norm: {:?}, examples: {}", self.attack, self.norm, self.example_count)?;
+        writeln!(f, "epsilon  accuracy")?;
+        for (epsilon, accuracy) in &self.accuracies {
+            writeln!(f, "{:<8.4} {:.4}", epsilon, accuracy)?;
+        }
+        Ok(())
+    }
+}
+
+pub fn robustness_report(network: &nodes_layers::Network, dataset: &dyn dataset::Dataset, attack_kind: Attack, norm: Norm, epsilons: &[f32], bounds: Option<(f32, f32)>) -> Result<RobustnessReport, NnError> {
+    //Attacks every example with each epsilon and finds the accuracy on the adversarial examples.
+    //An epsilon of 0.0 gives the accuracy on the clean examples.
+    let mut accuracies = Vec::new();
+    for &epsilon in epsilons {
+        let mut successes = 0;
+        for num in 0..dataset.len() {
+            let (inputs, desired_outputs) = dataset.get(num);
+            let adversarial = if epsilon > 0.0 {
+                    attack(network, &inputs, &desired_outputs, attack_kind, norm, epsilon, bounds)?
+                } else {
+                    inputs
+                };
+            if nodes_layers::same_biggest(&network.predict(&adversarial)?, &desired_outputs) {
+                successes += 1;
+            }
+        }
+        accuracies.push((epsilon, if dataset.is_empty() {0.0} else {successes as f32 / dataset.len() as f32}));
+    }
+    Ok(RobustnessReport {
+        attack: attack_kind,
+        norm: norm,
+        accuracies: accuracies,
+        example_count: dataset.len(),
+    })
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct AdversarialTraining {
+    //Settings for training on adversarial examples made on the fly.
+    pub attack: Attack,
+    pub norm: Norm,
+    pub epsilon: f32,
+    pub adversarial_fraction: f32, //The share of the training steps that use the adversarial version of the example instead of the clean one.
+    pub bounds: Option<(f32, f32)>, //The range the inputs are kept within, before the network's input_scaler. None for no limits.
+}
+
+impl AdversarialTraining {
+    pub fn check(&self) -> Result<(), NnError> {
+        //Whether the settings make sense. The epsilon and the bounds are checked along with the attack.
+        if !(self.adversarial_fraction >= 0.0 && self.adversarial_fraction <= 1.0) {
+            return Err(NnError::Config(format!("The adversarial_fraction must be between 0.0 and 1.0, but it's {:?}", self.adversarial_fraction)))
+        }
+        self.attack.check()
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct StepOutcome {
+    //Whether the network got an example right before it was trained on it.
+    pub clean_correct: bool,
+    pub robust_correct: bool, //Whether it got the adversarial version right.
+}
+
+pub fn train_step(network: &mut nodes_layers::Network, inputs: &Vec<f32>, desired_outputs: &Vec<f32>, training: &AdversarialTraining) -> Result<StepOutcome, NnError> {
+    //Makes an adversarial version of the example against the network as it is now, then trains on either it or the clean example,
+    //picking the adversarial one with the probability training.adversarial_fraction.
+    training.check()?;
+    let adversarial = attack(network, inputs, desired_outputs, training.attack, training.norm, training.epsilon, training.bounds)?;
+    let outcome = StepOutcome {
+        clean_correct: nodes_layers::same_biggest(&network.predict(inputs)?, desired_outputs),
+        robust_correct: nodes_layers::same_biggest(&network.predict(&adversarial)?, desired_outputs),
+    };
+    if rand::thread_rng().gen::<f32>() < training.adversarial_fraction {
+        network.find_make_adjust(&adversarial, desired_outputs)?;
+    } else {
+        network.find_make_adjust(inputs, desired_outputs)?;
+    }
+    Ok(outcome)
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct EpochReport {
+    pub epoch: usize,
+    pub clean_accuracy: f32, //Accuracy on the clean examples, measured just before training on each.
+    pub robust_accuracy: f32, //Accuracy on the adversarial versions, measured the same way.
+}
+
+impl fmt::Display for EpochReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Epoch {}: clean accuracy {:.4}, robust accuracy {:.4}", self.epoch, self.clean_accuracy, self.robust_accuracy)
+    }
+}
+
+pub fn train_epochs(network: &mut nodes_layers::Network, dataset: &dyn dataset::Dataset, training: &AdversarialTraining, epochs: usize) -> Result<Vec<EpochReport>, NnError> {
+    //Adversarial training over the whole training set a number of times, returning a report for each epoch.
+    let mut reports = Vec::new();
+    for epoch in 1..=epochs {
+        let mut clean_successes = 0;
+        let mut robust_successes = 0;
+        for num in 0..dataset.len() {
+            let (inputs, desired_outputs) = dataset.get(num);
+            let outcome = train_step(network, &inputs, &desired_outputs, training)?;
+            if outcome.clean_correct {
+                clean_successes += 1;
+            }
+            if outcome.robust_correct {
+                robust_successes += 1;
+            }
+        }
+        let example_count = dataset.len().max(1) as f32;
+        let report = EpochReport {
+            epoch: epoch,
+            clean_accuracy: clean_successes as f32 / example_count,
+            robust_accuracy: robust_successes as f32 / example_count,
+        };
+        reports.push(report);
+    }
+    Ok(reports)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config_error<T>(result: Result<T, NnError>, part: &str) {
+        match result {
+            Err(NnError::Config(details)) => assert!(details.contains(part), "{}", details),
+            Err(other) => panic!("Expected an error about {}, found {:?}", part, other),
+            Ok(_) => panic!("Expected an error about {}", part),
+        }
+    }
+
+    #[test]
+    fn bad_settings_are_rejected() {
+        let network = nodes_layers::Network::new(&[3, 2], 0.1);
+        let (inputs, desired_outputs) = (vec![0.2, 0.5, 0.8], vec![1.0, 0.0]);
+        let pgd = |steps: usize, step_size: f32| Attack::Pgd {steps: steps, step_size: step_size, random_start: false};
+        for &(attack_kind, epsilon, bounds, part) in &[
+            (Attack::Fgsm, -0.1, None, "epsilon"),
+            (Attack::Fgsm, std::f32::NAN, None, "epsilon"),
+            (pgd(0, 0.1), 0.1, None, "step"),
+            (pgd(5, -0.1), 0.1, None, "step_size"),
+            (pgd(5, std::f32::NAN), 0.1, None, "step_size"),
+            (Attack::Fgsm, 0.1, Some((1.0, 0.0)), "bounds"),
+            (Attack::Fgsm, 0.1, Some((0.0, std::f32::INFINITY)), "bounds"),
+        ] {
+            config_error(attack(&network, &inputs, &desired_outputs, attack_kind, Norm::LInf, epsilon, bounds), part);
+        }
+        for &fraction in &[-0.5, 1.5, std::f32::NAN] {
+            let training = AdversarialTraining {attack: Attack::Fgsm, norm: Norm::LInf, epsilon: 0.1, adversarial_fraction: fraction, bounds: None};
+            config_error(training.check(), "adversarial_fraction");
+            let mut trained = network.clone();
+            config_error(train_step(&mut trained, &inputs, &desired_outputs, &training), "adversarial_fraction");
+        }
+    }
+
+    #[test]
+    fn results_stay_within_epsilon_and_bounds() {
+        let mut network = nodes_layers::Network::new(&[3, 4, 2], 0.1);
+        //Standardised inputs are far outside [0; 1], so clamping to it would move them by more than epsilon.
+        let rows = vec![vec![10.0, -4.0, 100.0], vec![14.0, 0.0, 140.0], vec![12.0, -8.0, 90.0]];
+        network.input_scaler = Some(crate::scaling::Scaler::fit_standard(&rows).unwrap());
+        let desired_outputs = vec![0.0, 1.0];
+        let attacks = [Attack::Fgsm, Attack::Pgd {steps: 5, step_size: 0.05, random_start: true}];
+        for &attack_kind in &attacks {
+            for &norm in &[Norm::LInf, Norm::L2] {
+                let unbounded = attack(&network, &rows[0], &desired_outputs, attack_kind, norm, 0.2, None).unwrap();
+                let distance = match norm {
+                    Norm::LInf => unbounded.iter().zip(&rows[0]).map(|(first, second)| (first - second).abs()).fold(0.0, f32::max),
+                    Norm::L2 => unbounded.iter().zip(&rows[0]).map(|(first, second)| (first - second).powi(2)).sum::<f32>().sqrt(),
+                };
+                assert!(distance <= 0.2 + 1e-4, "{:?} {:?} moved by {}", attack_kind, norm, distance);
+                let bounded = attack(&network, &rows[1], &desired_outputs, attack_kind, norm, 5.0, Some((-10.0, 10.0))).unwrap();
+                assert!(bounded.iter().all(|value| *value >= -10.0 && *value <= 10.0), "{:?}", bounded);
+            }
+        }
+    }
+}
@@ -0,0 +1,430 @@
+extern crate rand;
+
+use rand::Rng;
+use rand::distributions::{Beta, Distribution, StandardNormal};
+
+use crate::dataset;
+use crate::error::NnError;
+use crate::nodes_layers;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Augmentation {
+    //A random change to an image, made fresh every time the image is used for training.
+    //All sizes are in pixels, and all the geometric changes are centred on the middle of the image.
+    Translate {max_shift: f32}, //Moves the image by up to max_shift in each direction.
+    Rotate {max_degrees: f32}, //Rotates the image by up to max_degrees either way.
+    Scale {min_factor: f32, max_factor: f32}, //Resizes the image by a factor between the two.
+    Shear {max_shear: f32}, //Slants the image sideways, moving each row by up to max_shear times its distance from the middle.
+    Elastic {alpha: f32, sigma: f32}, //Moves each pixel by a random amount, smoothed with a Gaussian of width sigma and scaled by alpha.
+    GaussianNoise {standard_deviation: f32}, //Adds noise to every pixel.
+    RandomErasing {probability: f32, min_area: f32, max_area: f32}, //With the given probability, fills a rectangle covering a fraction of the image between min_area and max_area with noise.
+}
+
+type Affine = [f32; 6]; //[a, b, c, d, e, f] maps (x, y) to (a*x + b*y + c, d*x + e*y + f).
+
+const IDENTITY: Affine = [1.0, 0.0, 0.0, 0.0, 1.0, 0.0];
+
+impl Augmentation {
+    pub fn check(&self) -> Result<(), NnError> {
+        //Whether the settings make sense, so applying the augmentation can't go wrong later.
+        let at_least_zero = |name: &str, value: f32| {
+            if value.is_finite() && value >= 0.0 {Ok(())} else {Err(NnError::Config(format!("The {} of an augmentation must be at least 0.0, but it's {:?}", name, value)))}
+        };
+        match *self {
+            Augmentation::Translate {max_shift} => at_least_zero("max_shift", max_shift),
+            Augmentation::Rotate {max_degrees} => at_least_zero("max_degrees", max_degrees),
+            Augmentation::Scale {min_factor, max_factor} => {
+                if !(min_factor > 0.0 && min_factor <= max_factor && max_factor.is_finite()) {
+                    return Err(NnError::Config(format!("The factors of a scaling must be above 0.0 with the smallest first, but they're {:?} and {:?}", min_factor, max_factor)))
+                }
+                Ok(())
+            },
+            Augmentation::Shear {max_shear} => at_least_zero("max_shear", max_shear),
+            Augmentation::Elastic {alpha, sigma} => {
+                if !alpha.is_finite() {
+                    return Err(NnError::Config(format!("The alpha of an elastic distortion must be a number, but it's {:?}", alpha)))
+                }
+                at_least_zero("sigma", sigma)
+            },
+            Augmentation::GaussianNoise {standard_deviation} => at_least_zero("standard_deviation", standard_deviation),
+            Augmentation::RandomErasing {probability, min_area, max_area} => {
+                if !(probability >= 0.0 && probability <= 1.0) {
+                    return Err(NnError::Config(format!("The probability of random erasing must be between 0.0 and 1.0, but it's {:?}", probability)))
+                }
+                if !(min_area >= 0.0 && min_area <= max_area && max_area <= 1.0) {
+                    return Err(NnError::Config(format!("The areas of random erasing must be between 0.0 and 1.0 with the smallest first, but they're {:?} and {:?}", min_area, max_area)))
+                }
+                Ok(())
+            },
+        }
+    }
+
+    fn random_affine<R: Rng>(&self, rng: &mut R) -> Option<Affine> {
+        //The geometric changes are turned into affine maps, so several in a row can be combined and the image only resampled once.
+        match *self {
+            Augmentation::Translate {max_shift} => {
+                Some([1.0, 0.0, uniform(rng, -max_shift, max_shift), 0.0, 1.0, uniform(rng, -max_shift, max_shift)])
+            },
+            Augmentation::Rotate {max_degrees} => {
+                let angle = uniform(rng, -max_degrees, max_degrees).to_radians();
+                Some([angle.cos(), -angle.sin(), 0.0, angle.sin(), angle.cos(), 0.0])
+            },
+            Augmentation::Scale {min_factor, max_factor} => {
+                let factor = uniform(rng, min_factor, max_factor);
+                Some([factor, 0.0, 0.0, 0.0, factor, 0.0])
+            },
+            Augmentation::Shear {max_shear} => {
+                Some([1.0, uniform(rng, -max_shear, max_shear), 0.0, 0.0, 1.0, 0.0])
+            },
+            _ => None,
+        }
+    }
+
+    pub fn apply<R: Rng>(&self, image: &[f32], shape: dataset::Shape, rng: &mut R) -> Result<Vec<f32>, NnError> {
+        //Applies the augmentation on its own to an image of the given shape. Every channel is changed the same way.
+        self.check()?;
+        check_image(image, shape)?;
+        Ok(self.apply_checked(image, shape, rng))
+    }
+
+    fn apply_checked<R: Rng>(&self, image: &[f32], shape: dataset::Shape, rng: &mut R) -> Vec<f32> {
+        //Works like apply, for an augmentation and image that have already been checked.
+        if let Some(affine) = self.random_affine(rng) {
+            return warp_affine(image, shape, affine)
+        }
+        let (width, height) = (shape.width, shape.height);
+        let plane = width * height;
+        match *self {
+            Augmentation::Elastic {alpha, sigma} => {
+                let mut x_shifts: Vec<f32> = (0..width*height).map(|_| rng.gen_range(-1.0, 1.0)).collect();
+                let mut y_shifts: Vec<f32> = (0..width*height).map(|_| rng.gen_range(-1.0, 1.0)).collect();
+                x_shifts = gaussian_blur(&x_shifts, width, height, sigma);
+                y_shifts = gaussian_blur(&y_shifts, width, height, sigma);
+                let mut output = Vec::new();
+                for channel in 0..shape.channels {
+                    let channel_image = &image[channel*plane..(channel + 1)*plane];
+                    for y in 0..height {
+                        for x in 0..width {
+                            let pos = y*width + x;
+                            output.push(sample(channel_image, width, height, x as f32 + alpha * x_shifts[pos], y as f32 + alpha * y_shifts[pos]));
+                        }
+                    }
+                }
+                output
+            },
+            Augmentation::GaussianNoise {standard_deviation} => {
+                image.iter().map(|value| (value + rng.sample(StandardNormal) as f32 * standard_deviation).max(0.0).min(1.0)).collect()
+            },
+            Augmentation::RandomErasing {probability, min_area, max_area} => {
+                let mut output = image.to_vec();
+                if rng.gen::<f32>() >= probability {
+                    return output
+                }
+                let area = uniform(rng, min_area, max_area) * (width * height) as f32;
+                let aspect_ratio = rng.gen_range(0.3f32.ln(), 3.3f32.ln()).exp();
+                let erase_width = ((area * aspect_ratio).sqrt().round() as usize).max(1).min(width);
+                let erase_height = ((area / aspect_ratio).sqrt().round() as usize).max(1).min(height);
+                let left = rng.gen_range(0, width - erase_width + 1);
+                let top = rng.gen_range(0, height - erase_height + 1);
+                for channel in 0..shape.channels {
+                    for y in top..top+erase_height {
+                        for x in left..left+erase_width {
+                            output[channel*plane + y*width + x] = rng.gen();
+                        }
+                    }
+                }
+                output
+            },
+            _ => image.to_vec(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Pipeline {
+    //A series of augmentations applied one after the other, with optional mixup of pairs of examples at the end.
+    //The fields are only set through then and with_mixup, so every augmentation in a pipeline has been checked.
+    augmentations: Vec<Augmentation>,
+    mixup: Option<f32>, //If there is one, examples are blended with another example, with a share drawn from a Beta(alpha, alpha) distribution.
+    shape: dataset::Shape, //The shape of the images the pipeline is used on.
+}
+
+impl Pipeline {
+    pub fn new(shape: dataset::Shape) -> Pipeline {
+        //An empty pipeline for images of the given shape. Add to it with then and with_mixup.
+        Pipeline {
+            augmentations: Vec::new(),
+            mixup: None,
+            shape: shape,
+        }
+    }
+
+    pub fn then(mut self, augmentation: Augmentation) -> Result<Pipeline, NnError> {
+        augmentation.check()?;
+        self.augmentations.push(augmentation);
+        Ok(self)
+    }
+
+    pub fn with_mixup(mut self, alpha: f32) -> Result<Pipeline, NnError> {
+        if !(alpha > 0.0 && alpha.is_finite()) {
+            return Err(NnError::Config(format!("The alpha of mixup must be above 0.0, but it's {:?}", alpha)))
+        }
+        self.mixup = Some(alpha);
+        Ok(self)
+    }
+
+    pub fn augmentations(&self) -> &[Augmentation] {
+        &self.augmentations
+    }
+
+    pub fn mixup(&self) -> Option<f32> {
+        self.mixup
+    }
+
+    pub fn shape(&self) -> dataset::Shape {
+        self.shape
+    }
+
+    pub fn apply<R: Rng>(&self, image: &[f32], rng: &mut R) -> Result<Vec<f32>, NnError> {
+        //Applies every augmentation to an image. Geometric augmentations next to each other are combined before the image is resampled.
+        check_image(image, self.shape)?;
+        let mut output = image.to_vec();
+        let mut pending = IDENTITY;
+        let mut has_pending = false;
+        for augmentation in &self.augmentations {
+            match augmentation.random_affine(rng) {
+                Some(affine) => {
+                    pending = compose(affine, pending);
+                    has_pending = true;
+                },
+                None => {
+                    if has_pending {
+                        output = warp_affine(&output, self.shape, pending);
+                        pending = IDENTITY;
+                        has_pending = false;
+                    }
+                    output = augmentation.apply_checked(&output, self.shape, rng);
+                },
+            }
+        }
+        if has_pending {
+            output = warp_affine(&output, self.shape, pending);
+        }
+        Ok(output)
+    }
+
+    pub fn apply_pair<R: Rng>(&self, image: &[f32], label: &[f32], other_image: &[f32], other_label: &[f32], rng: &mut R) -> Result<(Vec<f32>, Vec<f32>), NnError> {
+        //Augments both examples, then blends them together if the pipeline has mixup. The labels are blended by the same share.
+        //Without mixup, the other example is ignored.
+        let augmented = self.apply(image, rng)?;
+        let alpha = match self.mixup {
+            Some(alpha) => alpha,
+            None => return Ok((augmented, label.to_vec())),
+        };
+        if other_label.len() != label.len() {
+            return Err(NnError::shape_mismatch("the label of the mixup partner", label.len(), other_label.len()))
+        }
+        let other_augmented = self.apply(other_image, rng)?;
+        let share = Beta::new(alpha as f64, alpha as f64).sample(rng) as f32;
+        let mixed_image = (0..augmented.len()).map(|num| share * augmented[num] + (1.0 - share) * other_augmented[num]).collect();
+        let mixed_label = (0..label.len()).map(|num| share * label[num] + (1.0 - share) * other_label[num]).collect();
+        Ok((mixed_image, mixed_label))
+    }
+}
+
+pub fn partner_index<R: Rng>(num: usize, example_count: usize, rng: &mut R) -> usize {
+    //A random example other than num to mix it up with. With a single example, there's nothing else to pick.
+    if example_count < 2 {
+        return num
+    }
+    let partner = rng.gen_range(0, example_count - 1);
+    if partner >= num {partner + 1} else {partner}
+}
+
+pub fn train_epoch<R: Rng>(network: &mut nodes_layers::Network, dataset: &dyn dataset::Dataset, pipeline: &Pipeline, rng: &mut R) -> Result<(), NnError> {
+    //Trains on every example once, augmented by the pipeline. Mixup partners are picked at random from the rest of the examples.
+    for num in 0..dataset.len() {
+        let (image, label) = dataset.get(num);
+        let (other_image, other_label) = dataset.get(partner_index(num, dataset.len(), rng));
+        let (image, label) = pipeline.apply_pair(&image, &label, &other_image, &other_label, rng)?;
+        network.find_make_adjust(&image, &label)?;
+    }
+    Ok(())
+}
+
+fn check_image(image: &[f32], shape: dataset::Shape) -> Result<(), NnError> {
+    //The augmentations work on whole planes of the image, so it must be exactly the size of the shape.
+    if image.len() != shape.size() {
+        return Err(NnError::shape_mismatch("the image being augmented", shape.size(), image.len()))
+    }
+    Ok(())
+}
+
+fn uniform<R: Rng>(rng: &mut R, low: f32, high: f32) -> f32 {
+    //A random value between low and high. gen_range can't take an empty range, so when they're the same, that's the value.
+    if low < high {rng.gen_range(low, high)} else {low}
+}
+
+fn compose(second: Affine, first: Affine) -> Affine {
+    //The affine map that does first, then second.
+    [
+        second[0]*first[0] + second[1]*first[3],
+        second[0]*first[1] + second[1]*first[4],
+        second[0]*first[2] + second[1]*first[5] + second[2],
+        second[3]*first[0] + second[4]*first[3],
+        second[3]*first[1] + second[4]*first[4],
+        second[3]*first[2] + second[4]*first[5] + second[5],
+    ]
+}
+
+fn warp_affine(image: &[f32], shape: dataset::Shape, affine: Affine) -> Vec<f32> {
+    //Moves the image by the affine map around its centre. Each output pixel is found by mapping it back to where it came from.
+    let determinant = affine[0]*affine[4] - affine[1]*affine[3];
+    if determinant.abs() < 1e-6 {
+        return image.to_vec()
+    }
+    let inverse = [
+        affine[4] / determinant, -affine[1] / determinant,
+        -affine[3] / determinant, affine[0] / determinant,
+    ];
+    let (width, height) = (shape.width, shape.height);
+    let centre_x = (width as f32 - 1.0) / 2.0;
+    let centre_y = (height as f32 - 1.0) / 2.0;
+    let mut output = Vec::new();
+    for channel in 0..shape.channels {
+        let channel_image = &image[channel*width*height..(channel + 1)*width*height];
+        for y in 0..height {
+            for x in 0..width {
+                let moved_x = x as f32 - centre_x - affine[2];
+                let moved_y = y as f32 - centre_y - affine[5];
+                let source_x = inverse[0]*moved_x + inverse[1]*moved_y + centre_x;
+                let source_y = inverse[2]*moved_x + inverse[3]*moved_y + centre_y;
+                output.push(sample(channel_image, width, height, source_x, source_y));
+            }
+        }
+    }
+    output
+}
+
+fn sample(image: &[f32], width: usize, height: usize, x: f32, y: f32) -> f32 {
+    //Bilinear interpolation between the four nearest pixels. Everything outside the image counts as 0.0.
+    let left = x.floor();
+    let top = y.floor();
+    let x_share = x - left;
+    let y_share = y - top;
+    let pixel = |px: f32, py: f32| {
+        if px < 0.0 || py < 0.0 || px >= width as f32 || py >= height as f32 {
+            0.0
+        } else {
+            image[py as usize * width + px as usize]
+        }
+    };
+    pixel(left, top) * (1.0 - x_share) * (1.0 - y_share)
+        + pixel(left + 1.0, top) * x_share * (1.0 - y_share)
+        + pixel(left, top + 1.0) * (1.0 - x_share) * y_share
+        + pixel(left + 1.0, top + 1.0) * x_share * y_share
+}
+
+fn gaussian_blur(values: &[f32], width: usize, height: usize, sigma: f32) -> Vec<f32> {
+    //Blurs horizontally and then vertically, with the kernel cut off at three sigma. A sigma of 0.0 or less doesn't blur at all.
+    if !(sigma > 0.0) {
+        return values.to_vec()
+    }
+    let radius = (3.0 * sigma).ceil().max(1.0) as isize;
+    let mut kernel: Vec<f32> = (-radius..=radius).map(|offset| (-(offset as f32).powi(2) / (2.0 * sigma.powi(2))).exp()).collect();
+    let kernel_sum: f32 = kernel.iter().sum();
+    for weight in kernel.iter_mut() {
+        *weight /= kernel_sum;
+    }
+    let blur_pass = |input: &[f32], horizontal: bool| {
+        let mut output = vec![0.0; input.len()];
+        for y in 0..height as isize {
+            for x in 0..width as isize {
+                let mut total = 0.0;
+                for offset in -radius..=radius {
+                    let (sx, sy) = if horizontal {(x + offset, y)} else {(x, y + offset)};
+                    if sx >= 0 && sy >= 0 && sx < width as isize && sy < height as isize {
+                        total += kernel[(offset + radius) as usize] * input[(sy * width as isize + sx) as usize];
+                    }
+                }
+                output[(y * width as isize + x) as usize] = total;
+            }
+        }
+        output
+    };
+    let horizontal = blur_pass(values, true);
+    blur_pass(&horizontal, false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+    use rand::rngs::StdRng;
+
+    fn gradient_image(shape: dataset::Shape) -> Vec<f32> {
+        (0..shape.size()).map(|pos| pos as f32 / shape.size() as f32).collect()
+    }
+
+    #[test]
+    fn wrong_image_size_is_an_error() {
+        let shape = dataset::Shape::image(3, 4, 5);
+        let mut rng = StdRng::seed_from_u64(1);
+        let short_image = vec![0.5; shape.size() - 1];
+        let augmentation = Augmentation::Elastic {alpha: 2.0, sigma: 1.0};
+        assert!(augmentation.apply(&short_image, shape, &mut rng).is_err());
+        let pipeline = Pipeline::new(shape).then(Augmentation::Rotate {max_degrees: 10.0}).unwrap();
+        assert!(pipeline.apply(&short_image, &mut rng).is_err());
+        assert!(pipeline.apply(&gradient_image(shape), &mut rng).is_ok());
+    }
+
+    #[test]
+    fn invalid_settings_are_rejected() {
+        let shape = dataset::Shape::image(1, 4, 4);
+        let mut rng = StdRng::seed_from_u64(2);
+        assert!(Pipeline::new(shape).then(Augmentation::Translate {max_shift: -1.0}).is_err());
+        assert!(Pipeline::new(shape).with_mixup(0.0).is_err());
+        let augmentation = Augmentation::Scale {min_factor: 2.0, max_factor: 1.0};
+        assert!(augmentation.apply(&gradient_image(shape), shape, &mut rng).is_err());
+    }
+
+    #[test]
+    fn empty_ranges_give_their_value() {
+        let shape = dataset::Shape::image(1, 5, 5);
+        let image = gradient_image(shape);
+        let mut rng = StdRng::seed_from_u64(3);
+        let unchanged = Pipeline::new(shape)
+            .then(Augmentation::Translate {max_shift: 0.0})
+            .and_then(|pipeline| pipeline.then(Augmentation::Scale {min_factor: 1.0, max_factor: 1.0}))
+            .unwrap();
+        let output = unchanged.apply(&image, &mut rng).unwrap();
+        for pos in 0..image.len() {
+            assert!((output[pos] - image[pos]).abs() < 1e-6);
+        }
+        let elastic = Augmentation::Elastic {alpha: 1.0, sigma: 0.0};
+        assert!(elastic.apply(&image, shape, &mut rng).unwrap().iter().all(|value| value.is_finite()));
+    }
+
+    #[test]
+    fn mixup_partner_is_another_example() {
+        let mut rng = StdRng::seed_from_u64(4);
+        for num in 0..5 {
+            for _ in 0..100 {
+                let partner = partner_index(num, 5, &mut rng);
+                assert!(partner < 5 && partner != num);
+            }
+        }
+        assert_eq!(partner_index(0, 1, &mut rng), 0);
+    }
+
+    #[test]
+    fn mixup_blends_labels_by_the_image_share() {
+        let shape = dataset::Shape::image(1, 2, 2);
+        let mut rng = StdRng::seed_from_u64(5);
+        let pipeline = Pipeline::new(shape).with_mixup(0.4).unwrap();
+        let (image, label) = pipeline.apply_pair(&[1.0; 4], &[1.0, 0.0], &[0.0; 4], &[0.0, 1.0], &mut rng).unwrap();
+        assert!((image[0] - label[0]).abs() < 1e-6);
+        assert!((label[0] + label[1] - 1.0).abs() < 1e-6);
+        assert!(pipeline.apply_pair(&[1.0; 4], &[1.0, 0.0], &[0.0; 4], &[0.0, 1.0, 0.0], &mut rng).is_err());
+    }
+}
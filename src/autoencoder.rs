@@ -0,0 +1,127 @@
+extern crate rand;
+
+use rand::Rng;
+use rand::distributions::StandardNormal;
+
+use crate::error::NnError;
+use crate::nodes_layers;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Corruption {
+    //Noise put on the inputs during training. Training to undo it makes a denoising autoencoder.
+    Nothing, //The inputs are used as they are, making a plain autoencoder.
+    Masking(f32), //Each input is set to 0.0 with the given probability.
+    Gaussian(f32), //Noise with the given standard deviation is added to each input, and the result is clamped to [0; 1].
+    SaltAndPepper(f32), //Each input is set to either 0.0 or 1.0 with the given probability.
+}
+
+impl Corruption {
+    pub fn apply(&self, inputs: &[f32]) -> Vec<f32> {
+        //Returns a corrupted copy of the inputs.
+        let mut rng = rand::thread_rng();
+        let mut output = inputs.to_vec();
+        match *self {
+            Corruption::Nothing => {},
+            Corruption::Masking(probability) => {
+                for value in output.iter_mut() {
+                    if rng.gen::<f32>() < probability {
+                        *value = 0.0;
+                    }
+                }
+            },
+            Corruption::Gaussian(standard_deviation) => {
+                for value in output.iter_mut() {
+                    let noise = rng.sample(StandardNormal) as f32 * standard_deviation;
+                    *value = (*value + noise).max(0.0).min(1.0);
+                }
+            },
+            Corruption::SaltAndPepper(probability) => {
+                for value in output.iter_mut() {
+                    if rng.gen::<f32>() < probability {
+                        *value = if rng.gen::<bool>() {1.0} else {0.0};
+                    }
+                }
+            },
+        }
+        output
+    }
+}
+
+#[derive(Clone)]
+pub struct Autoencoder {
+    //A network that is trained to give back its own inputs, squeezing them through a small code layer on the way.
+    pub network: nodes_layers::Network,
+    pub code_layer: usize, //The position in network.layers of the layer whose values are the code. The layers up to and including it are the encoder, the rest are the decoder.
+    pub corruption: Corruption,
+}
+
+impl Autoencoder {
+    pub fn new(encoder_nums: &[usize], learning_rate: f32, corruption: Corruption) -> Result<Autoencoder, NnError> {
+        //Makes a new autoencoder with random weights and biases.
+        //encoder_nums works like node_nums for nodes_layers::Network::new, starting with the input layer and ending with the code layer.
+        //The decoder is made as a mirror image of the encoder, so the network ends with as many outputs as it has inputs.
+        if encoder_nums.len() < 2 {
+            return Err(NnError::Config(format!("An encoder needs an input layer and a code layer, but it has {:?} layers", encoder_nums.len())))
+        }
+        let mut node_nums = encoder_nums.to_vec();
+        for num in (0..encoder_nums.len()-1).rev() {
+            node_nums.push(encoder_nums[num]);
+        }
+        Ok(Autoencoder {
+            network: nodes_layers::Network::new(&node_nums, learning_rate),
+            code_layer: encoder_nums.len() - 2,
+            corruption: corruption,
+        })
+    }
+
+    pub fn from_network(network: nodes_layers::Network, code_layer: usize, corruption: Corruption) -> Result<Autoencoder, NnError> {
+        //Wraps a network that was loaded with inout::read_network. The code layer can't be the output layer, since the decoder needs a layer of its own.
+        if code_layer + 1 >= network.layer_count {
+            return Err(NnError::Config(format!("The code layer {:?} leaves no decoder in a network of {:?} layers", code_layer, network.layer_count)))
+        }
+        Ok(Autoencoder {
+            network: network,
+            code_layer: code_layer,
+            corruption: corruption,
+        })
+    }
+
+    pub fn train(&mut self, inputs: &Vec<f32>) -> Result<(), NnError> {
+        //Trains on a single example. The corrupted inputs are fed in, but the clean ones are the desired outputs.
+        let corrupted = self.corruption.apply(inputs);
+        self.network.find_make_adjust(&corrupted, inputs)
+    }
+
+    pub fn encode(&self, inputs: &Vec<f32>) -> Result<Vec<f32>, NnError> {
+        //The values of the code layer.
+        Ok(self.network.calculate(inputs)?.swap_remove(self.code_layer))
+    }
+
+    pub fn reconstruct(&self, inputs: &Vec<f32>) -> Result<Vec<f32>, NnError> {
+        //Runs the inputs through both halves, giving the autoencoder's attempt at recreating them.
+        self.network.predict(inputs)
+    }
+
+    pub fn reconstruction_error(&self, inputs: &Vec<f32>) -> Result<f32, NnError> {
+        //The mean squared difference between the inputs and their reconstruction.
+        let reconstruction = self.reconstruct(inputs)?;
+        if reconstruction.len() != inputs.len() {
+            return Err(NnError::shape_mismatch("the reconstruction", inputs.len(), reconstruction.len()))
+        }
+        let mut error = 0.0;
+        for num in 0..inputs.len() {
+            error += (inputs[num] - reconstruction[num]).powi(2);
+        }
+        Ok(error / inputs.len() as f32)
+    }
+
+    pub fn encoder(&self) -> Result<nodes_layers::Network, NnError> {
+        //A copy of the layers that turn inputs into codes.
+        Ok(self.network.split_at(self.code_layer)?.0)
+    }
+
+    pub fn decoder(&self) -> Result<nodes_layers::Network, NnError> {
+        //A copy of the layers that turn codes back into inputs.
+        Ok(self.network.split_at(self.code_layer)?.1)
+    }
+}
@@ -0,0 +1,109 @@
+use rand::SeedableRng;
+use rand::rngs::StdRng;
+
+use std::str::FromStr;
+
+use numbers_nn::error::NnError;
+use numbers_nn::idx_edit::LabelledImages;
+
+const USAGE: &str = "Usage:
+    idx_tool info <images> <labels>
+    idx_tool subset <images> <labels> <start> <count> <out-images> <out-labels>
+    idx_tool classes <images> <labels> <class,class,...> <out-images> <out-labels>
+    idx_tool merge <images> <labels> <other-images> <other-labels> <out-images> <out-labels>
+    idx_tool split <images> <labels> <ratio> <first-images> <first-labels> <second-images> <second-labels> [seed=<number>]
+    idx_tool split-class <images> <labels> <class,class,...> <chosen-images> <chosen-labels> <other-images> <other-labels>
+    idx_tool relabel <images> <labels> <old:new,old:new,...> <out-images> <out-labels>
+Files ending with .gz are read and written compressed.";
+
+fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    if args.len() < 4 {
+        exit_with_usage()
+    }
+    let data = match LabelledImages::read(&args[2], &args[3]) {
+        Ok(data) => data,
+        Err(error) => {
+            println!("Issue with reading {:?} and {:?}: {}", args[2], args[3], error);
+            std::process::exit(1)
+        },
+    };
+    let result = match (args[1].as_str(), args.len()) {
+        ("info", 4) => {
+            println!("{:?} images with the dimensions {:?}, holding {:?} values", data.len(), data.images.item_dimensions(), data.images.element_type());
+            for (label, count) in data.class_counts() {
+                println!("Label {:?}: {:?} images", label, count);
+            }
+            Ok(())
+        },
+        ("subset", 8) => {
+            parse(&args[4], "start")
+                .and_then(|start| Ok((start, parse(&args[5], "count")?)))
+                .and_then(|(start, count)| data.range(start, count))
+                .and_then(|subset| subset.write(&args[6], &args[7]))
+        },
+        ("classes", 7) => {
+            parse_classes(&args[4])
+                .and_then(|classes| data.split_by_class(&classes))
+                .and_then(|(chosen, _)| chosen.write(&args[5], &args[6]))
+        },
+        ("merge", 8) => {
+            LabelledImages::read(&args[4], &args[5])
+                .and_then(|other| data.merge(&other))
+                .and_then(|merged| merged.write(&args[6], &args[7]))
+        },
+        ("split", 9) | ("split", 10) => {
+            let seed = match args.get(9) {
+                Some(arg) if arg.starts_with("seed=") => parse(&arg["seed=".len()..], "seed"),
+                Some(arg) => Err(NnError::Config(format!("Unknown argument {:?}", arg))),
+                None => Ok(0),
+            };
+            parse(&args[4], "ratio")
+                .and_then(|ratio| Ok((ratio, seed?)))
+                .and_then(|(ratio, seed)| data.shuffled(&mut StdRng::seed_from_u64(seed))?.split_by_ratio(ratio))
+                .and_then(|(first, second)| first.write(&args[5], &args[6]).and_then(|_| second.write(&args[7], &args[8])))
+        },
+        ("split-class", 9) => {
+            parse_classes(&args[4])
+                .and_then(|classes| data.split_by_class(&classes))
+                .and_then(|(chosen, others)| chosen.write(&args[5], &args[6]).and_then(|_| others.write(&args[7], &args[8])))
+        },
+        ("relabel", 7) => {
+            parse_mapping(&args[4]).and_then(|mapping| data.relabel(&mapping).write(&args[5], &args[6]))
+        },
+        _ => exit_with_usage(),
+    };
+    if let Err(error) = result {
+        println!("Something went wrong: {}", error);
+        if let NnError::Config(_) = error {
+            println!("{}", USAGE);
+        }
+        std::process::exit(1)
+    }
+}
+
+fn exit_with_usage() -> ! {
+    println!("{}", USAGE);
+    std::process::exit(2)
+}
+
+fn parse<T: FromStr>(arg: &str, what: &str) -> Result<T, NnError> {
+    arg.trim().parse().or_else(|_| Err(NnError::Config(format!("Invalid {}: {:?}", what, arg))))
+}
+
+fn parse_classes(arg: &str) -> Result<Vec<u8>, NnError> {
+    arg.split(',').map(|label| parse(label, "label")).collect()
+}
+
+fn parse_mapping(arg: &str) -> Result<Vec<(u8, u8)>, NnError> {
+    //Pairs of labels written as old:new, separated by commas.
+    let mut mapping = Vec::new();
+    for pair in arg.split(',') {
+        let labels: Vec<&str> = pair.split(':').collect();
+        if labels.len() != 2 {
+            return Err(NnError::Config(format!("Expected old:new, found {:?}", pair)))
+        }
+        mapping.push((parse(labels[0], "label")?, parse(labels[1], "label")?));
+    }
+    Ok(mapping)
+}
@@ -0,0 +1,156 @@
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::dataset;
+use crate::error::NnError;
+use crate::labels;
+
+pub const WIDTH: usize = 32;
+pub const HEIGHT: usize = 32;
+pub const CHANNELS: usize = 3;
+const IMAGE_SIZE: usize = WIDTH * HEIGHT * CHANNELS;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CifarKind {
+    //The CIFAR datasets in their binary versions, which differ in how many label bytes each image has.
+    Cifar10, //One label byte, for 10 classes.
+    Cifar100Fine, //Two label bytes, using the second, for 100 classes.
+    Cifar100Coarse, //Two label bytes, using the first, for the 20 superclasses the 100 classes are grouped in.
+}
+
+const CIFAR10_NAMES: [&str; 10] = ["airplane", "automobile", "bird", "cat", "deer", "dog", "frog", "horse", "ship", "truck"];
+
+const CIFAR100_FINE_NAMES: [&str; 100] = [
+    "apple", "aquarium_fish", "baby", "bear", "beaver", "bed", "bee", "beetle", "bicycle", "bottle",
+    "bowl", "boy", "bridge", "bus", "butterfly", "camel", "can", "castle", "caterpillar", "cattle",
+    "chair", "chimpanzee", "clock", "cloud", "cockroach", "couch", "crab", "crocodile", "cup", "dinosaur",
+    "dolphin", "elephant", "flatfish", "forest", "fox", "girl", "hamster", "house", "kangaroo", "keyboard",
+    "lamp", "lawn_mower", "leopard", "lion", "lizard", "lobster", "man", "maple_tree", "motorcycle", "mountain",
+    "mouse", "mushroom", "oak_tree", "orange", "orchid", "otter", "palm_tree", "pear", "pickup_truck", "pine_tree",
+    "plain", "plate", "poppy", "porcupine", "possum", "rabbit", "raccoon", "ray", "road", "rocket",
+    "rose", "sea", "seal", "shark", "shrew", "skunk", "skyscraper", "snail", "snake", "spider",
+    "squirrel", "streetcar", "sunflower", "sweet_pepper", "table", "tank", "telephone", "television", "tiger", "tractor",
+    "train", "trout", "tulip", "turtle", "wardrobe", "whale", "willow_tree", "wolf", "woman", "worm",
+];
+
+const CIFAR100_COARSE_NAMES: [&str; 20] = [
+    "aquatic_mammals", "fish", "flowers", "food_containers", "fruit_and_vegetables",
+    "household_electrical_devices", "household_furniture", "insects", "large_carnivores", "large_man-made_outdoor_things",
+    "large_natural_outdoor_scenes", "large_omnivores_and_herbivores", "medium_mammals", "non-insect_invertebrates", "people",
+    "reptiles", "small_mammals", "trees", "vehicles_1", "vehicles_2",
+];
+
+impl CifarKind {
+    pub fn label_bytes(&self) -> usize {
+        match self {
+            CifarKind::Cifar10 => 1,
+            CifarKind::Cifar100Fine | CifarKind::Cifar100Coarse => 2,
+        }
+    }
+
+    pub fn file_names(&self, is_training: bool) -> Vec<String> {
+        //The batch files as they're unpacked from the binary version's archive.
+        match (self, is_training) {
+            (CifarKind::Cifar10, true) => (1..=5).map(|num| format!("data_batch_{}.bin", num)).collect(),
+            (CifarKind::Cifar10, false) => vec![String::from("test_batch.bin")],
+            (_, true) => vec![String::from("train.bin")],
+            (_, false) => vec![String::from("test.bin")],
+        }
+    }
+
+    pub fn label_map(&self) -> labels::LabelMap {
+        let names: Vec<String> = match self {
+            CifarKind::Cifar10 => CIFAR10_NAMES.iter().map(|name| name.to_string()).collect(),
+            CifarKind::Cifar100Fine => CIFAR100_FINE_NAMES.iter().map(|name| name.to_string()).collect(),
+            CifarKind::Cifar100Coarse => CIFAR100_COARSE_NAMES.iter().map(|name| name.to_string()).collect(),
+        };
+        labels::LabelMap {
+            keys: labels::LabelMap::numbered(names.len()).keys,
+            names: names,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum CifarError {
+    Io(io::Error),
+    PartialRecord {length: usize, record_size: usize}, //The file's length isn't a whole number of records.
+    UnknownLabel(u8), //A label bigger than the number of classes.
+}
+
+impl fmt::Display for CifarError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CifarError::Io(error) => write!(f, "Couldn't read the CIFAR file: {}", error),
+            CifarError::PartialRecord {length, record_size} => write!(f, "The CIFAR file is {:?} bytes long, which isn't a whole number of {:?} byte records", length, record_size),
+            CifarError::UnknownLabel(label) => write!(f, "The label {:?} isn't one of the known classes", label),
+        }
+    }
+}
+
+impl Error for CifarError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            CifarError::Io(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for CifarError {
+    fn from(error: io::Error) -> CifarError {
+        CifarError::Io(error)
+    }
+}
+
+pub fn parse_batch(bytes: &[u8], kind: CifarKind) -> Result<(Vec<Vec<f32>>, Vec<u8>), CifarError> {
+    //Each record is the label bytes followed by a 32x32 image, stored as its red, green and blue channels one after the other.
+    //That's the channel by channel layout dataset::Shape expects, so the pixels are only turned from 0 to 255 into 0.0 to 1.0.
+    let record_size = kind.label_bytes() + IMAGE_SIZE;
+    if bytes.len() % record_size != 0 {
+        return Err(CifarError::PartialRecord {length: bytes.len(), record_size: record_size})
+    }
+    let label_pos = if kind == CifarKind::Cifar100Fine {1} else {0};
+    let mut images = Vec::new();
+    let mut labels = Vec::new();
+    for record in bytes.chunks_exact(record_size) {
+        labels.push(record[label_pos]);
+        images.push(record[kind.label_bytes()..].iter().map(|&value| value as f32 / 255.0).collect());
+    }
+    Ok((images, labels))
+}
+
+pub fn read_batch(filename: &str, kind: CifarKind) -> Result<(Vec<Vec<f32>>, Vec<u8>), CifarError> {
+    parse_batch(&fs::read(filename)?, kind)
+}
+
+pub fn read(folder: &Path, kind: CifarKind, is_training: bool) -> Result<(Vec<Vec<f32>>, Vec<u8>), CifarError> {
+    //Reads every batch of the training or test set in the folder.
+    let mut images = Vec::new();
+    let mut labels = Vec::new();
+    for file_name in kind.file_names(is_training) {
+        let (mut batch_images, mut batch_labels) = read_batch(&folder.join(file_name).to_string_lossy(), kind)?;
+        images.append(&mut batch_images);
+        labels.append(&mut batch_labels);
+    }
+    Ok((images, labels))
+}
+
+pub fn load(folder: &Path, kind: CifarKind, is_training: bool) -> Result<dataset::InMemoryDataset, NnError> {
+    let (images, labels) = read(folder, kind, is_training)?;
+    let label_map = kind.label_map();
+    for &label in &labels {
+        if label as usize >= label_map.class_count() {
+            return Err(NnError::Cifar(CifarError::UnknownLabel(label)))
+        }
+    }
+    let targets = label_map.to_results(&labels)?;
+    dataset::InMemoryDataset::new(images, targets, shape(), label_map)
+}
+
+pub fn shape() -> dataset::Shape {
+    dataset::Shape::image(CHANNELS, HEIGHT, WIDTH)
+}
@@ -0,0 +1,276 @@
+extern crate rand;
+
+use rand::Rng;
+use rand::seq::SliceRandom;
+
+use crate::error::NnError;
+use crate::labels;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Shape {
+    //The shape of a single input. Inputs are stored flat, channel by channel, and row by row within each channel.
+    pub channels: usize,
+    pub height: usize,
+    pub width: usize,
+}
+
+impl Shape {
+    pub fn image(channels: usize, height: usize, width: usize) -> Shape {
+        Shape {
+            channels: channels,
+            height: height,
+            width: width,
+        }
+    }
+
+    pub fn flat(size: usize) -> Shape {
+        //The shape of inputs that aren't images, like a row of a table.
+        Shape::image(1, 1, size)
+    }
+
+    pub fn size(&self) -> usize {
+        //The number of values in an input of this shape.
+        self.channels * self.height * self.width
+    }
+}
+
+pub trait Dataset {
+    //Examples that can be fetched by their position, each an input and its desired output.
+    //The adapters below (subset, concat, shuffled, map, split, batches) can be combined to make new datasets out of existing ones.
+    fn len(&self) -> usize;
+
+    fn get(&self, index: usize) -> (Vec<f32>, Vec<f32>);
+
+    fn shape(&self) -> Shape;
+
+    fn label_map(&self) -> &labels::LabelMap;
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn class_names(&self) -> Vec<String> {
+        self.label_map().names.clone()
+    }
+
+    fn subset(self, indices: Vec<usize>) -> Result<Subset<Self>, NnError> where Self: Sized {
+        //Only the examples at the given positions, in the given order.
+        Subset::new(self, indices)
+    }
+
+    fn concat<D: Dataset>(self, other: D) -> Result<Concat<Self, D>, NnError> where Self: Sized {
+        //The examples of this dataset followed by those of the other one.
+        Concat::new(self, other)
+    }
+
+    fn shuffled<R: Rng>(self, rng: &mut R) -> Subset<Self> where Self: Sized {
+        //The same examples in a random order.
+        let mut indices: Vec<usize> = (0..self.len()).collect();
+        indices.shuffle(rng);
+        Subset {
+            inner: self,
+            indices: indices,
+        }
+    }
+
+    fn map<F: Fn(Vec<f32>, Vec<f32>) -> (Vec<f32>, Vec<f32>)>(self, transform: F) -> Map<Self, F> where Self: Sized {
+        //Changes every example as it's fetched. The transform must keep inputs in the same shape.
+        Map {
+            inner: self,
+            transform: transform,
+        }
+    }
+
+    fn split(&self, fraction: f32) -> (Subset<&Self>, Subset<&Self>) where Self: Sized {
+        //Splits the examples in order, with the first part getting the given fraction of them. Shuffle first for a random split.
+        let split_point = ((self.len() as f32 * fraction).round() as usize).min(self.len());
+        let first = Subset {
+            inner: self,
+            indices: (0..split_point).collect(),
+        };
+        let second = Subset {
+            inner: self,
+            indices: (split_point..self.len()).collect(),
+        };
+        (first, second)
+    }
+
+    fn batches(&self, batch_size: usize) -> Batches<'_, Self> where Self: Sized {
+        //Goes through the examples in groups of batch_size. The last group can be smaller.
+        Batches {
+            dataset: self,
+            batch_size: batch_size.max(1),
+            position: 0,
+        }
+    }
+}
+
+impl<D: Dataset + ?Sized> Dataset for &D {
+    fn len(&self) -> usize {(**self).len()}
+    fn get(&self, index: usize) -> (Vec<f32>, Vec<f32>) {(**self).get(index)}
+    fn shape(&self) -> Shape {(**self).shape()}
+    fn label_map(&self) -> &labels::LabelMap {(**self).label_map()}
+}
+
+impl<D: Dataset + ?Sized> Dataset for Box<D> {
+    fn len(&self) -> usize {(**self).len()}
+    fn get(&self, index: usize) -> (Vec<f32>, Vec<f32>) {(**self).get(index)}
+    fn shape(&self) -> Shape {(**self).shape()}
+    fn label_map(&self) -> &labels::LabelMap {(**self).label_map()}
+}
+
+#[derive(Clone, Debug)]
+pub struct InMemoryDataset {
+    //Examples that are all loaded into memory, like the ones from inout::unpack_images.
+    pub inputs: Vec<Vec<f32>>,
+    pub targets: Vec<Vec<f32>>, //The desired outputs, one per input.
+    pub shape: Shape,
+    pub label_map: labels::LabelMap,
+}
+
+impl InMemoryDataset {
+    pub fn new(inputs: Vec<Vec<f32>>, targets: Vec<Vec<f32>>, shape: Shape, label_map: labels::LabelMap) -> Result<InMemoryDataset, NnError> {
+        //There must be a desired output for each input, and each input must fit the shape.
+        if inputs.len() != targets.len() {
+            return Err(NnError::shape_mismatch("the desired outputs of a dataset", inputs.len(), targets.len()))
+        }
+        if let Some(input) = inputs.iter().find(|input| input.len() != shape.size()) {
+            return Err(NnError::shape_mismatch("an input of a dataset", shape.size(), input.len()))
+        }
+        Ok(InMemoryDataset {
+            inputs: inputs,
+            targets: targets,
+            shape: shape,
+            label_map: label_map,
+        })
+    }
+
+    pub fn collect(dataset: &dyn Dataset) -> Result<InMemoryDataset, NnError> {
+        //Fetches every example of another dataset, so adapters don't have to be gone through again.
+        let mut inputs = Vec::new();
+        let mut targets = Vec::new();
+        for index in 0..dataset.len() {
+            let (input, target) = dataset.get(index);
+            inputs.push(input);
+            targets.push(target);
+        }
+        InMemoryDataset::new(inputs, targets, dataset.shape(), dataset.label_map().clone())
+    }
+}
+
+impl Dataset for InMemoryDataset {
+    fn len(&self) -> usize {
+        self.inputs.len()
+    }
+
+    fn get(&self, index: usize) -> (Vec<f32>, Vec<f32>) {
+        (self.inputs[index].clone(), self.targets[index].clone())
+    }
+
+    fn shape(&self) -> Shape {
+        self.shape
+    }
+
+    fn label_map(&self) -> &labels::LabelMap {
+        &self.label_map
+    }
+}
+
+pub struct Subset<D> {
+    pub inner: D,
+    pub indices: Vec<usize>, //The positions in inner of the examples that are included.
+}
+
+impl<D: Dataset> Subset<D> {
+    pub fn new(inner: D, indices: Vec<usize>) -> Result<Subset<D>, NnError> {
+        if let Some(index) = indices.iter().find(|index| **index >= inner.len()) {
+            return Err(NnError::Config(format!("The index {:?} is outside the dataset, which has {:?} examples", index, inner.len())))
+        }
+        Ok(Subset {
+            inner: inner,
+            indices: indices,
+        })
+    }
+}
+
+impl<D: Dataset> Dataset for Subset<D> {
+    fn len(&self) -> usize {self.indices.len()}
+    fn get(&self, index: usize) -> (Vec<f32>, Vec<f32>) {self.inner.get(self.indices[index])}
+    fn shape(&self) -> Shape {self.inner.shape()}
+    fn label_map(&self) -> &labels::LabelMap {self.inner.label_map()}
+}
+
+pub struct Concat<A, B> {
+    pub first: A,
+    pub second: B,
+}
+
+impl<A: Dataset, B: Dataset> Concat<A, B> {
+    pub fn new(first: A, second: B) -> Result<Concat<A, B>, NnError> {
+        //Both datasets must have the same shape and the same classes.
+        if first.shape() != second.shape() {
+            return Err(NnError::Config(format!("Can't put together datasets with the shapes {:?} and {:?}", first.shape(), second.shape())))
+        }
+        if first.label_map() != second.label_map() {
+            return Err(NnError::Config(String::from("Can't put together datasets with different classes")))
+        }
+        Ok(Concat {
+            first: first,
+            second: second,
+        })
+    }
+}
+
+impl<A: Dataset, B: Dataset> Dataset for Concat<A, B> {
+    fn len(&self) -> usize {
+        self.first.len() + self.second.len()
+    }
+
+    fn get(&self, index: usize) -> (Vec<f32>, Vec<f32>) {
+        if index < self.first.len() {
+            self.first.get(index)
+        } else {
+            self.second.get(index - self.first.len())
+        }
+    }
+
+    fn shape(&self) -> Shape {self.first.shape()}
+    fn label_map(&self) -> &labels::LabelMap {self.first.label_map()}
+}
+
+pub struct Map<D, F> {
+    pub inner: D,
+    pub transform: F,
+}
+
+impl<D: Dataset, F: Fn(Vec<f32>, Vec<f32>) -> (Vec<f32>, Vec<f32>)> Dataset for Map<D, F> {
+    fn len(&self) -> usize {self.inner.len()}
+
+    fn get(&self, index: usize) -> (Vec<f32>, Vec<f32>) {
+        let (input, target) = self.inner.get(index);
+        (self.transform)(input, target)
+    }
+
+    fn shape(&self) -> Shape {self.inner.shape()}
+    fn label_map(&self) -> &labels::LabelMap {self.inner.label_map()}
+}
+
+pub struct Batches<'a, D> {
+    dataset: &'a D,
+    batch_size: usize,
+    position: usize,
+}
+
+impl<'a, D: Dataset> Iterator for Batches<'a, D> {
+    type Item = Vec<(Vec<f32>, Vec<f32>)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.position >= self.dataset.len() {
+            return None
+        }
+        let end = (self.position + self.batch_size).min(self.dataset.len());
+        let batch = (self.position..end).map(|index| self.dataset.get(index)).collect();
+        self.position = end;
+        Some(batch)
+    }
+}
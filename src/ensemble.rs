@@ -0,0 +1,146 @@
+use crate::dataset;
+use crate::error::NnError;
+use crate::inout;
+use crate::nodes_layers;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CombinationMethod {
+    //How the outputs of the networks in an ensemble are turned into a single output.
+    AverageProbabilities, //The output values are averaged position by position.
+    MajorityVote, //Each network votes for its biggest output, and the output is the share of votes each position got.
+    WeightedVote, //Like MajorityVote, but each network's vote counts as much as its weight.
+}
+
+#[derive(Clone)]
+pub struct Ensemble {
+    //Several networks trained on the same task, answering together.
+    //The networks can have different hidden layers, but they must agree on the number of inputs and outputs.
+    pub networks: Vec<nodes_layers::Network>,
+    pub weights: Vec<f32>, //One per network, checked in predict. Only used by CombinationMethod::WeightedVote.
+    pub method: CombinationMethod,
+}
+
+impl Ensemble {
+    pub fn new(networks: Vec<nodes_layers::Network>, method: CombinationMethod) -> Ensemble {
+        //Makes an ensemble where every network has the weight 1.0.
+        let weights = vec![1.0; networks.len()];
+        Ensemble {
+            networks: networks,
+            weights: weights,
+            method: method,
+        }
+    }
+
+    pub fn read(members: Vec<(Vec<&str>, &[usize])>, learning_rate: f32, method: CombinationMethod) -> Result<Ensemble, NnError> {
+        //Loads each network with inout::read_network. Every member is the file paths of a network along with its network_form.
+        //No randomized layers are made in place of missing ones, since a random member would only drag the ensemble down.
+        let mut networks = Vec::new();
+        for (file_paths, network_form) in members {
+            networks.push(inout::read_network(file_paths, network_form, learning_rate, false)?.0);
+        }
+        Ok(Ensemble::new(networks, method))
+    }
+
+    pub fn set_weights_from_accuracy(&mut self, dataset: &dyn dataset::Dataset) -> Result<(), NnError> {
+        //Sets the weight of each network to its accuracy on the given examples, which should be kept apart from the training data.
+        //The weights are made anew, so a list of the wrong length is fixed along the way.
+        let mut weights = Vec::with_capacity(self.networks.len());
+        for network in &self.networks {
+            weights.push(network.evaluate(dataset)?);
+        }
+        self.weights = weights;
+        Ok(())
+    }
+
+    pub fn predict(&self, inputs: &Vec<f32>) -> Result<Vec<f32>, NnError> {
+        //Calculates the combined output of the networks.
+        if self.method == CombinationMethod::WeightedVote && self.weights.len() != self.networks.len() {
+            return Err(NnError::shape_mismatch("the ensemble weights", self.networks.len(), self.weights.len()))
+        }
+        let mut combined: Vec<f32> = Vec::new();
+        let mut total_weight = 0.0;
+        for num in 0..self.networks.len() {
+            let outputs = self.networks[num].predict(inputs)?;
+            if combined.is_empty() {
+                combined = vec![0.0; outputs.len()];
+            }
+            if outputs.len() != combined.len() {
+                return Err(NnError::shape_mismatch("the outputs of each network in the ensemble", combined.len(), outputs.len()))
+            }
+            match self.method {
+                CombinationMethod::AverageProbabilities => {
+                    for pos in 0..outputs.len() {
+                        combined[pos] += outputs[pos];
+                    }
+                    total_weight += 1.0;
+                },
+                CombinationMethod::MajorityVote | CombinationMethod::WeightedVote => {
+                    let weight = if self.method == CombinationMethod::WeightedVote {self.weights[num]} else {1.0};
+                    //A network that is torn between several positions splits its vote between them.
+                    let votes = nodes_layers::find_biggest(&outputs).1;
+                    for pos in &votes {
+                        combined[*pos] += weight / votes.len() as f32;
+                    }
+                    total_weight += weight;
+                },
+            }
+        }
+        if total_weight > 0.0 {
+            for value in combined.iter_mut() {
+                *value /= total_weight;
+            }
+        }
+        Ok(combined)
+    }
+
+    pub fn evaluate(&self, dataset: &dyn dataset::Dataset) -> Result<f32, NnError> {
+        //The fraction of the examples where the biggest combined output is in the same position as the biggest desired output.
+        let mut successes = 0;
+        for num in 0..dataset.len() {
+            let (inputs, desired_outputs) = dataset.get(num);
+            if nodes_layers::same_biggest(&self.predict(&inputs)?, &desired_outputs) {
+                successes += 1;
+            }
+        }
+        Ok(if dataset.is_empty() {0.0} else {successes as f32 / dataset.len() as f32})
+    }
+
+    pub fn compare_success(&self, inputs: &Vec<f32>, desired_outputs: &Vec<f32>, margin_of_error: f32) -> Result<bool, NnError> {
+        //Compares whether all the combined outputs are correct within a margin of error.
+        let output_values = self.predict(inputs)?;
+        if desired_outputs.len() != output_values.len() {
+            return Err(NnError::shape_mismatch("the desired outputs", output_values.len(), desired_outputs.len()))
+        }
+        let mut within_margin = true;
+        for num in 0..desired_outputs.len() {
+            if (desired_outputs[num] - output_values[num]).powi(2) > margin_of_error.powi(2) {
+                within_margin = false;
+            }
+        }
+        Ok(within_margin)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn weights_of_the_wrong_length_are_rejected() {
+        let networks = vec![nodes_layers::Network::new(&[2, 3], 0.1), nodes_layers::Network::new(&[2, 3], 0.1)];
+        let mut ensemble = Ensemble::new(networks, CombinationMethod::WeightedVote);
+        let inputs = vec![0.5, 0.25];
+        assert!(ensemble.predict(&inputs).is_ok());
+        ensemble.weights.pop();
+        match ensemble.predict(&inputs) {
+            Err(NnError::ShapeMismatch {expected, found, ..}) => assert_eq!((expected, found), (2, 1)),
+            other => panic!("Expected a shape mismatch, found {:?}", other),
+        }
+        //The other methods don't use the weights.
+        ensemble.method = CombinationMethod::MajorityVote;
+        assert!(ensemble.predict(&inputs).is_ok());
+        ensemble.networks.push(nodes_layers::Network::new(&[2, 3], 0.1));
+        ensemble.method = CombinationMethod::WeightedVote;
+        assert!(ensemble.predict(&inputs).is_err());
+    }
+}
@@ -0,0 +1,104 @@
+extern crate csv;
+
+use std::error::Error;
+use std::fmt;
+use std::io;
+use std::num;
+
+use crate::cifar;
+use crate::idx;
+use crate::image_import;
+use crate::tabular;
+
+#[derive(Debug)]
+pub enum NnError {
+    //Everything that can go wrong in the library, so programs using it can handle it instead of stopping.
+    //The file formats with error types of their own are kept whole inside it.
+    Io(io::Error),
+    Csv(csv::Error), //From reading or writing the .csv files that models are saved in.
+    Idx(idx::IdxError),
+    Cifar(cifar::CifarError),
+    Tabular(tabular::TabularError),
+    Image(image_import::ImageError),
+    Format(String), //A saved model that can't be understood, like a layer file with a value that isn't a number.
+    ShapeMismatch {what: String, expected: usize, found: usize}, //Something of the wrong size, like inputs for a network made for another number of them.
+    Config(String), //Settings that can't work, like fewer file paths than there are layers.
+}
+
+impl NnError {
+    pub fn shape_mismatch(what: &str, expected: usize, found: usize) -> NnError {
+        NnError::ShapeMismatch {what: what.to_string(), expected: expected, found: found}
+    }
+}
+
+impl fmt::Display for NnError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            NnError::Io(error) => write!(f, "Couldn't read or write a file: {}", error),
+            NnError::Csv(error) => write!(f, "Couldn't read or write a CSV file: {}", error),
+            NnError::Idx(error) => write!(f, "{}", error),
+            NnError::Cifar(error) => write!(f, "{}", error),
+            NnError::Tabular(error) => write!(f, "{}", error),
+            NnError::Image(error) => write!(f, "{}", error),
+            NnError::Format(details) => write!(f, "Couldn't understand the saved model: {}", details),
+            NnError::ShapeMismatch {what, expected, found} => write!(f, "Expected {} to have a size of {:?}, found {:?}", what, expected, found),
+            NnError::Config(details) => write!(f, "Invalid settings: {}", details),
+        }
+    }
+}
+
+impl Error for NnError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            NnError::Io(error) => Some(error),
+            NnError::Csv(error) => Some(error),
+            NnError::Idx(error) => Some(error),
+            NnError::Cifar(error) => Some(error),
+            NnError::Tabular(error) => Some(error),
+            NnError::Image(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for NnError {
+    fn from(error: io::Error) -> NnError {
+        NnError::Io(error)
+    }
+}
+
+impl From<csv::Error> for NnError {
+    fn from(error: csv::Error) -> NnError {
+        NnError::Csv(error)
+    }
+}
+
+impl From<idx::IdxError> for NnError {
+    fn from(error: idx::IdxError) -> NnError {
+        NnError::Idx(error)
+    }
+}
+
+impl From<cifar::CifarError> for NnError {
+    fn from(error: cifar::CifarError) -> NnError {
+        NnError::Cifar(error)
+    }
+}
+
+impl From<tabular::TabularError> for NnError {
+    fn from(error: tabular::TabularError) -> NnError {
+        NnError::Tabular(error)
+    }
+}
+
+impl From<image_import::ImageError> for NnError {
+    fn from(error: image_import::ImageError) -> NnError {
+        NnError::Image(error)
+    }
+}
+
+impl From<num::ParseIntError> for NnError {
+    fn from(error: num::ParseIntError) -> NnError {
+        NnError::Format(error.to_string())
+    }
+}
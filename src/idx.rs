@@ -0,0 +1,577 @@
+extern crate flate2;
+
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::io::{BufRead, Read, Write};
+
+use crate::dataset;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ElementType {
+    //The types of values an IDX file can hold, named by the code in the third byte of the header.
+    U8, //0x08
+    I8, //0x09
+    I16, //0x0B
+    I32, //0x0C
+    F32, //0x0D
+    F64, //0x0E
+}
+
+impl ElementType {
+    pub fn from_code(code: u8) -> Option<ElementType> {
+        match code {
+            0x08 => Some(ElementType::U8),
+            0x09 => Some(ElementType::I8),
+            0x0B => Some(ElementType::I16),
+            0x0C => Some(ElementType::I32),
+            0x0D => Some(ElementType::F32),
+            0x0E => Some(ElementType::F64),
+            _ => None,
+        }
+    }
+
+    pub fn code(&self) -> u8 {
+        match self {
+            ElementType::U8 => 0x08,
+            ElementType::I8 => 0x09,
+            ElementType::I16 => 0x0B,
+            ElementType::I32 => 0x0C,
+            ElementType::F32 => 0x0D,
+            ElementType::F64 => 0x0E,
+        }
+    }
+
+    pub fn size(&self) -> usize {
+        //The number of bytes a single value takes up.
+        match self {
+            ElementType::U8 | ElementType::I8 => 1,
+            ElementType::I16 => 2,
+            ElementType::I32 | ElementType::F32 => 4,
+            ElementType::F64 => 8,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum IdxData {
+    //The values of an IDX file, in the order they were stored, kept in their own type.
+    U8(Vec<u8>),
+    I8(Vec<i8>),
+    I16(Vec<i16>),
+    I32(Vec<i32>),
+    F32(Vec<f32>),
+    F64(Vec<f64>),
+}
+
+impl IdxData {
+    pub fn len(&self) -> usize {
+        match self {
+            IdxData::U8(values) => values.len(),
+            IdxData::I8(values) => values.len(),
+            IdxData::I16(values) => values.len(),
+            IdxData::I32(values) => values.len(),
+            IdxData::F32(values) => values.len(),
+            IdxData::F64(values) => values.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn element_type(&self) -> ElementType {
+        match self {
+            IdxData::U8(_) => ElementType::U8,
+            IdxData::I8(_) => ElementType::I8,
+            IdxData::I16(_) => ElementType::I16,
+            IdxData::I32(_) => ElementType::I32,
+            IdxData::F32(_) => ElementType::F32,
+            IdxData::F64(_) => ElementType::F64,
+        }
+    }
+
+    pub fn to_f32(&self) -> Vec<f32> {
+        //The values as f32s, unchanged apart from the conversion. Use IdxFile::inputs for values fit for a nodes_layers::Network.
+        match self {
+            IdxData::U8(values) => values.iter().map(|&value| value as f32).collect(),
+            IdxData::I8(values) => values.iter().map(|&value| value as f32).collect(),
+            IdxData::I16(values) => values.iter().map(|&value| value as f32).collect(),
+            IdxData::I32(values) => values.iter().map(|&value| value as f32).collect(),
+            IdxData::F32(values) => values.clone(),
+            IdxData::F64(values) => values.iter().map(|&value| value as f32).collect(),
+        }
+    }
+
+    pub fn select(&self, ranges: &[(usize, usize)]) -> IdxData {
+        //Picks out the values in the given ranges of positions, given as start and end, in order.
+        fn pick<T: Clone>(values: &[T], ranges: &[(usize, usize)]) -> Vec<T> {
+            let mut picked = Vec::new();
+            for &(start, end) in ranges {
+                picked.extend_from_slice(&values[start..end]);
+            }
+            picked
+        }
+        match self {
+            IdxData::U8(values) => IdxData::U8(pick(values, ranges)),
+            IdxData::I8(values) => IdxData::I8(pick(values, ranges)),
+            IdxData::I16(values) => IdxData::I16(pick(values, ranges)),
+            IdxData::I32(values) => IdxData::I32(pick(values, ranges)),
+            IdxData::F32(values) => IdxData::F32(pick(values, ranges)),
+            IdxData::F64(values) => IdxData::F64(pick(values, ranges)),
+        }
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        //The values as they're stored in an IDX file, big-endian.
+        let mut bytes = Vec::new();
+        match self {
+            IdxData::U8(values) => bytes.extend_from_slice(values),
+            IdxData::I8(values) => bytes.extend(values.iter().map(|&value| value as u8)),
+            IdxData::I16(values) => for value in values {bytes.extend_from_slice(&value.to_be_bytes())},
+            IdxData::I32(values) => for value in values {bytes.extend_from_slice(&value.to_be_bytes())},
+            IdxData::F32(values) => for value in values {bytes.extend_from_slice(&value.to_bits().to_be_bytes())},
+            IdxData::F64(values) => for value in values {bytes.extend_from_slice(&value.to_bits().to_be_bytes())},
+        }
+        bytes
+    }
+
+    fn from_bytes(element_type: ElementType, bytes: &[u8]) -> IdxData {
+        //Every value wider than a byte is stored big-endian.
+        let size = element_type.size();
+        let chunks = bytes.chunks_exact(size);
+        match element_type {
+            ElementType::U8 => IdxData::U8(bytes.to_vec()),
+            ElementType::I8 => IdxData::I8(bytes.iter().map(|&byte| byte as i8).collect()),
+            ElementType::I16 => IdxData::I16(chunks.map(|chunk| i16::from_be_bytes([chunk[0], chunk[1]])).collect()),
+            ElementType::I32 => IdxData::I32(chunks.map(|chunk| i32::from_be_bytes([chunk[0], chunk[1], chunk[2], chunk[3]])).collect()),
+            ElementType::F32 => IdxData::F32(chunks.map(|chunk| f32::from_bits(u32::from_be_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]))).collect()),
+            ElementType::F64 => IdxData::F64(chunks.map(|chunk| {
+                    let mut array = [0u8; 8];
+                    array.copy_from_slice(chunk);
+                    f64::from_bits(u64::from_be_bytes(array))
+                }).collect()),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum IdxError {
+    //The ways reading an IDX file can go wrong.
+    Io(io::Error),
+    BadMagic([u8; 2]), //The first two bytes, which should both be zero.
+    UnknownElementType(u8), //The type code from the header.
+    NoDimensions,
+    Truncated {expected: usize, found: usize}, //In bytes, counting the header.
+    TrailingBytes {expected: usize}, //The file kept going after the expected number of bytes.
+    UnexpectedElementType {expected: ElementType, found: ElementType},
+    UnexpectedDimensionCount {expected: usize, found: usize},
+    TooFewDimensions {minimum: usize, found: usize},
+    CountMismatch {images: usize, labels: usize},
+    ShapeMismatch {expected: Vec<usize>, found: Vec<usize>}, //The dimensions of the items, when putting files together.
+    DimensionTooLarge(usize), //The header can only hold dimensions that fit in a u32.
+    TooManyValues(Vec<usize>), //The dimensions multiply to more values than can be held in memory.
+    ValueCountMismatch {expected: usize, found: usize}, //The dimensions don't fit the number of values being written.
+    UnknownLabel(u8), //A label that isn't one of the classes the data is supposed to have.
+    ItemOutOfRange {index: usize, count: usize}, //An item that was asked for, past the end of the file.
+}
+
+impl fmt::Display for IdxError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            IdxError::Io(error) => write!(f, "Couldn't read the IDX file: {}", error),
+            IdxError::BadMagic(bytes) => write!(f, "Expected the IDX file to start with two zero bytes, found {:?}", bytes),
+            IdxError::UnknownElementType(code) => write!(f, "The IDX header has an unknown element type {:#04x}", code),
+            IdxError::NoDimensions => write!(f, "The IDX header declares no dimensions"),
+            IdxError::Truncated {expected, found} => write!(f, "Expected the IDX file to be {:?} bytes long, found only {:?}", expected, found),
+            IdxError::TrailingBytes {expected} => write!(f, "Expected the IDX file to end after {:?} bytes, but it kept going", expected),
+            IdxError::UnexpectedElementType {expected, found} => write!(f, "Expected the IDX file to hold {:?} values, found {:?} values", expected, found),
+            IdxError::UnexpectedDimensionCount {expected, found} => write!(f, "Expected the IDX file to have {:?} dimensions, found {:?}", expected, found),
+            IdxError::TooFewDimensions {minimum, found} => write!(f, "Expected the IDX file to have at least {:?} dimensions, found {:?}", minimum, found),
+            IdxError::CountMismatch {images, labels} => write!(f, "There are {:?} images, but {:?} labels", images, labels),
+            IdxError::ShapeMismatch {expected, found} => write!(f, "Expected items with the dimensions {:?}, found {:?}", expected, found),
+            IdxError::ValueCountMismatch {expected, found} => write!(f, "The dimensions call for {:?} values, but there are {:?}", expected, found),
+            IdxError::UnknownLabel(label) => write!(f, "The label {:?} isn't one of the known classes", label),
+            IdxError::DimensionTooLarge(dimension) => write!(f, "The dimension {:?} is too large to be written to an IDX file", dimension),
+            IdxError::TooManyValues(dimensions) => write!(f, "The dimensions {:?} call for more values than can be held in memory", dimensions),
+            IdxError::ItemOutOfRange {index, count} => write!(f, "Tried to select item {:?}, but there are only {:?} items", index, count),
+        }
+    }
+}
+
+impl Error for IdxError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            IdxError::Io(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for IdxError {
+    fn from(error: io::Error) -> IdxError {
+        IdxError::Io(error)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct IdxFile {
+    //The contents of an IDX file, the format the MNIST database comes in.
+    pub dimensions: Vec<usize>, //The first dimension is the number of items, the rest are the shape of each item.
+    pub data: IdxData,
+}
+
+impl IdxFile {
+    pub fn element_type(&self) -> ElementType {
+        self.data.element_type()
+    }
+
+    pub fn item_count(&self) -> usize {
+        self.dimensions[0]
+    }
+
+    pub fn item_dimensions(&self) -> &[usize] {
+        &self.dimensions[1..]
+    }
+
+    pub fn item_shape(&self) -> dataset::Shape {
+        item_shape(self.item_dimensions())
+    }
+
+    pub fn item_size(&self) -> usize {
+        //The number of values in one item, like the number of pixels in an image.
+        self.item_dimensions().iter().product()
+    }
+
+    pub fn select(&self, indices: &[usize]) -> Result<IdxFile, IdxError> {
+        //A file with only the items at the given positions, in the given order.
+        let item_size = self.item_size();
+        let mut ranges = Vec::new();
+        for &index in indices {
+            if index >= self.item_count() {
+                return Err(IdxError::ItemOutOfRange {index: index, count: self.item_count()})
+            }
+            ranges.push((index * item_size, (index + 1) * item_size));
+        }
+        let mut dimensions = self.dimensions.clone();
+        dimensions[0] = indices.len();
+        Ok(IdxFile {
+            dimensions: dimensions,
+            data: self.data.select(&ranges),
+        })
+    }
+
+    pub fn concat(&self, other: &IdxFile) -> Result<IdxFile, IdxError> {
+        //The items of both files, this one's first. The items have to be the same shape and type.
+        if self.item_dimensions() != other.item_dimensions() {
+            return Err(IdxError::ShapeMismatch {expected: self.item_dimensions().to_vec(), found: other.item_dimensions().to_vec()})
+        }
+        let data = match (&self.data, &other.data) {
+            (IdxData::U8(first), IdxData::U8(second)) => IdxData::U8([&first[..], &second[..]].concat()),
+            (IdxData::I8(first), IdxData::I8(second)) => IdxData::I8([&first[..], &second[..]].concat()),
+            (IdxData::I16(first), IdxData::I16(second)) => IdxData::I16([&first[..], &second[..]].concat()),
+            (IdxData::I32(first), IdxData::I32(second)) => IdxData::I32([&first[..], &second[..]].concat()),
+            (IdxData::F32(first), IdxData::F32(second)) => IdxData::F32([&first[..], &second[..]].concat()),
+            (IdxData::F64(first), IdxData::F64(second)) => IdxData::F64([&first[..], &second[..]].concat()),
+            _ => return Err(IdxError::UnexpectedElementType {expected: self.element_type(), found: other.element_type()}),
+        };
+        let mut dimensions = self.dimensions.clone();
+        dimensions[0] += other.item_count();
+        Ok(IdxFile {
+            dimensions: dimensions,
+            data: data,
+        })
+    }
+
+    pub fn inputs(&self) -> Vec<Vec<f32>> {
+        //Splits the values into one vector per item, to be used as inputs by the nodes_layers::Network struct.
+        //u8 values are turned from 0 to 255 into 0.0 to 1.0 like with inout::turn_to_float. Other types are kept as they are.
+        let values = match &self.data {
+            IdxData::U8(values) => values.iter().map(|&value| value as f32 / 255.0).collect(),
+            data => data.to_f32(),
+        };
+        let item_size = self.item_size();
+        let mut inputs = Vec::new();
+        for num in 0..self.item_count() {
+            inputs.push(values[num * item_size..(num + 1) * item_size].to_vec());
+        }
+        inputs
+    }
+}
+
+pub fn read_from<R: Read>(reader: &mut R) -> Result<IdxFile, IdxError> {
+    //Parses an IDX file: two zero bytes, an element type code, the number of dimensions,
+    //the size of each dimension as a big-endian u32, and then the values themselves.
+    //The file has to end right after the number of values the dimensions declare.
+    let (element_type, dimensions) = read_header_from(reader)?;
+    let header_length = 4 + 4 * dimensions.len();
+    //The values are read with take and read_to_end instead of into a buffer of the declared size, so the buffer only grows as the file delivers bytes
+    //and a broken header can't ask for more memory than the file has.
+    let data_length = value_count(&dimensions)?.checked_mul(element_type.size()).ok_or_else(|| IdxError::TooManyValues(dimensions.clone()))?;
+    let mut data_bytes = Vec::new();
+    reader.take(data_length as u64).read_to_end(&mut data_bytes)?;
+    if data_bytes.len() < data_length {
+        return Err(IdxError::Truncated {expected: header_length + data_length, found: header_length + data_bytes.len()})
+    }
+    let mut extra = [0u8; 1];
+    if reader.read(&mut extra)? > 0 {
+        return Err(IdxError::TrailingBytes {expected: header_length + data_length})
+    }
+    Ok(IdxFile {
+        dimensions: dimensions,
+        data: IdxData::from_bytes(element_type, &data_bytes),
+    })
+}
+
+fn value_count(dimensions: &[usize]) -> Result<usize, IdxError> {
+    //The number of values the dimensions call for. The dimensions come from the file, so the product is checked for overflow.
+    dimensions.iter()
+        .try_fold(1usize, |count, &dimension| count.checked_mul(dimension))
+        .ok_or_else(|| IdxError::TooManyValues(dimensions.to_vec()))
+}
+
+fn read_header_from<R: Read>(reader: &mut R) -> Result<(ElementType, Vec<usize>), IdxError> {
+    let mut header = [0u8; 4];
+    read_exactly(reader, &mut header, 0, 4)?;
+    if header[0] != 0 || header[1] != 0 {
+        return Err(IdxError::BadMagic([header[0], header[1]]))
+    }
+    let element_type = ElementType::from_code(header[2]).ok_or(IdxError::UnknownElementType(header[2]))?;
+    let dimension_count = header[3] as usize;
+    if dimension_count == 0 {
+        return Err(IdxError::NoDimensions)
+    }
+    let header_length = 4 + 4 * dimension_count;
+    let mut dimension_bytes = vec![0u8; 4 * dimension_count];
+    read_exactly(reader, &mut dimension_bytes, 4, header_length)?;
+    let mut dimensions = Vec::new();
+    for chunk in dimension_bytes.chunks_exact(4) {
+        dimensions.push(u32::from_be_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]) as usize);
+    }
+    Ok((element_type, dimensions))
+}
+
+fn read_exactly<R: Read>(reader: &mut R, buffer: &mut [u8], already_read: usize, expected: usize) -> Result<(), IdxError> {
+    //Like Read::read_exact, but reports how far the file got when it ends early.
+    let mut filled = 0;
+    while filled < buffer.len() {
+        match reader.read(&mut buffer[filled..]) {
+            Ok(0) => return Err(IdxError::Truncated {expected: expected, found: already_read + filled}),
+            Ok(count) => filled += count,
+            Err(ref error) if error.kind() == io::ErrorKind::Interrupted => {},
+            Err(error) => return Err(IdxError::Io(error)),
+        }
+    }
+    Ok(())
+}
+
+const GZIP_MAGIC: [u8; 2] = [0x1F, 0x8B];
+
+pub fn read(filename: &str) -> Result<IdxFile, IdxError> {
+    //Reads an IDX file, either as it is or compressed with gzip like the files the MNIST database is distributed as.
+    //Compressed files are found by their first two bytes rather than their name, and are decompressed while they're parsed.
+    let mut file = io::BufReader::new(fs::File::open(filename)?);
+    if file.fill_buf()?.starts_with(&GZIP_MAGIC) {
+        read_from(&mut flate2::bufread::MultiGzDecoder::new(file))
+    } else {
+        read_from(&mut file)
+    }
+}
+
+pub fn read_header(filename: &str) -> Result<(ElementType, Vec<usize>), IdxError> {
+    //Reads only the element type and dimensions of an IDX file, which is enough to know the shape of its items.
+    let mut file = io::BufReader::new(fs::File::open(filename)?);
+    if file.fill_buf()?.starts_with(&GZIP_MAGIC) {
+        read_header_from(&mut flate2::bufread::MultiGzDecoder::new(file))
+    } else {
+        read_header_from(&mut file)
+    }
+}
+
+pub fn read_images(filename: &str) -> Result<IdxFile, IdxError> {
+    //Reads a file of images, which has a dimension for the number of images and at least one more for their shape.
+    let file = read(filename)?;
+    if file.dimensions.len() < 2 {
+        return Err(IdxError::TooFewDimensions {minimum: 2, found: file.dimensions.len()})
+    }
+    Ok(file)
+}
+
+pub fn read_labels(filename: &str) -> Result<Vec<u8>, IdxError> {
+    //Reads a file of labels, a single dimension of u8 values.
+    let file = read(filename)?;
+    if file.dimensions.len() != 1 {
+        return Err(IdxError::UnexpectedDimensionCount {expected: 1, found: file.dimensions.len()})
+    }
+    match file.data {
+        IdxData::U8(labels) => Ok(labels),
+        data => Err(IdxError::UnexpectedElementType {expected: ElementType::U8, found: data.element_type()}),
+    }
+}
+
+pub fn read_pair(images_filename: &str, labels_filename: &str) -> Result<(IdxFile, Vec<u8>), IdxError> {
+    //Reads a file of images and the file of their labels, making sure there's a label for every image.
+    let images = read_images(images_filename)?;
+    let labels = read_labels(labels_filename)?;
+    if images.item_count() != labels.len() {
+        return Err(IdxError::CountMismatch {images: images.item_count(), labels: labels.len()})
+    }
+    Ok((images, labels))
+}
+
+pub fn item_shape(item_dimensions: &[usize]) -> dataset::Shape {
+    //The shape of one item from the dimensions after the first. Two dimensions are the height and width of an image,
+    //three are the channels, height and width, and a single one is a flat list of values.
+    let dimensions = item_dimensions;
+    match dimensions.len() {
+        0 => dataset::Shape::flat(1),
+        1 => dataset::Shape::flat(dimensions[0]),
+        2 => dataset::Shape::image(1, dimensions[0], dimensions[1]),
+        _ => dataset::Shape::image(dimensions[..dimensions.len()-2].iter().product(), dimensions[dimensions.len()-2], dimensions[dimensions.len()-1]),
+    }
+}
+
+pub fn write_to<W: Write>(file: &IdxFile, writer: &mut W) -> Result<(), IdxError> {
+    //Writes the header and the values in the same layout read_from expects.
+    if file.dimensions.is_empty() {
+        return Err(IdxError::NoDimensions)
+    }
+    if file.dimensions.len() > u8::max_value() as usize {
+        return Err(IdxError::UnexpectedDimensionCount {expected: u8::max_value() as usize, found: file.dimensions.len()})
+    }
+    let expected_length = value_count(&file.dimensions)?;
+    if file.data.len() != expected_length {
+        return Err(IdxError::ValueCountMismatch {expected: expected_length, found: file.data.len()})
+    }
+    writer.write_all(&[0, 0, file.element_type().code(), file.dimensions.len() as u8])?;
+    for &dimension in &file.dimensions {
+        if dimension > u32::max_value() as usize {
+            return Err(IdxError::DimensionTooLarge(dimension))
+        }
+        writer.write_all(&(dimension as u32).to_be_bytes())?;
+    }
+    writer.write_all(&file.data.to_bytes())?;
+    Ok(())
+}
+
+pub fn write(file: &IdxFile, filename: &str) -> Result<(), IdxError> {
+    //Writes an IDX file, compressed with gzip if the filename ends with .gz.
+    let output = io::BufWriter::new(fs::File::create(filename)?);
+    if filename.ends_with(".gz") {
+        let mut encoder = flate2::write::GzEncoder::new(output, flate2::Compression::default());
+        write_to(file, &mut encoder)?;
+        encoder.finish()?.flush()?;
+    } else {
+        let mut output = output;
+        write_to(file, &mut output)?;
+        output.flush()?;
+    }
+    Ok(())
+}
+
+pub fn labels_file(labels: &[u8]) -> IdxFile {
+    //A file of labels, in the form read_labels reads.
+    IdxFile {
+        dimensions: vec![labels.len()],
+        data: IdxData::U8(labels.to_vec()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn round_trip(file: &IdxFile) -> IdxFile {
+        let mut bytes = Vec::new();
+        write_to(file, &mut bytes).unwrap();
+        read_from(&mut &bytes[..]).unwrap()
+    }
+
+    #[test]
+    fn round_trips_element_types() {
+        let files = vec![
+            IdxFile {dimensions: vec![2, 2, 3], data: IdxData::U8(vec![0, 1, 2, 3, 4, 5, 250, 251, 252, 253, 254, 255])},
+            IdxFile {dimensions: vec![3, 2], data: IdxData::I16(vec![-32768, -1, 0, 1, 256, 32767])},
+            IdxFile {dimensions: vec![2, 1, 2], data: IdxData::F32(vec![-1.5, 0.0, 3.25, std::f32::MAX])},
+        ];
+        for file in &files {
+            assert_eq!(&round_trip(file), file);
+        }
+    }
+
+    #[test]
+    fn stores_values_big_endian() {
+        let file = IdxFile {dimensions: vec![2], data: IdxData::I16(vec![0x0102, -2])};
+        let mut bytes = Vec::new();
+        write_to(&file, &mut bytes).unwrap();
+        assert_eq!(bytes, vec![0, 0, 0x0B, 1, 0, 0, 0, 2, 0x01, 0x02, 0xFF, 0xFE]);
+    }
+
+    #[test]
+    fn reads_gzipped_files() {
+        let file = IdxFile {dimensions: vec![3, 2, 2], data: IdxData::U8((0..12).collect())};
+        let directory = std::env::temp_dir();
+        let plain_path = directory.join(format!("numbers_nn_{}_idx_test.idx", std::process::id())).to_string_lossy().into_owned();
+        let gzip_path = format!("{}.gz", plain_path);
+        write(&file, &plain_path).unwrap();
+        write(&file, &gzip_path).unwrap();
+        let gzip_bytes = fs::read(&gzip_path).unwrap();
+        let plain = read(&plain_path);
+        let gzipped = read_images(&gzip_path);
+        let header = read_header(&gzip_path);
+        fs::remove_file(&plain_path).unwrap();
+        fs::remove_file(&gzip_path).unwrap();
+        assert!(gzip_bytes.starts_with(&GZIP_MAGIC));
+        assert_eq!(plain.unwrap(), file);
+        assert_eq!(gzipped.unwrap(), file);
+        assert_eq!(header.unwrap(), (ElementType::U8, vec![3, 2, 2]));
+    }
+
+    #[test]
+    fn truncated_body_is_reported() {
+        let file = IdxFile {dimensions: vec![2, 3], data: IdxData::F32(vec![1.0; 6])};
+        let mut bytes = Vec::new();
+        write_to(&file, &mut bytes).unwrap();
+        let length = bytes.len();
+        match read_from(&mut &bytes[..length - 3]) {
+            Err(IdxError::Truncated {expected, found}) => assert_eq!((expected, found), (length, length - 3)),
+            other => panic!("Expected the file to be truncated, found {:?}", other),
+        }
+        match read_from(&mut &bytes[..6]) {
+            Err(IdxError::Truncated {expected, found}) => assert_eq!((expected, found), (12, 6)),
+            other => panic!("Expected the header to be truncated, found {:?}", other),
+        }
+        bytes.push(0);
+        match read_from(&mut &bytes[..]) {
+            Err(IdxError::TrailingBytes {expected}) => assert_eq!(expected, length),
+            other => panic!("Expected trailing bytes, found {:?}", other),
+        }
+    }
+
+    #[test]
+    fn bad_magic_is_reported() {
+        match read_from(&mut &[0, 1, 0x08, 1, 0, 0, 0, 0][..]) {
+            Err(IdxError::BadMagic(bytes)) => assert_eq!(bytes, [0, 1]),
+            other => panic!("Expected a bad magic number, found {:?}", other),
+        }
+        match read_from(&mut &[0, 0, 0x07, 1, 0, 0, 0, 0][..]) {
+            Err(IdxError::UnknownElementType(code)) => assert_eq!(code, 0x07),
+            other => panic!("Expected an unknown element type, found {:?}", other),
+        }
+    }
+
+    #[test]
+    fn huge_dimensions_are_too_many_values() {
+        let bytes = [0, 0, 0x0E, 3, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF];
+        match read_from(&mut &bytes[..]) {
+            Err(IdxError::TooManyValues(dimensions)) => assert_eq!(dimensions.len(), 3),
+            other => panic!("Expected too many values, found {:?}", other),
+        }
+        let file = IdxFile {dimensions: vec![usize::max_value(), 2], data: IdxData::U8(Vec::new())};
+        match write_to(&file, &mut Vec::new()) {
+            Err(IdxError::TooManyValues(_)) => {},
+            other => panic!("Expected too many values, found {:?}", other),
+        }
+    }
+}
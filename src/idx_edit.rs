@@ -0,0 +1,129 @@
+extern crate rand;
+
+use rand::Rng;
+use rand::seq::SliceRandom;
+
+use crate::error::NnError;
+use crate::idx;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct LabelledImages {
+    //A file of images and the labels that go with them, like a part of the MNIST database, for building new datasets out of.
+    pub images: idx::IdxFile,
+    pub labels: Vec<u8>, //One per image.
+}
+
+impl LabelledImages {
+    pub fn new(images: idx::IdxFile, labels: Vec<u8>) -> Result<LabelledImages, NnError> {
+        if images.item_count() != labels.len() {
+            return Err(NnError::Idx(idx::IdxError::CountMismatch {images: images.item_count(), labels: labels.len()}))
+        }
+        Ok(LabelledImages {
+            images: images,
+            labels: labels,
+        })
+    }
+
+    pub fn read(images_filename: &str, labels_filename: &str) -> Result<LabelledImages, NnError> {
+        let (images, labels) = idx::read_pair(images_filename, labels_filename)?;
+        LabelledImages::new(images, labels)
+    }
+
+    pub fn write(&self, images_filename: &str, labels_filename: &str) -> Result<(), NnError> {
+        //Writes the images and labels as two IDX files. Filenames ending with .gz are compressed.
+        idx::write(&self.images, images_filename)?;
+        idx::write(&idx::labels_file(&self.labels), labels_filename)?;
+        Ok(())
+    }
+
+    pub fn len(&self) -> usize {
+        self.labels.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.labels.is_empty()
+    }
+
+    pub fn select(&self, indices: &[usize]) -> Result<LabelledImages, NnError> {
+        //The images at the given positions, in the given order, along with their labels.
+        let images = self.images.select(indices)?;
+        let mut labels = Vec::new();
+        for &index in indices {
+            match self.labels.get(index) {
+                Some(label) => labels.push(*label),
+                None => return Err(NnError::Idx(idx::IdxError::ItemOutOfRange {index: index, count: self.labels.len()})),
+            }
+        }
+        Ok(LabelledImages {
+            images: images,
+            labels: labels,
+        })
+    }
+
+    pub fn range(&self, start: usize, count: usize) -> Result<LabelledImages, NnError> {
+        //count images from start onwards, or as many as there are.
+        let end = start.saturating_add(count).min(self.len());
+        let indices: Vec<usize> = (start.min(end)..end).collect();
+        self.select(&indices)
+    }
+
+    pub fn merge(&self, other: &LabelledImages) -> Result<LabelledImages, NnError> {
+        //The images of both, this one's first. The images have to be the same size and type.
+        Ok(LabelledImages {
+            images: self.images.concat(&other.images)?,
+            labels: [&self.labels[..], &other.labels[..]].concat(),
+        })
+    }
+
+    pub fn shuffled<R: Rng>(&self, rng: &mut R) -> Result<LabelledImages, NnError> {
+        let mut indices: Vec<usize> = (0..self.len()).collect();
+        indices.shuffle(rng);
+        self.select(&indices)
+    }
+
+    pub fn split_by_ratio(&self, ratio: f32) -> Result<(LabelledImages, LabelledImages), NnError> {
+        //Splits into the first ratio of the images and the rest, keeping their order. Shuffle first to get a random split.
+        let first_count = ((self.len() as f32 * ratio.max(0.0).min(1.0)).round() as usize).min(self.len());
+        Ok((self.range(0, first_count)?, self.range(first_count, self.len() - first_count)?))
+    }
+
+    pub fn split_by_class(&self, classes: &[u8]) -> Result<(LabelledImages, LabelledImages), NnError> {
+        //Splits into the images with one of the given labels and the rest, keeping their order.
+        let mut chosen = Vec::new();
+        let mut others = Vec::new();
+        for num in 0..self.len() {
+            if classes.contains(&self.labels[num]) {
+                chosen.push(num);
+            } else {
+                others.push(num);
+            }
+        }
+        Ok((self.select(&chosen)?, self.select(&others)?))
+    }
+
+    pub fn relabel(&self, mapping: &[(u8, u8)]) -> LabelledImages {
+        //Changes each label that's the first of a pair in the mapping to the second. Other labels are kept.
+        let mut relabelled = self.clone();
+        for label in &mut relabelled.labels {
+            if let Some(&(_, new_label)) = mapping.iter().find(|(old_label, _)| old_label == label) {
+                *label = new_label;
+            }
+        }
+        relabelled
+    }
+
+    pub fn class_counts(&self) -> Vec<(u8, usize)> {
+        //The number of images with each label, ordered by label.
+        let mut counts = vec![0usize; 256];
+        for &label in &self.labels {
+            counts[label as usize] += 1;
+        }
+        let mut class_counts = Vec::new();
+        for label in 0..256 {
+            if counts[label] > 0 {
+                class_counts.push((label as u8, counts[label]));
+            }
+        }
+        class_counts
+    }
+}
@@ -0,0 +1,726 @@
+extern crate crc32fast;
+extern crate flate2;
+
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::io::Read;
+
+use crate::dataset;
+use crate::error::NnError;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Image {
+    //An image read from a file, with its values turned into [0; 1] like with inout::turn_to_float.
+    //The pixels are stored channel by channel, and row by row within each channel, the same way as the inputs of a dataset.
+    pub width: usize,
+    pub height: usize,
+    pub channels: usize, //1 for grey images and 3 for colour ones. Transparency is blended onto white when the file is read.
+    pub pixels: Vec<f32>,
+}
+
+#[derive(Debug)]
+pub enum ImageError {
+    Io(io::Error),
+    UnknownFormat, //The file doesn't start like a PGM/PPM, BMP or PNG file.
+    Malformed(String), //What was wrong with the file.
+    Unsupported(String), //A part of the format that isn't read, like compressed BMP files.
+}
+
+impl fmt::Display for ImageError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ImageError::Io(error) => write!(f, "Couldn't read the image: {}", error),
+            ImageError::UnknownFormat => write!(f, "The file isn't a PGM, PPM, BMP or PNG image"),
+            ImageError::Malformed(details) => write!(f, "The image is malformed: {}", details),
+            ImageError::Unsupported(details) => write!(f, "The image uses something that isn't supported: {}", details),
+        }
+    }
+}
+
+impl Error for ImageError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ImageError::Io(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for ImageError {
+    fn from(error: io::Error) -> ImageError {
+        ImageError::Io(error)
+    }
+}
+
+fn malformed<T>(details: &str) -> Result<T, ImageError> {
+    Err(ImageError::Malformed(details.to_string()))
+}
+
+impl Image {
+    pub fn new(width: usize, height: usize, channels: usize, pixels: Vec<f32>) -> Result<Image, NnError> {
+        if channels != 1 && channels != 3 {
+            return Err(NnError::Config(format!("Images can only have 1 or 3 channels, not {:?}", channels)))
+        }
+        if width == 0 || height == 0 {
+            return Err(NnError::Config(format!("An image must have pixels, not a width of {:?} and a height of {:?}", width, height)))
+        }
+        match width.checked_mul(height).and_then(|plane| plane.checked_mul(channels)) {
+            Some(count) if count == pixels.len() => {},
+            Some(count) => return Err(NnError::shape_mismatch("the pixels of the image", count, pixels.len())),
+            None => return Err(NnError::Config(format!("An image of {:?} by {:?} pixels is too big", width, height))),
+        }
+        Ok(Image {
+            width: width,
+            height: height,
+            channels: channels,
+            pixels: pixels,
+        })
+    }
+
+    pub fn shape(&self) -> dataset::Shape {
+        dataset::Shape::image(self.channels, self.height, self.width)
+    }
+
+    pub fn to_grey(&self) -> Image {
+        //Colour images are turned grey with the usual weights for how bright each colour looks.
+        if self.channels == 1 {
+            return self.clone()
+        }
+        let plane = self.width * self.height;
+        let pixels = (0..plane).map(|pos| 0.299 * self.pixels[pos] + 0.587 * self.pixels[plane + pos] + 0.114 * self.pixels[2*plane + pos]).collect();
+        Image {
+            width: self.width,
+            height: self.height,
+            channels: 1,
+            pixels: pixels,
+        }
+    }
+
+    pub fn to_colour(&self) -> Image {
+        if self.channels == 3 {
+            return self.clone()
+        }
+        Image {
+            width: self.width,
+            height: self.height,
+            channels: 3,
+            pixels: [&self.pixels[..], &self.pixels[..], &self.pixels[..]].concat(),
+        }
+    }
+
+    pub fn resize(&self, width: usize, height: usize) -> Image {
+        //Shrinking averages the pixels that are merged together, while enlarging interpolates between them.
+        let mut pixels = Vec::new();
+        for channel in 0..self.channels {
+            let plane = &self.pixels[channel * self.width * self.height..(channel + 1) * self.width * self.height];
+            let mut rows = Vec::new();
+            for y in 0..self.height {
+                rows.extend(resample(&plane[y * self.width..(y + 1) * self.width], width));
+            }
+            let mut resized = vec![0.0; width * height];
+            for x in 0..width {
+                let column: Vec<f32> = (0..self.height).map(|y| rows[y * width + x]).collect();
+                let column = resample(&column, height);
+                for y in 0..height {
+                    resized[y * width + x] = column[y];
+                }
+            }
+            pixels.extend(resized);
+        }
+        Image {
+            width: width,
+            height: height,
+            channels: self.channels,
+            pixels: pixels,
+        }
+    }
+
+    pub fn fit_to(&self, shape: dataset::Shape) -> Result<Vec<f32>, NnError> {
+        //Turns the image into an input of the given shape, changing the number of channels and resizing it as needed.
+        let image = match shape.channels {
+            1 => self.to_grey(),
+            3 => self.to_colour(),
+            channels => return Err(NnError::Config(format!("Images can only be fitted to 1 or 3 channels, not {:?}", channels))),
+        };
+        Ok(image.resize(shape.width, shape.height).pixels)
+    }
+}
+
+fn resample(values: &[f32], length: usize) -> Vec<f32> {
+    //Resizes a line of pixels. Each new pixel covers scale old pixels.
+    //An empty line has nothing to stretch, so it comes out as black.
+    if values.is_empty() {
+        return vec![0.0; length]
+    }
+    let scale = values.len() as f32 / length as f32;
+    let mut output = Vec::new();
+    for num in 0..length {
+        if scale > 1.0 {
+            let start = num as f32 * scale;
+            let end = start + scale;
+            let mut total = 0.0;
+            for pos in start.floor() as usize..(end.ceil() as usize).min(values.len()) {
+                let overlap = (end.min(pos as f32 + 1.0) - start.max(pos as f32)).max(0.0);
+                total += values[pos] * overlap;
+            }
+            output.push(total / scale);
+        } else {
+            let centre = ((num as f32 + 0.5) * scale - 0.5).max(0.0).min(values.len() as f32 - 1.0);
+            let left = centre.floor() as usize;
+            let right = (left + 1).min(values.len() - 1);
+            let share = centre - left as f32;
+            output.push(values[left] * (1.0 - share) + values[right] * share);
+        }
+    }
+    output
+}
+
+pub fn read(filename: &str) -> Result<Image, ImageError> {
+    //Reads a PGM/PPM, BMP or PNG file, telling them apart by how they start.
+    decode(&fs::read(filename)?)
+}
+
+pub fn decode(bytes: &[u8]) -> Result<Image, ImageError> {
+    if bytes.starts_with(&PNG_SIGNATURE) {
+        decode_png(bytes)
+    } else if bytes.starts_with(b"BM") {
+        decode_bmp(bytes)
+    } else if bytes.len() >= 2 && bytes[0] == b'P' && b"2356".contains(&bytes[1]) {
+        decode_pnm(bytes)
+    } else {
+        Err(ImageError::UnknownFormat)
+    }
+}
+
+fn decode_pnm(bytes: &[u8]) -> Result<Image, ImageError> {
+    //P2 and P5 are grey PGM files, P3 and P6 are colour PPM files. The first of each is written as text, the second as bytes.
+    //The header is the width, height and largest value, separated by whitespace, with comments starting with #.
+    let channels = if bytes[1] == b'2' || bytes[1] == b'5' {1} else {3};
+    let is_text = bytes[1] == b'2' || bytes[1] == b'3';
+    let mut pos = 2;
+    let mut header = Vec::new();
+    while header.len() < 3 {
+        match next_pnm_token(bytes, &mut pos) {
+            Some(token) => header.push(token.parse::<usize>().or_else(|_| malformed("a PNM header value isn't a number"))?),
+            None => return malformed("the PNM header ends early"),
+        }
+    }
+    let (width, height, max_value) = (header[0], header[1], header[2]);
+    if max_value == 0 || max_value > 65535 {
+        return malformed("the largest PNM value must be between 1 and 65535")
+    }
+    if width == 0 || height == 0 {
+        return malformed("the PNM image has no pixels")
+    }
+    let count = match width.checked_mul(height).and_then(|plane| plane.checked_mul(channels)) {
+        Some(count) => count,
+        None => return malformed("the PNM image is too big"),
+    };
+    let mut values = Vec::new();
+    if is_text {
+        for _ in 0..count {
+            match next_pnm_token(bytes, &mut pos) {
+                Some(token) => values.push(token.parse::<usize>().or_else(|_| malformed("a PNM value isn't a number"))?),
+                None => return malformed("the PNM file ends early"),
+            }
+        }
+    } else {
+        //A single whitespace byte separates the header from the values.
+        pos += 1;
+        let value_size = if max_value > 255 {2} else {1};
+        if (bytes.len().saturating_sub(pos)) / value_size < count {
+            return malformed("the PNM file ends early")
+        }
+        for num in 0..count {
+            let start = pos + num * value_size;
+            values.push(if value_size == 2 {(bytes[start] as usize) << 8 | bytes[start + 1] as usize} else {bytes[start] as usize});
+        }
+    }
+    //The values are stored pixel by pixel, so colour images have to be split into channels.
+    let mut pixels = vec![0.0; count];
+    for num in 0..width * height {
+        for channel in 0..channels {
+            pixels[channel * width * height + num] = values[num * channels + channel].min(max_value) as f32 / max_value as f32;
+        }
+    }
+    Ok(Image {
+        width: width,
+        height: height,
+        channels: channels,
+        pixels: pixels,
+    })
+}
+
+fn next_pnm_token<'a>(bytes: &'a [u8], pos: &mut usize) -> Option<&'a str> {
+    loop {
+        while *pos < bytes.len() && bytes[*pos].is_ascii_whitespace() {
+            *pos += 1;
+        }
+        if *pos < bytes.len() && bytes[*pos] == b'#' {
+            while *pos < bytes.len() && bytes[*pos] != b'\n' {
+                *pos += 1;
+            }
+        } else {
+            break
+        }
+    }
+    let start = *pos;
+    while *pos < bytes.len() && !bytes[*pos].is_ascii_whitespace() {
+        *pos += 1;
+    }
+    if start == *pos {None} else {std::str::from_utf8(&bytes[start..*pos]).ok()}
+}
+
+fn read_u16_le(bytes: &[u8], pos: usize) -> usize {
+    bytes[pos] as usize | (bytes[pos + 1] as usize) << 8
+}
+
+fn read_u32_le(bytes: &[u8], pos: usize) -> u32 {
+    u32::from_le_bytes([bytes[pos], bytes[pos + 1], bytes[pos + 2], bytes[pos + 3]])
+}
+
+fn decode_bmp(bytes: &[u8]) -> Result<Image, ImageError> {
+    //Reads uncompressed BMP files with 1, 2, 4 or 8 bits per pixel and a palette, or 24 or 32 bits per pixel.
+    //Rows are stored from the bottom up unless the height is negative, and each row is padded to a multiple of 4 bytes.
+    if bytes.len() < 54 {
+        return malformed("the BMP headers end early")
+    }
+    let data_start = read_u32_le(bytes, 10) as usize;
+    let header_size = read_u32_le(bytes, 14) as usize;
+    let width = read_u32_le(bytes, 18) as i32;
+    let signed_height = read_u32_le(bytes, 22) as i32;
+    let bits_per_pixel = read_u16_le(bytes, 28);
+    let compression = read_u32_le(bytes, 30);
+    if width <= 0 || signed_height == 0 {
+        return malformed("the BMP image has no pixels")
+    }
+    if compression != 0 {
+        return Err(ImageError::Unsupported(format!("BMP compression {:?}", compression)))
+    }
+    let (width, height) = (width as usize, (signed_height as i64).abs() as usize);
+    let palette = if bits_per_pixel == 1 || bits_per_pixel == 2 || bits_per_pixel == 4 || bits_per_pixel == 8 {
+            let stated_colours = read_u32_le(bytes, 46) as usize;
+            let colour_count = if stated_colours == 0 {1 << bits_per_pixel} else {stated_colours};
+            let palette_start = 14 + header_size;
+            if bytes.len() < palette_start + 4 * colour_count {
+                return malformed("the BMP palette ends early")
+            }
+            //Each colour is stored as blue, green, red and an unused byte.
+            (0..colour_count).map(|num| {
+                let pos = palette_start + 4 * num;
+                [bytes[pos + 2], bytes[pos + 1], bytes[pos]]
+            }).collect()
+        } else if bits_per_pixel == 24 || bits_per_pixel == 32 {
+            Vec::new()
+        } else {
+            return Err(ImageError::Unsupported(format!("{:?} bits per pixel in a BMP file", bits_per_pixel)))
+        };
+    let row_size = (width * bits_per_pixel + 31) / 32 * 4;
+    match row_size.checked_mul(height).and_then(|size| size.checked_add(data_start)) {
+        Some(end) if end <= bytes.len() => {},
+        _ => return malformed("the BMP pixels end early"),
+    }
+    let plane = width * height;
+    let mut pixels = vec![0.0; 3 * plane];
+    for row in 0..height {
+        let y = if signed_height > 0 {height - 1 - row} else {row};
+        let row_bytes = &bytes[data_start + row * row_size..data_start + (row + 1) * row_size];
+        for x in 0..width {
+            let colour = if bits_per_pixel <= 8 {
+                    let bit_pos = x * bits_per_pixel;
+                    let index = (row_bytes[bit_pos / 8] >> (8 - bits_per_pixel - bit_pos % 8)) as usize & ((1 << bits_per_pixel) - 1);
+                    *palette.get(index).ok_or_else(|| ImageError::Malformed(String::from("a BMP pixel is outside the palette")))?
+                } else {
+                    let pos = x * bits_per_pixel / 8;
+                    [row_bytes[pos + 2], row_bytes[pos + 1], row_bytes[pos]]
+                };
+            for channel in 0..3 {
+                pixels[channel * plane + y * width + x] = colour[channel] as f32 / 255.0;
+            }
+        }
+    }
+    let image = Image {
+        width: width,
+        height: height,
+        channels: 3,
+        pixels: pixels,
+    };
+    Ok(if is_grey(&image) {image.to_grey()} else {image})
+}
+
+fn is_grey(image: &Image) -> bool {
+    //Whether every pixel of a colour image has the same value in all channels, so it can be kept as a grey image.
+    let plane = image.width * image.height;
+    (0..plane).all(|pos| image.pixels[pos] == image.pixels[plane + pos] && image.pixels[pos] == image.pixels[2*plane + pos])
+}
+
+const PNG_SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A];
+
+const ADAM7_PASSES: [(usize, usize, usize, usize); 7] = [(0, 0, 8, 8), (4, 0, 8, 8), (0, 4, 4, 8), (2, 0, 4, 4), (0, 2, 2, 4), (1, 0, 2, 2), (0, 1, 1, 2)]; //The left, top, column step and row step of each pass.
+
+fn decode_png(bytes: &[u8]) -> Result<Image, ImageError> {
+    //Reads a PNG file of any colour type and bit depth, interlaced or not.
+    //The file is a series of chunks, each a length, a type, the data and a CRC. The image data is split across IDAT chunks,
+    //and is zlib compressed, with each row starting with a byte saying how it's filtered.
+    let mut pos = PNG_SIGNATURE.len();
+    let mut header = None;
+    let mut palette = Vec::new();
+    let mut palette_alpha = Vec::new();
+    let mut compressed = Vec::new();
+    loop {
+        if bytes.len() < pos + 12 {
+            return malformed("the PNG file ends before its IEND chunk")
+        }
+        let length = u32::from_be_bytes([bytes[pos], bytes[pos + 1], bytes[pos + 2], bytes[pos + 3]]) as usize;
+        let chunk_type = &bytes[pos + 4..pos + 8];
+        if bytes.len() < pos + 12 + length {
+            return malformed("a PNG chunk ends early")
+        }
+        let data = &bytes[pos + 8..pos + 8 + length];
+        let checksum = u32::from_be_bytes([bytes[pos + 8 + length], bytes[pos + 9 + length], bytes[pos + 10 + length], bytes[pos + 11 + length]]);
+        if crc32fast::hash(&bytes[pos + 4..pos + 8 + length]) != checksum {
+            return malformed(&format!("the CRC of a {} chunk doesn't match, so the file is damaged", String::from_utf8_lossy(chunk_type)))
+        }
+        match chunk_type {
+            b"IHDR" => {
+                if length != 13 {
+                    return malformed("the PNG header has the wrong length")
+                }
+                header = Some(PngHeader {
+                    width: u32::from_be_bytes([data[0], data[1], data[2], data[3]]) as usize,
+                    height: u32::from_be_bytes([data[4], data[5], data[6], data[7]]) as usize,
+                    bit_depth: data[8] as usize,
+                    colour_type: data[9],
+                    is_interlaced: data[12] == 1,
+                });
+            },
+            b"PLTE" => palette = data.chunks_exact(3).map(|colour| [colour[0], colour[1], colour[2]]).collect(),
+            b"tRNS" => palette_alpha = data.to_vec(),
+            b"IDAT" => compressed.extend_from_slice(data),
+            b"IEND" => break,
+            _ => {},
+        }
+        pos += 12 + length;
+    }
+    let header = match header {
+        Some(header) => header,
+        None => return malformed("the PNG file has no header"),
+    };
+    let samples_per_pixel = match header.colour_type {
+        0 | 3 => 1,
+        2 => 3,
+        4 => 2,
+        6 => 4,
+        colour_type => return malformed(&format!("unknown PNG colour type {:?}", colour_type)),
+    };
+    let valid_depths: &[usize] = match header.colour_type {
+        0 => &[1, 2, 4, 8, 16],
+        3 => &[1, 2, 4, 8],
+        _ => &[8, 16],
+    };
+    if !valid_depths.contains(&header.bit_depth) {
+        return malformed(&format!("a bit depth of {:?} doesn't go with colour type {:?}", header.bit_depth, header.colour_type))
+    }
+    if header.width == 0 || header.height == 0 {
+        return malformed("the PNG image has no pixels")
+    }
+    let bits_per_pixel = samples_per_pixel * header.bit_depth;
+    let passes: Vec<(usize, usize, usize, usize)> = if header.is_interlaced {ADAM7_PASSES.to_vec()} else {vec![(0, 0, 1, 1)]};
+    //The header's size is checked against the image data before anything is made from it, so a small file can't claim a huge image.
+    //The data is only inflated up to that size, so a small file can't unpack into gigabytes either.
+    let mut expected_length = 0usize;
+    for &(left, top, column_step, row_step) in &passes {
+        let pass_width = (header.width + column_step - 1 - left) / column_step;
+        let pass_height = (header.height + row_step - 1 - top) / row_step;
+        if pass_width > 0 && pass_height > 0 {
+            expected_length = expected_length.saturating_add(((pass_width * bits_per_pixel + 7) / 8 + 1).saturating_mul(pass_height));
+        }
+    }
+    let mut raw = Vec::new();
+    flate2::read::ZlibDecoder::new(&compressed[..]).take(expected_length.saturating_add(1) as u64).read_to_end(&mut raw)?;
+    if raw.len() < expected_length {
+        return malformed("the PNG image data ends early")
+    }
+    if raw.len() > expected_length {
+        return malformed("the PNG image data is longer than its header says")
+    }
+    //Every pixel is first read as a colour and an alpha in [0; 1].
+    let mut colours = vec![[0.0f32; 4]; header.width * header.height];
+    let mut raw_pos = 0;
+    for (left, top, column_step, row_step) in passes {
+        let pass_width = (header.width + column_step - 1 - left) / column_step;
+        let pass_height = (header.height + row_step - 1 - top) / row_step;
+        if pass_width == 0 || pass_height == 0 {
+            continue
+        }
+        let row_size = (pass_width * bits_per_pixel + 7) / 8;
+        let pass_length = (row_size + 1) * pass_height;
+        if raw.len() < raw_pos + pass_length {
+            return malformed("the PNG image data ends early")
+        }
+        let rows = unfilter(&raw[raw_pos..raw_pos + pass_length], row_size, pass_height, (bits_per_pixel + 7) / 8)?;
+        raw_pos += pass_length;
+        for row in 0..pass_height {
+            for column in 0..pass_width {
+                let mut samples = [0usize; 4];
+                for sample in 0..samples_per_pixel {
+                    let bit_pos = (column * samples_per_pixel + sample) * header.bit_depth;
+                    let row_bytes = &rows[row * row_size..(row + 1) * row_size];
+                    samples[sample] = match header.bit_depth {
+                        16 => (row_bytes[bit_pos / 8] as usize) << 8 | row_bytes[bit_pos / 8 + 1] as usize,
+                        8 => row_bytes[bit_pos / 8] as usize,
+                        depth => (row_bytes[bit_pos / 8] >> (8 - depth - bit_pos % 8)) as usize & ((1 << depth) - 1),
+                    };
+                }
+                let max_value = ((1usize << header.bit_depth) - 1) as f32;
+                let scaled = |sample: usize| samples[sample] as f32 / max_value;
+                let colour = match header.colour_type {
+                    0 => [scaled(0), scaled(0), scaled(0), 1.0],
+                    2 => [scaled(0), scaled(1), scaled(2), 1.0],
+                    3 => {
+                        let index = samples[0];
+                        let colour = palette.get(index).ok_or_else(|| ImageError::Malformed(String::from("a PNG pixel is outside the palette")))?;
+                        let alpha = palette_alpha.get(index).map_or(1.0, |&alpha| alpha as f32 / 255.0);
+                        [colour[0] as f32 / 255.0, colour[1] as f32 / 255.0, colour[2] as f32 / 255.0, alpha]
+                    },
+                    4 => [scaled(0), scaled(0), scaled(0), scaled(1)],
+                    _ => [scaled(0), scaled(1), scaled(2), scaled(3)],
+                };
+                colours[(top + row * row_step) * header.width + left + column * column_step] = colour;
+            }
+        }
+    }
+    let plane = header.width * header.height;
+    let mut pixels = vec![0.0; 3 * plane];
+    for pos in 0..plane {
+        let [red, green, blue, alpha] = colours[pos];
+        pixels[pos] = red * alpha + 1.0 - alpha;
+        pixels[plane + pos] = green * alpha + 1.0 - alpha;
+        pixels[2*plane + pos] = blue * alpha + 1.0 - alpha;
+    }
+    let image = Image {
+        width: header.width,
+        height: header.height,
+        channels: 3,
+        pixels: pixels,
+    };
+    Ok(if header.colour_type == 0 || header.colour_type == 4 || is_grey(&image) {image.to_grey()} else {image})
+}
+
+struct PngHeader {
+    width: usize,
+    height: usize,
+    bit_depth: usize,
+    colour_type: u8,
+    is_interlaced: bool,
+}
+
+fn unfilter(filtered: &[u8], row_size: usize, row_count: usize, pixel_size: usize) -> Result<Vec<u8>, ImageError> {
+    //Undoes the filter of each row. Each filter predicts a byte from the ones to the left and above, and stores the difference.
+    let mut rows = vec![0u8; row_size * row_count];
+    for row in 0..row_count {
+        let filter = filtered[row * (row_size + 1)];
+        let source = &filtered[row * (row_size + 1) + 1..(row + 1) * (row_size + 1)];
+        for pos in 0..row_size {
+            let left = if pos >= pixel_size {rows[row * row_size + pos - pixel_size]} else {0};
+            let above = if row > 0 {rows[(row - 1) * row_size + pos]} else {0};
+            let above_left = if row > 0 && pos >= pixel_size {rows[(row - 1) * row_size + pos - pixel_size]} else {0};
+            let prediction = match filter {
+                0 => 0,
+                1 => left,
+                2 => above,
+                3 => ((left as u16 + above as u16) / 2) as u8,
+                4 => paeth(left, above, above_left),
+                _ => return malformed(&format!("unknown PNG filter {:?}", filter)),
+            };
+            rows[row * row_size + pos] = source[pos].wrapping_add(prediction);
+        }
+    }
+    Ok(rows)
+}
+
+fn paeth(left: u8, above: u8, above_left: u8) -> u8 {
+    //Picks whichever neighbour is closest to left + above - above_left.
+    let estimate = left as i16 + above as i16 - above_left as i16;
+    let left_distance = (estimate - left as i16).abs();
+    let above_distance = (estimate - above as i16).abs();
+    let above_left_distance = (estimate - above_left as i16).abs();
+    if left_distance <= above_distance && left_distance <= above_left_distance {
+        left
+    } else if above_distance <= above_left_distance {
+        above
+    } else {
+        above_left
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    fn bmp(width: i32, height: i32, bits_per_pixel: u16, palette: &[[u8; 3]], rows: &[Vec<u8>]) -> Vec<u8> {
+        //A BMP file with a 40 byte header. The rows are given top to bottom and stored bottom up, each padded to 4 bytes.
+        let data_start = 54 + 4 * palette.len() as u32;
+        let mut bytes = b"BM".to_vec();
+        bytes.extend(&[0u8; 8]);
+        bytes.extend(&data_start.to_le_bytes());
+        bytes.extend(&40u32.to_le_bytes());
+        bytes.extend(&width.to_le_bytes());
+        bytes.extend(&height.to_le_bytes());
+        bytes.extend(&1u16.to_le_bytes());
+        bytes.extend(&bits_per_pixel.to_le_bytes());
+        bytes.extend(&[0u8; 16]);
+        bytes.extend(&(palette.len() as u32).to_le_bytes());
+        bytes.extend(&[0u8; 4]);
+        for colour in palette {
+            bytes.extend(&[colour[2], colour[1], colour[0], 0]);
+        }
+        for row in rows.iter().rev() {
+            bytes.extend(row);
+            while (bytes.len() - data_start as usize) % 4 != 0 {
+                bytes.push(0);
+            }
+        }
+        bytes
+    }
+
+    fn png_chunk(bytes: &mut Vec<u8>, chunk_type: &[u8], data: &[u8]) {
+        bytes.extend(&(data.len() as u32).to_be_bytes());
+        let start = bytes.len();
+        bytes.extend(chunk_type);
+        bytes.extend(data);
+        let checksum = crc32fast::hash(&bytes[start..]);
+        bytes.extend(&checksum.to_be_bytes());
+    }
+
+    fn png(width: u32, height: u32, colour_type: u8, is_interlaced: bool, raw: &[u8]) -> Vec<u8> {
+        //An 8 bit PNG file with the raw, already filtered, rows compressed into a single IDAT chunk.
+        let mut header = Vec::new();
+        header.extend(&width.to_be_bytes());
+        header.extend(&height.to_be_bytes());
+        header.extend(&[8, colour_type, 0, 0, is_interlaced as u8]);
+        let mut encoder = flate2::write::ZlibEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(raw).unwrap();
+        let mut bytes = PNG_SIGNATURE.to_vec();
+        png_chunk(&mut bytes, b"IHDR", &header);
+        png_chunk(&mut bytes, b"IDAT", &encoder.finish().unwrap());
+        png_chunk(&mut bytes, b"IEND", &[]);
+        bytes
+    }
+
+    fn interlaced_grey_raw() -> Vec<u8> {
+        //A 3 by 3 grey image where the pixel at (x, y) is 10 * y + x, split into the Adam7 passes that have pixels,
+        //each row starting with filter 0.
+        vec![
+            0, 0, //Pass 1: (0, 0)
+            0, 2, //Pass 4: (2, 0)
+            0, 20, 22, //Pass 5: (0, 2) and (2, 2)
+            0, 1, 0, 21, //Pass 6: (1, 0), then (1, 2)
+            0, 10, 11, 12, //Pass 7: the whole of row 1
+        ]
+    }
+
+    fn scaled(values: &[u8], max_value: f32) -> Vec<f32> {
+        values.iter().map(|&value| value as f32 / max_value).collect()
+    }
+
+    fn assert_close(found: &[f32], expected: &[f32]) {
+        //Grey images made from colour ones go through the brightness weights, which aren't exact in f32.
+        assert_eq!(found.len(), expected.len());
+        for (found_value, expected_value) in found.iter().zip(expected) {
+            assert!((found_value - expected_value).abs() < 1e-6, "{:?} != {:?}", found, expected);
+        }
+    }
+
+    #[test]
+    fn reads_pgm() {
+        let text = decode(b"P2\n# a comment\n3 2\n10\n0 5 10\n10 5 0\n").unwrap();
+        assert_eq!((text.width, text.height, text.channels), (3, 2, 1));
+        assert_eq!(text.pixels, scaled(&[0, 5, 10, 10, 5, 0], 10.0));
+        let mut binary = b"P5 2 2 255\n".to_vec();
+        binary.extend(&[0, 51, 204, 255]);
+        assert_eq!(decode(&binary).unwrap().pixels, scaled(&[0, 51, 204, 255], 255.0));
+    }
+
+    #[test]
+    fn reads_ppm() {
+        let mut bytes = b"P6\n2 1\n255\n".to_vec();
+        bytes.extend(&[255, 0, 0, 10, 20, 30]);
+        let image = decode(&bytes).unwrap();
+        assert_eq!((image.width, image.height, image.channels), (2, 1, 3));
+        //Channel by channel: the reds, then the greens, then the blues.
+        assert_eq!(image.pixels, scaled(&[255, 10, 0, 20, 0, 30], 255.0));
+    }
+
+    #[test]
+    fn reads_24_bit_bmp() {
+        //Pixels are stored as blue, green, red.
+        let rows = vec![vec![0, 0, 255, 0, 255, 0], vec![255, 0, 0, 30, 20, 10]];
+        let image = decode(&bmp(2, 2, 24, &[], &rows)).unwrap();
+        assert_eq!((image.width, image.height, image.channels), (2, 2, 3));
+        assert_eq!(image.pixels, scaled(&[255, 0, 0, 10, 0, 255, 0, 20, 0, 0, 255, 30], 255.0));
+    }
+
+    #[test]
+    fn reads_8_bit_bmp() {
+        let palette = [[0, 0, 0], [200, 100, 50], [255, 255, 255]];
+        let rows = vec![vec![0, 1, 2], vec![2, 2, 1]];
+        let image = decode(&bmp(3, 2, 8, &palette, &rows)).unwrap();
+        assert_eq!((image.width, image.height, image.channels), (3, 2, 3));
+        assert_eq!(image.pixels, scaled(&[0, 200, 255, 255, 255, 200, 0, 100, 255, 255, 255, 100, 0, 50, 255, 255, 255, 50], 255.0));
+        //A top-down file has a negative height.
+        let top_down = decode(&bmp(3, -2, 8, &palette, &[rows[1].clone(), rows[0].clone()])).unwrap();
+        assert_eq!(top_down.pixels, image.pixels);
+    }
+
+    #[test]
+    fn reads_interlaced_png() {
+        let image = decode(&png(3, 3, 0, true, &interlaced_grey_raw())).unwrap();
+        assert_eq!((image.width, image.height, image.channels), (3, 3, 1));
+        assert_close(&image.pixels, &scaled(&[0, 1, 2, 10, 11, 12, 20, 21, 22], 255.0));
+    }
+
+    #[test]
+    fn rejects_png_with_bad_crc() {
+        let mut bytes = png(3, 3, 0, true, &interlaced_grey_raw());
+        let last = bytes.len() - 1;
+        bytes[last] ^= 1;
+        match decode(&bytes) {
+            Err(ImageError::Malformed(details)) => assert!(details.contains("CRC"), "{}", details),
+            other => panic!("Expected a CRC error, found {:?}", other),
+        }
+    }
+
+    #[test]
+    fn rejects_png_data_longer_than_header() {
+        //A 1 by 1 image whose data inflates to a megabyte, far more than the 2 bytes its header allows.
+        let mut raw = vec![0, 7];
+        raw.extend(vec![0; 1 << 20]);
+        match decode(&png(1, 1, 0, false, &raw)) {
+            Err(ImageError::Malformed(details)) => assert!(details.contains("longer"), "{}", details),
+            other => panic!("Expected the data to be too long, found {:?}", other),
+        }
+        assert!(decode(&png(1, 1, 0, false, &raw[..2])).is_ok());
+    }
+
+    #[test]
+    fn empty_images_are_rejected() {
+        assert!(Image::new(0, 4, 1, Vec::new()).is_err());
+        assert!(Image::new(4, 0, 3, Vec::new()).is_err());
+        assert!(decode(b"P5 0 2 255\n").is_err());
+        assert_eq!(resample(&[], 3), vec![0.0; 3]);
+    }
+
+    #[test]
+    fn resize_keeps_flat_image_flat() {
+        let image = Image::new(4, 2, 1, vec![0.5; 8]).unwrap();
+        for &(width, height) in &[(2, 1), (8, 6), (1, 1)] {
+            let resized = image.resize(width, height);
+            assert_eq!(resized.pixels.len(), width * height);
+            assert!(resized.pixels.iter().all(|&value| (value - 0.5).abs() < 1e-6));
+        }
+    }
+}
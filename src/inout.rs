@@ -1,86 +1,55 @@
 extern crate csv;
 
-use std::error::Error;
 use std::fs;
-use std::fmt;
+use std::io;
+use std::path::Path;
 
+use crate::error::NnError;
+use crate::idx;
+use crate::labels;
+use crate::model_file;
 use crate::nodes_layers;
+use crate::scaling;
 
-#[derive(Debug)]
-struct UnfittingLayerError {
-    details: String
-}
-
-impl UnfittingLayerError {
-    fn new(msg: &str) -> UnfittingLayerError {
-        UnfittingLayerError{details: msg.to_string()}
-    }
-}
-
-impl fmt::Display for UnfittingLayerError {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f,"{}",self.details)
-    }
-}
-
-impl Error for UnfittingLayerError {
-    fn description(&self) -> &str {
-        &self.details
+fn raises_unfitting_layer_error(nodes: &Vec<nodes_layers::Node>, desired_weight_count: usize, desired_node_count: usize) -> Result<(), NnError> {
+    if desired_node_count != nodes.len() {
+        return Err(NnError::shape_mismatch("the nodes of the layer", desired_node_count, nodes.len()))
     }
-}
-
-fn raises_unfitting_layer_error(nodes: &Vec<nodes_layers::Node>, desired_weight_count: usize, desired_node_count: usize) -> Result<(),UnfittingLayerError> {
-    if desired_node_count == 0 && nodes.len() == 0 {
-        Ok(())
-    } else if desired_node_count != nodes.len() {
-        let message = format!("Expected the layer to have {:?} nodes, found that it has {:?}", desired_node_count, nodes.len());
-        Err(UnfittingLayerError::new(&message))
-    } else if desired_weight_count != nodes[0].weights.len() {
-        let message = format!("Expected each node to have {:?} weights, found that they have {:?}", desired_weight_count, nodes[0].weights.len());
-        Err(UnfittingLayerError::new(&message))
-    } else {
-        Ok(())
+    for node in nodes {
+        if desired_weight_count != node.weights.len() {
+            return Err(NnError::shape_mismatch("the weights of each node", desired_weight_count, node.weights.len()))
+        }
     }
+    Ok(())
 }
 
-pub fn unpack_labels(filename: &str) -> Vec<u8> {
+pub fn unpack_labels(filename: &str) -> Result<Vec<u8>, NnError> {
     //Unpacks a file assuming it's a list of labels for MNIST images of numbers.
     //By default, this is in the form of u8s that are equal to the number displayed.
     //To convert them to output values that work with the nodes_layers::Network struct, use turn_to_result
-    let content = fs::read(filename)
-        .expect("Something went wrong while reading labels")
-        [8..].to_vec();
-    content
+    Ok(idx::read_labels(filename)?)
 }
 
-pub fn turn_to_result(input: Vec<u8>) -> Vec<Vec<f32>> {
+pub fn turn_to_result(input: Vec<u8>, class_count: usize) -> Result<Vec<Vec<f32>>, NnError> {
     //Turns a list of u8 values into vectors that can be used by the nodes_layers::Network struct.
+    //Each label n gets a 1.0 at position n, so class_count must be bigger than every label. Use labels::LabelMap::to_results for other mappings.
     let mut output = Vec::new();
     for number in input {
-        let mut element = vec![0.0;10];
+        if number as usize >= class_count {
+            return Err(NnError::Config(format!("The label {:?} doesn't fit in {:?} classes", number, class_count)))
+        }
+        let mut element = vec![0.0;class_count];
         element[number as usize] = 1.0;
         output.push(element);
     }
-    output
+    Ok(output)
 }
 
-pub fn unpack_images(filename: &str) -> Vec<Vec<f32>> {
+pub fn unpack_images(filename: &str) -> Result<Vec<Vec<f32>>, NnError> {
     //Unpacks a file assuming it's a list of images of handwritten digits from the MNIST database.
     //It also turns them into vectors to be used as inputs by the nodes_layers::Network struct.
-    //Since the values are by default u8 values between 0 and 255, and the nodes_layers::Network struct only accepts f32 values between 0.0 and 1.0, turn_to_float is used on them.
-    let raw_content = fs::read(filename)
-        .expect("Something went wrong while reading images")
-        [16..].to_vec();
-    let raw_content = turn_to_float(raw_content);
-    let content_as_iter = raw_content.chunks_exact(28*28);
-    if content_as_iter.remainder().len() > 0 {
-        panic!("An image was only partially formed. It had only {:?} elements, when it should have had {:?}", content_as_iter.remainder().len(), 28*28)
-    }
-    let mut content = Vec::new();
-    for chonk in content_as_iter {
-        content.push(chonk.to_vec());
-    }
-    content
+    //Since the values are by default u8 values between 0 and 255, and the nodes_layers::Network struct only accepts f32 values between 0.0 and 1.0, they're scaled like with turn_to_float.
+    Ok(idx::read_images(filename)?.inputs())
 }
 
 pub fn turn_to_float(input: Vec<u8>) -> Vec<f32> {
@@ -93,81 +62,275 @@ pub fn turn_to_float(input: Vec<u8>) -> Vec<f32> {
 }
 
 
-pub fn write_network(network: nodes_layers::Network, file_paths: Vec<&str>) -> Result<(), Box<dyn Error>> {
+pub fn write_network(network: nodes_layers::Network, file_paths: Vec<&str>) -> Result<(), NnError> {
     //Takes a nodes_layers::Network and writes it onto a series of .csv files. Each file represents a layer, and each line is a node.
     //The first value of a line is the node's bias, and the rest are its weights. They are written as u32 values to make them easier to read for read_network.
-    if network.layer_count < file_paths.len() {
-        panic!("There's not enough file paths for the number of layers")
-    }
     if network.layer_count > file_paths.len() {
-        println!("There were more file paths provided than there were layers.");
+        return Err(NnError::Config(format!("There are {:?} file paths for the {:?} layers of the network", file_paths.len(), network.layer_count)))
+    }
+    //Any file paths beyond the number of layers are left alone.
+    if file_paths.is_empty() {
+        return Ok(())
     }
     for num in 0..network.layer_count {
-        let mut wtr = csv::Writer::from_path(file_paths[num])?;
-        for node in network.layers[num].nodes.clone() {
-            wtr.write_record(node)?;
+        write_layer(&network.layers[num], file_paths[num])?;
+    }
+    //The scalers are written next to the first layer, so read_network gives back the same preprocessing. Old ones are removed if there are none.
+    for (scaler, name) in [(&network.input_scaler, "input"), (&network.target_scaler, "target")].iter() {
+        let scaler_path = scaler_path(file_paths[0], name);
+        match scaler {
+            Some(scaler) => write_scaler(scaler, &scaler_path)?,
+            None => if let Err(error) = fs::remove_file(&scaler_path) {
+                if error.kind() != io::ErrorKind::NotFound {
+                    return Err(NnError::Io(error))
+                }
+            },
         }
-        wtr.flush()?;
     }
+    //The layer files only hold numbers, so the activations, the loss and the label map are written next to the first layer as well, as the header of a model file.
+    let mut wtr = csv::WriterBuilder::new()
+        .flexible(true)
+        .from_path(settings_path(file_paths[0]))
+    ?;
+    model_file::ModelHeader::new(&network, &model_file::ModelMetadata::new()).write_records(&mut wtr)?;
+    wtr.flush()?;
     Ok(())
 }
 
-pub fn read_network(file_paths: Vec<&str>, network_form: &[usize], learning_rate: f32, make_replacements: bool) -> Result<nodes_layers::Network, Box<dyn Error>> {
+fn path_stem(first_layer_path: &str) -> &str {
+    if first_layer_path.ends_with(".csv") {&first_layer_path[..first_layer_path.len() - ".csv".len()]} else {first_layer_path}
+}
+
+pub fn scaler_path(first_layer_path: &str, name: &str) -> String {
+    //Where write_network puts a network's scaler, like datas\network1_input_scaler.csv for the input scaler.
+    format!("{}_{}_scaler.csv", path_stem(first_layer_path), name)
+}
+
+pub fn settings_path(first_layer_path: &str) -> String {
+    //Where write_network puts the activations, the loss and the label map of a network, like datas\network1_settings.csv.
+    format!("{}_settings.csv", path_stem(first_layer_path))
+}
+
+pub fn write_layer(layer: &nodes_layers::Layer, file_path: &str) -> Result<(), NnError> {
+    //Writes a single layer in the same format as write_network, for models that aren't a plain chain of layers.
+    let mut wtr = csv::Writer::from_path(file_path)?;
+    for node in layer.nodes.clone() {
+        wtr.write_record(node)?;
+    }
+    wtr.flush()?;
+    Ok(())
+}
+
+pub fn write_label_map(label_map: &labels::LabelMap, file_path: &str) -> Result<(), NnError> {
+    //Writes a label map as a .csv file with a line per class, holding the label as it's written in the data followed by the class name.
+    let mut wtr = csv::Writer::from_path(file_path)?;
+    for num in 0..label_map.class_count() {
+        wtr.write_record(&[&label_map.keys[num], &label_map.names[num]])?;
+    }
+    wtr.flush()?;
+    Ok(())
+}
+
+pub fn read_label_map(file_path: &str) -> Result<labels::LabelMap, NnError> {
+    //Reads a label map written by write_label_map.
+    let mut rdr = csv::ReaderBuilder::new()
+        .has_headers(false)
+        .from_path(file_path)
+    ?;
+    let mut keys = Vec::new();
+    let mut names = Vec::new();
+    for result in rdr.records() {
+        let record = result?;
+        if record.len() < 2 {
+            return Err(NnError::Format(format!("Expected a label and a name on each line of the label map, found {:?} values", record.len())))
+        }
+        keys.push(record[0].to_string());
+        names.push(record[1].to_string());
+    }
+    labels::LabelMap::from_keys(keys).with_names(names)
+}
+
+pub fn write_scaler(scaler: &scaling::Scaler, file_path: &str) -> Result<(), NnError> {
+    //Writes a scaler as a .csv file with the offsets on the first line and the scales on the second.
+    //If it whitens, the whitening's scales follow on the third line, and then a line per component.
+    //Like the weights, they're written as u32 values so they're read back exactly.
+    let mut wtr = csv::WriterBuilder::new()
+        .flexible(true)
+        .from_path(file_path)
+    ?;
+    write_scaler_records(scaler, &mut wtr)?;
+    wtr.flush()?;
+    Ok(())
+}
+
+pub fn write_scaler_records<W: io::Write>(scaler: &scaling::Scaler, wtr: &mut csv::Writer<W>) -> Result<(), NnError> {
+    //Writes the lines of a scaler to a writer that's already open, so it can be part of a bigger file.
+    wtr.write_record(scaler.offsets.iter().map(|offset| offset.to_bits().to_string()))?;
+    wtr.write_record(scaler.scales.iter().map(|scale| scale.to_bits().to_string()))?;
+    if let Some(whitening) = &scaler.whitening {
+        wtr.write_record(whitening.scales.iter().map(|scale| scale.to_bits().to_string()))?;
+        for component in &whitening.components {
+            wtr.write_record(component.iter().map(|value| value.to_bits().to_string()))?;
+        }
+    }
+    Ok(())
+}
+
+pub fn read_scaler(file_path: &str) -> Result<scaling::Scaler, NnError> {
+    //Reads a scaler written by write_scaler.
+    let mut rdr = csv::ReaderBuilder::new()
+        .has_headers(false)
+        .flexible(true)
+        .from_path(file_path)
+    ?;
+    let mut rows = Vec::new();
+    for result in rdr.records() {
+        rows.push(values_from_record(&result?)?);
+    }
+    scaler_from_rows(rows)
+}
+
+pub fn values_from_record(record: &csv::StringRecord) -> Result<Vec<f32>, NnError> {
+    //Reads a line of f32 values written as u32 values, like the lines of a scaler.
+    let mut values = Vec::new();
+    for field in record.iter() {
+        let bits: u32 = field.parse()?;
+        values.push(f32::from_bits(bits));
+    }
+    Ok(values)
+}
+
+pub fn scaler_from_rows(mut rows: Vec<Vec<f32>>) -> Result<scaling::Scaler, NnError> {
+    //Puts together a scaler from its lines, in the order write_scaler writes them.
+    if rows.len() < 2 || rows.len() == 3 {
+        return Err(NnError::Format(format!("Expected a scaler to have 2 lines, or 3 and more when it whitens, found that it has {:?}", rows.len())))
+    }
+    let components = rows.split_off(3.min(rows.len()));
+    let whitening_scales = rows.split_off(2);
+    let scales = rows.pop().unwrap_or_default();
+    let offsets = rows.pop().unwrap_or_default();
+    if scales.len() != offsets.len() {
+        return Err(NnError::shape_mismatch("the scales of the scaler", offsets.len(), scales.len()))
+    }
+    let whitening = match whitening_scales.into_iter().next() {
+        Some(whitening_scales) => {
+            if whitening_scales.len() != components.len() {
+                return Err(NnError::shape_mismatch("the components of the whitening", whitening_scales.len(), components.len()))
+            }
+            if let Some(component) = components.iter().find(|component| component.len() != offsets.len()) {
+                return Err(NnError::shape_mismatch("a component of the whitening", offsets.len(), component.len()))
+            }
+            Some(scaling::Whitening {
+                components: components,
+                scales: whitening_scales,
+            })
+        },
+        None => None,
+    };
+    Ok(scaling::Scaler {
+        offsets: offsets,
+        scales: scales,
+        whitening: whitening,
+    })
+}
+
+#[derive(Debug)]
+pub struct ReplacedLayer {
+    //A layer read_network couldn't load, which was replaced by a randomized one.
+    pub layer_num: usize,
+    pub file_path: Option<String>, //None if there was no file path for the layer.
+    pub error: Option<NnError>, //Why the file couldn't be read. None if there was no file path.
+}
+
+pub fn read_network(file_paths: Vec<&str>, network_form: &[usize], learning_rate: f32, make_replacements: bool) -> Result<(nodes_layers::Network, Vec<ReplacedLayer>), NnError> {
+    //Reads a network written by write_network. network_form works like node_nums for nodes_layers::Network::new.
+    //With make_replacements, layers that are missing or can't be read are replaced by randomized ones instead of being errors,
+    //and are returned along with the network so the caller can tell that it will perform poorly. Without it, the list is always empty.
+    if network_form.len() < 2 {
+        return Err(NnError::Config(format!("A network needs an input layer and at least one more, but the form has {:?} layers", network_form.len())))
+    }
     let mut layers = Vec::new();
+    let mut replaced_layers = Vec::new();
     for num in 0..network_form.len()-1{
         let layer = if num < file_paths.len() {
             let layer_result = read_layer(file_paths[num], network_form[num], network_form[num+1], learning_rate);
                 match layer_result {
                     Ok(layer) => layer,
                     Err(error) => {
-                        println!("Issue with loading the layer at {:?}: {:?}", file_paths[num], error);
                         if make_replacements {
-                            println!("Generating a randomized layer in its place. If this is testing, the network will obviously perform very poorly.");
+                            replaced_layers.push(ReplacedLayer {layer_num: num, file_path: Some(file_paths[num].to_string()), error: Some(error)});
                             nodes_layers::Layer::new(network_form[num], network_form[num+1], learning_rate)
                         } else {return Err(error)}
                     },
                 }
             } else if make_replacements {
-                println!("More layers were asked for than there were provided file paths");
-                println!("Generating a randomized layer to fill out the remainder. If this is testing, the network will obviously perform very poorly.");
+                replaced_layers.push(ReplacedLayer {layer_num: num, file_path: None, error: None});
                 nodes_layers::Layer::new(network_form[num], network_form[num+1], learning_rate)
             } else {
-                panic!("More layers were asked for than there were provided file paths")
+                return Err(NnError::Config(format!("There are {:?} file paths for the {:?} layers of the network", file_paths.len(), network_form.len() - 1)))
             };
         layers.push(
             layer
         )
     }
-    let layer_count = layers.len();
-    Ok(nodes_layers::Network{
-        layers: layers,
-        layer_count: layer_count,
-        // learning_rate: learning_rate,
-    })
+    //Scalers written by write_network are picked up, so the inputs get the preprocessing the network was trained with.
+    let mut network = nodes_layers::Network::from_layers(layers);
+    if !file_paths.is_empty() {
+        network.input_scaler = read_scaler_if_saved(&scaler_path(file_paths[0], "input"))?;
+        network.target_scaler = read_scaler_if_saved(&scaler_path(file_paths[0], "target"))?;
+        //Networks written before the settings were saved are all sigmoid, trained with the squared error and have numbered outputs, which is what from_layers gives.
+        if let Some(header) = read_settings_if_saved(&settings_path(file_paths[0]))? {
+            if header.layers.len() != network.layer_count {
+                return Err(NnError::shape_mismatch("the layers in the settings", network.layer_count, header.layers.len()))
+            }
+            for num in 0..network.layer_count {
+                network.layers[num].activation = header.layers[num].activation;
+            }
+            network.loss = header.loss;
+            if !header.keys.is_empty() {
+                network = network.with_label_map(labels::LabelMap::from_keys(header.keys).with_names(header.names)?)?;
+            }
+        }
+    }
+    if let Some(input_scaler) = &network.input_scaler {
+        if input_scaler.output_count() != network_form[0] {
+            return Err(NnError::shape_mismatch("the values the input scaler gives", network_form[0], input_scaler.output_count()))
+        }
+    }
+    Ok((network, replaced_layers))
+}
+
+fn read_scaler_if_saved(file_path: &str) -> Result<Option<scaling::Scaler>, NnError> {
+    if Path::new(file_path).exists() {
+        Ok(Some(read_scaler(file_path)?))
+    } else {
+        Ok(None)
+    }
 }
 
-fn read_layer(file_path: &str, desired_weight_count: usize, desired_node_count: usize, learning_rate: f32) -> Result<nodes_layers::Layer, Box<dyn Error>> {
+fn read_settings_if_saved(file_path: &str) -> Result<Option<model_file::ModelHeader>, NnError> {
+    if Path::new(file_path).exists() {
+        let mut rdr = csv::ReaderBuilder::new()
+            .has_headers(false)
+            .flexible(true)
+            .from_path(file_path)
+        ?;
+        Ok(Some(model_file::ModelHeader::read_records(&mut rdr.records())?))
+    } else {
+        Ok(None)
+    }
+}
+
+pub fn read_layer(file_path: &str, desired_weight_count: usize, desired_node_count: usize, learning_rate: f32) -> Result<nodes_layers::Layer, NnError> {
+    //Reads a single layer written by write_layer or write_network, checking that it has the desired shape.
     let mut rdr = csv::ReaderBuilder::new()
         .has_headers(false)
         .from_path(file_path)
     ?;
     let mut nodes = Vec::new();
     for result in rdr.records() {
-        let record = result?;
-        let bias_as_bits: u32 = record[0].parse()?;
-        let bias = f32::from_bits(bias_as_bits);
-        let mut weights = Vec::new();
-        for num in 1..record.len() {
-            let weight: u32 = record[num].parse()?;
-            weights.push(f32::from_bits(weight));
-        }
-        nodes.push(
-            nodes_layers::Node{
-                bias: bias,
-                weights: weights,
-                personal_pos: nodes.len(),
-            }
-        )
+        let node = node_from_record(&result?, nodes.len())?;
+        nodes.push(node);
     }
     raises_unfitting_layer_error(&nodes, desired_weight_count, desired_node_count)?;
     let node_count = nodes.len();
@@ -176,7 +339,37 @@ fn read_layer(file_path: &str, desired_weight_count: usize, desired_node_count:
             nodes: nodes,
             node_count: node_count,
             learning_rate: learning_rate,
+            activation: nodes_layers::Activation::Sigmoid,
         }
     )
 
 }
+
+pub fn node_from_record(record: &csv::StringRecord, personal_pos: usize) -> Result<nodes_layers::Node, NnError> {
+    //Reads a node from a line written by write_layer, with the bias first and the weights after it.
+    if record.is_empty() {
+        return Err(NnError::Format("Expected a node to have at least a bias, found an empty line".to_string()))
+    }
+    let bias_as_bits: u32 = record[0].parse()?;
+    let bias = f32::from_bits(bias_as_bits);
+    let mut weights = Vec::new();
+    let mut active_weights = Vec::new();
+    let mut is_sparse = false;
+    for num in 1..record.len() {
+        //An empty field is a weight that has been pruned.
+        if record[num].is_empty() {
+            is_sparse = true;
+            weights.push(0.0);
+        } else {
+            let weight: u32 = record[num].parse()?;
+            active_weights.push(weights.len());
+            weights.push(f32::from_bits(weight));
+        }
+    }
+    Ok(nodes_layers::Node{
+        bias: bias,
+        weights: weights,
+        personal_pos: personal_pos,
+        active_weights: if is_sparse {Some(active_weights)} else {None},
+    })
+}
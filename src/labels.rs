@@ -0,0 +1,83 @@
+use crate::error::NnError;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct LabelMap {
+    //Connects the labels found in a dataset to the positions of the output layer.
+    //The label at keys[n] is the one whose desired output has a 1.0 at position n.
+    pub keys: Vec<String>, //The labels as they're written in the data, like "5" for a u8 label of 5.
+    pub names: Vec<String>, //What each class is called when shown to people, like "five" or "T-shirt". Same length as keys.
+}
+
+impl LabelMap {
+    pub fn numbered(class_count: usize) -> LabelMap {
+        //A map where the label n goes to position n, which is how MNIST digits are set up.
+        let keys: Vec<String> = (0..class_count).map(|num| num.to_string()).collect();
+        LabelMap {
+            names: keys.clone(),
+            keys: keys,
+        }
+    }
+
+    pub fn from_codes(codes: &[u8]) -> LabelMap {
+        //Makes a map from every distinct label in a list of u8 labels, sorted so the smallest label gets position 0.
+        //EMNIST letters, which are labelled from 1 to 26, get positions 0 to 25 this way.
+        let mut seen = [false; 256];
+        for code in codes {
+            seen[*code as usize] = true;
+        }
+        let keys: Vec<String> = (0..256).filter(|code| seen[*code]).map(|code| code.to_string()).collect();
+        LabelMap {
+            names: keys.clone(),
+            keys: keys,
+        }
+    }
+
+    pub fn from_keys(keys: Vec<String>) -> LabelMap {
+        //Makes a map from labels that aren't numbers, in the order they're given.
+        LabelMap {
+            names: keys.clone(),
+            keys: keys,
+        }
+    }
+
+    pub fn with_names(mut self, names: Vec<String>) -> Result<LabelMap, NnError> {
+        //Replaces the names shown for each class. There must be one name per class.
+        if names.len() != self.keys.len() {
+            return Err(NnError::shape_mismatch("the class names", self.keys.len(), names.len()))
+        }
+        self.names = names;
+        Ok(self)
+    }
+
+    pub fn class_count(&self) -> usize {
+        self.keys.len()
+    }
+
+    pub fn index_of(&self, key: &str) -> Option<usize> {
+        //The output position of a label, if the label is known.
+        self.keys.iter().position(|known_key| known_key == key)
+    }
+
+    pub fn name(&self, index: usize) -> &str {
+        &self.names[index]
+    }
+
+    pub fn to_results(&self, codes: &[u8]) -> Result<Vec<Vec<f32>>, NnError> {
+        //Turns a list of u8 labels into desired output vectors with one position per class.
+        //Works like inout::turn_to_result, but with the positions decided by the map.
+        let mut positions = [None; 256];
+        for code in 0..256 {
+            positions[code] = self.index_of(&code.to_string());
+        }
+        let mut output = Vec::new();
+        for code in codes {
+            let mut element = vec![0.0; self.class_count()];
+            match positions[*code as usize] {
+                Some(position) => element[position] = 1.0,
+                None => return Err(NnError::Config(format!("The label {:?} isn't in the label map", code))),
+            }
+            output.push(element);
+        }
+        Ok(output)
+    }
+}
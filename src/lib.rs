@@ -1,2 +1,24 @@
+pub mod error;
 pub mod nodes_layers;
 pub mod inout;
+pub mod model_file;
+pub mod model_binary;
+pub mod idx;
+pub mod idx_edit;
+pub mod cifar;
+pub mod tabular;
+pub mod image_import;
+pub mod preprocess;
+pub mod labels;
+pub mod dataset;
+pub mod presets;
+pub mod pruning;
+pub mod quantize;
+pub mod ensemble;
+pub mod autoencoder;
+pub mod vae;
+pub mod scaling;
+pub mod regression;
+pub mod saliency;
+pub mod adversarial;
+pub mod augment;
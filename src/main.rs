@@ -1,26 +1,189 @@
 use nannou::prelude::*;
+use rand::SeedableRng;
+use rand::rngs::StdRng;
 // use std::thread::sleep;
 // use std::time;
 
-pub mod nodes_layers;
-pub mod inout;
+use numbers_nn::{adversarial, augment, autoencoder, dataset, image_import, inout, labels, model_binary, model_file, nodes_layers, preprocess, presets, saliency, scaling, vae};
+use numbers_nn::dataset::Dataset;
+use numbers_nn::error::NnError;
 
 fn main() {
 
+    let args: Vec<String> = std::env::args().collect();
+    if args.len() > 1 && args[1] == "classify" {
+        classify(&args);
+        return
+    }
     nannou::app(model)
         .update(update)
         .run()
 }
 
+enum Task {
+    //What the network in the app is being used for.
+    Classifier(nodes_layers::Network),
+    Autoencoder(autoencoder::Autoencoder),
+    Vae(vae::Vae),
+}
+
+const LATENT_SCALE: f32 = 100.0; //The number of points on screen per unit of the VAE's latent space.
+const IMAGE_SPAN: f32 = 56.0; //The number of points on screen taken up by the longest side of an image, which gives 2.0 per pixel for MNIST.
+
 struct Model {
     _window: WindowId,
-    network: nodes_layers::Network,
+    task: Task,
     is_training: bool,
-    images: Vec<Vec<f32>>,
-    labels: Vec<Vec<f32>>,
+    is_exploring: bool, //Whether the mouse is being used to explore the latent space of a VAE, instead of going through the images.
+    saliency_method: Option<saliency::SaliencyMethod>, //If there is one, the classifier's saliency map for its guess is drawn over the image.
+    adversarial: Option<adversarial::AdversarialTraining>, //If there is one, the classifier is also shown an adversarial version of each image, and trained on a mix of clean and adversarial images.
+    augmentation: Option<(augment::Pipeline, StdRng)>, //If there is one, the classifier is trained on augmented images.
+    latent_points: Vec<(Vec<f32>, usize)>, //The encoded means of some of the images and their classes, drawn as a map of the latent space when exploring.
+    data: Box<dyn dataset::Dataset>, //The images and the desired outputs for them.
+    folder: String, //Where the model is saved.
+    metadata: model_file::ModelMetadata, //Saved with the classifier, and updated after each run through the images.
     pos_counter: usize,
     marg_success: usize,
     biggest_success: usize,
+    robust_success: usize,
+    reconstruction_error: f32,
+}
+
+fn model_paths(folder: &str, name: &str, count: usize) -> Vec<String> {
+    //The files a model is saved in, one per layer, like datas\network1.csv to datas\network4.csv.
+    (1..=count).map(|num| format!(r"{}\{}{}.csv", folder, name, num)).collect()
+}
+
+fn network_paths(folder: &str) -> Vec<String> {
+    model_paths(folder, "network", 4)
+}
+
+fn autoencoder_paths(folder: &str) -> Vec<String> {
+    model_paths(folder, "autoencoder", 4)
+}
+
+fn vae_paths(folder: &str) -> Vec<String> {
+    model_paths(folder, "vae", 5)
+}
+
+fn model_file_path(folder: &str) -> String {
+    //The single file a classifier is saved in, holding its layers along with everything needed to use it.
+    format!(r"{}\model.nnm", folder)
+}
+
+fn read_network_reporting(file_paths: Vec<&str>, network_form: &[usize], learning_rate: f32, make_replacements: bool) -> Result<nodes_layers::Network, NnError> {
+    //Reads a network with inout::read_network, telling the user about any layer that had to be replaced by a randomized one.
+    let (network, replaced_layers) = inout::read_network(file_paths, network_form, learning_rate, make_replacements)?;
+    for replaced_layer in replaced_layers {
+        match (&replaced_layer.file_path, &replaced_layer.error) {
+            (Some(file_path), Some(error)) => println!("Issue with loading the layer at {:?}: {}", file_path, error),
+            _ => println!("There was no file path for layer {:?}", replaced_layer.layer_num),
+        }
+        println!("Generating a randomized layer in its place. If this is testing, the network will obviously perform very poorly.");
+    }
+    Ok(network)
+}
+
+fn saved_model_path(folder: &str) -> Option<String> {
+    //The model file to load a classifier from, if there is one. Classifiers saved before there were binary model files are in model.csv.
+    let csv_path = format!(r"{}\model.csv", folder);
+    if std::path::Path::new(&model_file_path(folder)).exists() {
+        Some(model_file_path(folder))
+    } else if std::path::Path::new(&csv_path).exists() {
+        Some(csv_path)
+    } else {
+        None
+    }
+}
+
+fn label_map_path(folder: &str) -> String {
+    format!(r"{}\labels.csv", folder)
+}
+
+fn as_strs(paths: &[String]) -> Vec<&str> {
+    paths.iter().map(|path| path.as_str()).collect()
+}
+
+fn dataset_choice(args: &[String]) -> (Option<&str>, Option<presets::Preset>, String) {
+    //"dataset=<name>" picks another dataset than MNIST, like fashion-mnist, kmnist or emnist-letters.
+    //Any other name is read as IDX files named like MNIST's, with the image size taken from the files and the classes from the labels.
+    //MNIST's files and models are kept in the datas folder, and the others' in a folder of their own within it.
+    let dataset_name = args.iter().find(|arg| arg.starts_with("dataset=")).map(|arg| &arg["dataset=".len()..]);
+    let preset = dataset_name.and_then(presets::Preset::from_name);
+    let folder = match dataset_name {
+            Some(name) if preset != Some(presets::Preset::Mnist) => format!(r"datas\{}", name),
+            _ => String::from("datas"),
+        };
+    (dataset_name, preset, folder)
+}
+
+fn classifier_form(shape: dataset::Shape, class_count: usize) -> Vec<usize> {
+    //The networks' sizes follow the images, with a first hidden layer a quarter the size of the input, like 784 to 196 for MNIST.
+    vec![shape.size(), hidden_size(shape), 64, 16, class_count]
+}
+
+fn hidden_size(shape: dataset::Shape) -> usize {
+    (shape.size() / 4).max(1)
+}
+
+fn classify(args: &[String]) {
+    //"classify <files>" prints the trained classifier's guess for each image file, resized to the dataset's images.
+    //With "preprocess", the digits are first cropped, straightened and centred the way MNIST's were, which suits scans and drawings.
+    //"model=<path>" classifies with a model file from anywhere, which needs no dataset since everything is in the file.
+    let (dataset_name, preset, folder) = dataset_choice(args);
+    let model_path = match args.iter().find(|arg| arg.starts_with("model=")) {
+        Some(arg) => Some(arg["model=".len()..].to_string()),
+        None => saved_model_path(&folder),
+    };
+    let dataset_shape = || match preset.unwrap_or(presets::Preset::Mnist).shape(std::path::Path::new(&folder), false) {
+        Ok(shape) => shape,
+        Err(error) => panic!("Issue with finding the image size in {:?}: {}", folder, error),
+    };
+    let (network, metadata) = if let Some(model_path) = model_path {
+            model_binary::read_any(&model_path).expect("Something went wrong while reading the model file")
+        } else {
+            //Classifiers saved before there were model files are read from their layer files and label map.
+            let shape = dataset_shape();
+            let label_map = inout::read_label_map(&label_map_path(&folder)).unwrap_or_else(|error| {
+                println!("Issue with loading the label map at {:?}: {:?}", label_map_path(&folder), error);
+                match (dataset_name, preset) {
+                    (Some(_), None) => panic!("A custom dataset needs its label map to classify"),
+                    _ => preset.unwrap_or(presets::Preset::Mnist).label_map(),
+                }
+            });
+            let network = inout::read_network(
+                as_strs(&network_paths(&folder)),
+                &classifier_form(shape, label_map.class_count()),
+                0.5,
+                false,
+                ).and_then(|(network, _)| network.with_label_map(label_map))
+                .expect("Something went wrong while reading the network");
+            (network, model_file::ModelMetadata::new().with_shape(shape))
+        };
+    let shape = metadata.shape.unwrap_or_else(dataset_shape);
+    let preprocessing = if args.iter().any(|arg| arg == "preprocess") {Some(preprocess::Preprocessing::mnist())} else {metadata.preprocessing.clone()};
+    for path in args[2..].iter().filter(|arg| !arg.starts_with("dataset=") && !arg.starts_with("model=") && *arg != "preprocess") {
+        let image = match image_import::read(path) {
+            Ok(image) => image,
+            Err(error) => {
+                println!("Issue with loading the image at {:?}: {}", path, error);
+                continue
+            },
+        };
+        let input = match &preprocessing {
+            Some(preprocessing) => Ok(preprocessing.apply(&image, shape)),
+            None => image.fit_to(shape),
+        };
+        let outputs = match input.and_then(|input| network.predict(&input)) {
+            Ok(outputs) => outputs,
+            Err(error) => {
+                println!("Issue with classifying the image at {:?}: {}", path, error);
+                continue
+            },
+        };
+        let (biggest, positions) = nodes_layers::find_biggest(&outputs);
+        println!("{}: {} ({:.3})", path, network.label_map.name(positions[0]), biggest);
+    }
 }
 
 fn model(app: &App) -> Model {
@@ -33,82 +196,329 @@ fn model(app: &App) -> Model {
     .build()
     .unwrap();
     let args: Vec<String> = std::env::args().collect();
-    let is_training = match args[1].as_str() {
-        "training" => true,
-        "testing" => false,
+    //Either "training"/"testing" for the digit classifier, or the same preceded by "autoencoder" or "vae".
+    //A VAE can also be used with "vae exploring".
+    let is_autoencoder = args[1] == "autoencoder";
+    let is_vae = args[1] == "vae";
+    let mode_arg = if is_autoencoder || is_vae {&args[2]} else {&args[1]};
+    let (is_training, is_exploring) = match mode_arg.as_str() {
+        "training" => (true, false),
+        "testing" => (false, false),
+        "exploring" if is_vae => (false, true),
         _ => panic!("Invalid parameters"),
     };
-    let network_form = &[28*28, 28*7, 64, 16, 10];
-    let network = if is_training && args[args.len()-1] == "new" {
-            nodes_layers::Network::new(network_form, 0.5)
-        } else {
-            inout::read_network(vec![
-                r"datas\network1.csv",
-                r"datas\network2.csv",
-                r"datas\network3.csv",
-                r"datas\network4.csv",
-            ],
-            network_form,
-            0.5,
-            is_training,
-            ).expect("Something went wrong while reading the network")
+    let is_new = is_training && args[args.len()-1] == "new";
+    //"saliency" draws integrated gradients over the digit, and "saliency=<method>" picks another method.
+    let saliency_method = args.iter().find(|arg| arg.starts_with("saliency")).map(|arg| match arg.as_str() {
+        "saliency" | "saliency=integrated" => saliency::SaliencyMethod::IntegratedGradients {steps: 32},
+        "saliency=vanilla" => saliency::SaliencyMethod::Vanilla,
+        "saliency=gradient-input" => saliency::SaliencyMethod::GradientTimesInput,
+        "saliency=smoothgrad" => saliency::SaliencyMethod::SmoothGrad {samples: 25, noise: 0.15},
+        _ => panic!("Invalid saliency method"),
+    });
+    //"adversarial" attacks the classifier with PGD under the L∞ norm, and "adversarial=<attack>" picks fgsm, pgd, fgsm-l2 or pgd-l2.
+    //The size of the attack is set with "epsilon=<number>", and when training, "mix=<number>" sets the share of the steps that train on adversarial images.
+    let adversarial = args.iter().find(|arg| arg.starts_with("adversarial")).map(|arg| {
+        let (attack_name, norm) = match arg.as_str() {
+            "adversarial" | "adversarial=pgd" => ("pgd", adversarial::Norm::LInf),
+            "adversarial=fgsm" => ("fgsm", adversarial::Norm::LInf),
+            "adversarial=pgd-l2" => ("pgd", adversarial::Norm::L2),
+            "adversarial=fgsm-l2" => ("fgsm", adversarial::Norm::L2),
+            _ => panic!("Invalid adversarial attack"),
+        };
+        let epsilon = match args.iter().find(|arg| arg.starts_with("epsilon=")) {
+            Some(arg) => arg["epsilon=".len()..].parse().expect("Invalid epsilon"),
+            None => if norm == adversarial::Norm::LInf {0.1} else {1.5},
+        };
+        let attack = if attack_name == "fgsm" {
+                adversarial::Attack::Fgsm
+            } else {
+                adversarial::Attack::Pgd {steps: 10, step_size: epsilon / 4.0, random_start: true}
+            };
+        let adversarial_fraction = match args.iter().find(|arg| arg.starts_with("mix=")) {
+            Some(arg) => arg["mix=".len()..].parse().expect("Invalid mix"),
+            None => 0.5,
+        };
+        let training = adversarial::AdversarialTraining {
+            attack: attack,
+            norm: norm,
+            epsilon: epsilon,
+            adversarial_fraction: adversarial_fraction,
+            bounds: Some((0.0, 1.0)), //The attacks change the images before input_scaler, and those are within [0; 1].
         };
-    let images = if is_training {
-            inout::unpack_images(r".\datas\train-images.idx3-ubyte")
+        training.check().expect("Invalid adversarial settings");
+        training
+    });
+    let (dataset_name, preset, folder) = dataset_choice(&args);
+    let (images, raw_labels, shape) = match preset.unwrap_or(presets::Preset::Mnist).read(std::path::Path::new(&folder), is_training) {
+        Ok(data) => data,
+        Err(error) => panic!("Issue with loading the data in {:?}: {}", folder, error),
+    };
+    let dataset_label_map = match (dataset_name, preset) {
+        (Some(_), None) => labels::LabelMap::from_codes(&raw_labels),
+        _ => preset.unwrap_or(presets::Preset::Mnist).label_map(),
+    };
+    //A classifier saved as a model file is loaded from it, classes and all. Older ones are loaded from their layer files further down.
+    let saved_model = match saved_model_path(&folder) {
+        Some(model_path) if !is_new && !is_autoencoder && !is_vae => Some(model_binary::read_any(&model_path).expect("Something went wrong while reading the model file")),
+        _ => None,
+    };
+    //A new classifier takes its classes from the dataset. Otherwise they're read along with the network.
+    let label_map = if is_new || is_autoencoder || is_vae {
+            dataset_label_map
+        } else if let Some((network, _)) = &saved_model {
+            network.label_map.clone()
         } else {
-            inout::unpack_images(r".\datas\t10k-images.idx3-ubyte")
+            inout::read_label_map(&label_map_path(&folder)).unwrap_or_else(|error| {
+                println!("Issue with loading the label map at {:?}: {:?}", label_map_path(&folder), error);
+                println!("Using the labels of the dataset instead.");
+                dataset_label_map
+            })
         };
-    let labels = if is_training {
-            inout::unpack_labels(r".\datas\train-labels.idx1-ubyte")
+    let metadata = match &saved_model {
+        Some((_, metadata)) => metadata.clone(),
+        None => model_file::ModelMetadata::new().with_dataset(dataset_name.unwrap_or("mnist")).with_shape(shape),
+    };
+    let targets = label_map.to_results(&raw_labels).expect("The labels don't fit the label map");
+    let data = dataset::InMemoryDataset::new(images, targets, shape, label_map.clone()).expect("The images don't fit the dataset");
+    let hidden_size = hidden_size(shape);
+    let task = if is_vae {
+        let encoder_form = &[shape.size(), hidden_size];
+        let vae = if is_new {
+                vae::Vae::new(encoder_form, 2, 0.01).expect("Something went wrong while making the VAE")
+            } else {
+                vae::Vae::read(as_strs(&vae_paths(&folder)), encoder_form, 2, 0.01).expect("Something went wrong while reading the VAE")
+            };
+        Task::Vae(vae)
+    } else if is_autoencoder {
+        let corruption = if args.iter().any(|arg| arg == "denoising") {
+                autoencoder::Corruption::Masking(0.25)
+            } else {
+                autoencoder::Corruption::Nothing
+            };
+        let encoder_form = &[shape.size(), hidden_size, 32];
+        let autoencoder = if is_new {
+                autoencoder::Autoencoder::new(encoder_form, 0.5, corruption).expect("Something went wrong while making the autoencoder")
+            } else {
+                let network = read_network_reporting(
+                    as_strs(&autoencoder_paths(&folder)),
+                    &[shape.size(), hidden_size, 32, hidden_size, shape.size()],
+                    0.5,
+                    is_training,
+                ).expect("Something went wrong while reading the autoencoder");
+                autoencoder::Autoencoder::from_network(network, 1, corruption).expect("Something went wrong while making the autoencoder")
+            };
+        Task::Autoencoder(autoencoder)
+    } else {
+        let network_form = &classifier_form(shape, label_map.class_count());
+        //"standardise=<kind>" makes a new classifier standardise its inputs, with per-pixel, global or pca statistics from the training set.
+        //They're saved with the network, so testing and classifying use the same ones.
+        //"standardise=pca:<number>" keeps that many principal components, and plain "standardise=pca" keeps 50.
+        let standardisation = args.iter().find(|arg| arg.starts_with("standardise=")).map(|arg| match arg.as_str() {
+            "standardise=per-pixel" => scaling::Standardisation::PerPixel,
+            "standardise=global" => scaling::Standardisation::Global,
+            "standardise=pca" => scaling::Standardisation::PcaWhitening {components: 50, epsilon: 0.1},
+            arg if arg.starts_with("standardise=pca:") => {
+                let components = arg["standardise=pca:".len()..].parse().expect("Invalid number of components");
+                if components == 0 {
+                    panic!("PCA needs to keep at least one component");
+                }
+                scaling::Standardisation::PcaWhitening {components: components, epsilon: 0.1}
+            },
+            _ => panic!("Invalid standardisation"),
+        });
+        let network = if is_new {
+                let input_scaler = standardisation.map(|standardisation| {
+                    //A sample of the training set is plenty for the statistics, and keeps PCA from taking too long.
+                    let sample: Vec<Vec<f32>> = (0..data.len().min(10000)).map(|num| data.get(num).0).collect();
+                    standardisation.fit(&sample).expect("Something went wrong while fitting the standardisation")
+                });
+                //PCA leaves fewer values than there are pixels, so the first layer takes however many the scaler gives.
+                let mut form = network_form.clone();
+                if let Some(input_scaler) = &input_scaler {
+                    form[0] = input_scaler.output_count();
+                }
+                let mut network = nodes_layers::Network::new(&form, 0.5);
+                network.input_scaler = input_scaler;
+                network
+            } else if let Some((network, _)) = saved_model {
+                network
+            } else {
+                read_network_reporting(
+                as_strs(&network_paths(&folder)),
+                network_form,
+                0.5,
+                is_training,
+                ).expect("Something went wrong while reading the network")
+            };
+        Task::Classifier(network.with_label_map(label_map).expect("The label map doesn't fit the network"))
+    };
+    let mut latent_points = Vec::new();
+    if is_exploring {
+        if let Task::Vae(vae) = &task {
+            for num in 0..data.len().min(2000) {
+                let (image, label) = data.get(num);
+                latent_points.push((vae.encode(&image).expect("The VAE couldn't take the image").0, nodes_layers::find_biggest(&label).1[0]));
+            }
+        }
+    }
+    //"augment" trains the classifier on randomly moved and distorted images, and "mixup" blends pairs of them together as well.
+    //The augmentations are repeatable with "seed=<number>".
+    let augmentation = if args.iter().any(|arg| arg == "augment" || arg == "mixup") {
+            let seed = match args.iter().find(|arg| arg.starts_with("seed=")) {
+                Some(arg) => arg["seed=".len()..].parse().expect("Invalid seed"),
+                None => 0,
+            };
+            let mut pipeline = augment::Pipeline::new(shape);
+            if args.iter().any(|arg| arg == "augment") {
+                pipeline = pipeline
+                    .then(augment::Augmentation::Translate {max_shift: 2.0})
+                    .and_then(|pipeline| pipeline.then(augment::Augmentation::Rotate {max_degrees: 10.0}))
+                    .and_then(|pipeline| pipeline.then(augment::Augmentation::Scale {min_factor: 0.9, max_factor: 1.1}))
+                    .and_then(|pipeline| pipeline.then(augment::Augmentation::Shear {max_shear: 0.15}))
+                    .and_then(|pipeline| pipeline.then(augment::Augmentation::Elastic {alpha: 34.0, sigma: 4.0}))
+                    .and_then(|pipeline| pipeline.then(augment::Augmentation::GaussianNoise {standard_deviation: 0.05}))
+                    .and_then(|pipeline| pipeline.then(augment::Augmentation::RandomErasing {probability: 0.25, min_area: 0.02, max_area: 0.15}))
+                    .expect("Invalid augmentation");
+            }
+            if args.iter().any(|arg| arg == "mixup") {
+                pipeline = pipeline.with_mixup(0.2).expect("Invalid mixup");
+            }
+            Some((pipeline, StdRng::seed_from_u64(seed)))
         } else {
-            inout::unpack_labels(r".\datas\t10k-labels.idx1-ubyte")
+            None
         };
-    let labels = inout::turn_to_result(labels);
     let pos_counter = 0;
     let marg_success = 0;
     let biggest_success = 0;
+    let robust_success = 0;
+    let reconstruction_error = 0.0;
 
     Model {
         _window,
-        network,
+        task,
         is_training,
-        images,
-        labels,
+        is_exploring,
+        saliency_method,
+        adversarial,
+        augmentation,
+        latent_points,
+        data: Box::new(data),
+        folder,
+        metadata,
         pos_counter,
         marg_success,
         biggest_success,
+        robust_success,
+        reconstruction_error,
     }
 }
 
 
 fn update(_app: &App, model: &mut Model, _update: Update) {
-    if model.network.compare_success(&model.images[model.pos_counter], &model.labels[model.pos_counter], 0.5) {
-        model.marg_success += 1;
+    if model.is_exploring {
+        //Everything happens in view, following the mouse.
+        return
     }
-    if nodes_layers::find_biggest(&model.labels[model.pos_counter]).1 == nodes_layers::find_biggest(&model.network.calculate(&model.images[model.pos_counter])[model.network.layer_count-1]).1 {
-        model.biggest_success += 1;
-    }
-    if model.is_training {
-        model.network.find_make_adjust(&model.images[model.pos_counter], &model.labels[model.pos_counter]);
+    let (clean_image, clean_label) = model.data.get(model.pos_counter);
+    match &mut model.task {
+        Task::Classifier(network) => {
+            if network.compare_success(&clean_image, &clean_label, 0.5).expect("The network couldn't take the image") {
+                model.marg_success += 1;
+            }
+            if nodes_layers::find_biggest(&clean_label).1 == nodes_layers::find_biggest(&network.predict(&clean_image).expect("The network couldn't take the image")).1 {
+                model.biggest_success += 1;
+            }
+            let (image, label) = match &mut model.augmentation {
+                Some((pipeline, rng)) if model.is_training => {
+                    let (other_image, other_label) = model.data.get(augment::partner_index(model.pos_counter, model.data.len(), rng));
+                    pipeline.apply_pair(&clean_image, &clean_label, &other_image, &other_label, rng).expect("The image couldn't be augmented")
+                },
+                _ => (clean_image.clone(), clean_label.clone()),
+            };
+            match (&model.adversarial, model.is_training) {
+                (Some(training), true) => {
+                    if adversarial::train_step(network, &image, &label, training).expect("The network couldn't take the image").robust_correct {
+                        model.robust_success += 1;
+                    }
+                },
+                (Some(training), false) => {
+                    let adversarial_image = adversarial::attack(network, &clean_image, &clean_label, training.attack, training.norm, training.epsilon, training.bounds).expect("The network couldn't take the image");
+                    if nodes_layers::same_biggest(&network.predict(&adversarial_image).expect("The network couldn't take the image"), &clean_label) {
+                        model.robust_success += 1;
+                    }
+                },
+                (None, true) => network.find_make_adjust(&image, &label).expect("The network couldn't take the image"),
+                (None, false) => {},
+            }
+        },
+        Task::Autoencoder(autoencoder) => {
+            model.reconstruction_error += autoencoder.reconstruction_error(&clean_image).expect("The autoencoder couldn't take the image");
+            if model.is_training {
+                autoencoder.train(&clean_image).expect("The autoencoder couldn't take the image");
+            }
+        },
+        Task::Vae(vae) => {
+            let loss = if model.is_training {
+                    vae.train(&clean_image)
+                } else {
+                    vae.loss(&clean_image)
+                };
+            model.reconstruction_error += loss.expect("The VAE couldn't take the image").total(vae.beta);
+        },
     }
     model.pos_counter += 1;
     if model.pos_counter % 1000 == 0 {
     // if model.pos_counter % 10000 == 0 {
-        println!("{:?} marg_success: {:?} biggest_success: {:?}", model.pos_counter, model.marg_success, model.biggest_success);
-    }
-    if model.pos_counter >= model.images.len() {
-        println!("Number of successes according to the margin calculation: {:?}", model.marg_success);
-        println!("Number of successes according to the find_biggest calculation: {:?}", model.biggest_success);
-        println!("Number of attempts: {:?}", model.images.len());
-        if model.is_training {
-            let file_paths = vec![
-                r"datas\network1.csv",
-                r"datas\network2.csv",
-                r"datas\network3.csv",
-                r"datas\network4.csv",
-            ];
-            inout::write_network(model.network.clone(), file_paths).expect("Something went wrong with writing the data");
-            println!("Data successfully written to files!");
+        match &model.task {
+            Task::Classifier(_) => {
+                if model.adversarial.is_some() {
+                    println!("{:?} marg_success: {:?} biggest_success: {:?} robust_success: {:?}", model.pos_counter, model.marg_success, model.biggest_success, model.robust_success)
+                } else {
+                    println!("{:?} marg_success: {:?} biggest_success: {:?}", model.pos_counter, model.marg_success, model.biggest_success)
+                }
+            },
+            Task::Autoencoder(_) => println!("{:?} mean reconstruction_error: {:?}", model.pos_counter, model.reconstruction_error / model.pos_counter as f32),
+            Task::Vae(_) => println!("{:?} mean loss: {:?}", model.pos_counter, model.reconstruction_error / model.pos_counter as f32),
+        }
+    }
+    if model.pos_counter >= model.data.len() {
+        match &model.task {
+            Task::Classifier(network) => {
+                println!("Number of successes according to the margin calculation: {:?}", model.marg_success);
+                println!("Number of successes according to the find_biggest calculation: {:?}", model.biggest_success);
+                println!("Number of attempts: {:?}", model.data.len());
+                if let Some(training) = model.adversarial {
+                    println!("Number of successes on adversarial images: {:?}", model.robust_success);
+                    if !model.is_training {
+                        let epsilons: Vec<f32> = (0..=8).map(|num| training.epsilon * num as f32 / 4.0).collect();
+                        let example_count = model.data.len().min(1000);
+                        let examples = dataset::Subset::new(&*model.data, (0..example_count).collect()).expect("The examples should be in the dataset");
+                        println!("{}", adversarial::robustness_report(network, &examples, training.attack, training.norm, &epsilons, training.bounds).expect("The network couldn't take the image"));
+                    }
+                }
+                if model.is_training {
+                    let metadata = model.metadata.clone()
+                        .with_examples_seen(model.metadata.examples_seen + model.data.len())
+                        .with_accuracy(model.biggest_success as f32 / model.data.len().max(1) as f32);
+                    model_binary::write(network, &metadata, &model_file_path(&model.folder)).expect("Something went wrong with writing the data");
+                    println!("Data successfully written to {:?}!", model_file_path(&model.folder));
+                }
+            },
+            Task::Autoencoder(autoencoder) => {
+                println!("Mean reconstruction error: {:?}", model.reconstruction_error / model.data.len() as f32);
+                if model.is_training {
+                    inout::write_network(autoencoder.network.clone(), as_strs(&autoencoder_paths(&model.folder))).expect("Something went wrong with writing the data");
+                    println!("Data successfully written to files!");
+                }
+            },
+            Task::Vae(vae) => {
+                println!("Mean loss: {:?}", model.reconstruction_error / model.data.len() as f32);
+                if model.is_training {
+                    vae.write(as_strs(&vae_paths(&model.folder))).expect("Something went wrong with writing the data");
+                    println!("Data successfully written to files!");
+                }
+            },
         }
     }
     // if 5 > 4 {
@@ -123,27 +533,117 @@ fn view(app: &App, model: &Model, frame: &Frame) {
 
     let draw = app.draw();
 
-    draw_results(model, &draw);
+    if model.is_exploring {
+        draw_latent_space(app, model, &draw);
+    } else {
+        draw_results(model, &draw);
+    }
 
     draw.to_frame(app, frame).unwrap();
 }
 
-fn draw_results(model: &Model, draw: &nannou::app::Draw) {
+fn pixel_colour(image: &[f32], shape: dataset::Shape, x: usize, y: usize) -> (f32, f32, f32) {
+    //Images with three channels are drawn in colour, and the rest in grey using their first channel.
+    let plane = shape.width * shape.height;
+    let pos = y*shape.width + x;
+    if shape.channels == 3 {
+        (image[pos], image[plane + pos], image[2*plane + pos])
+    } else {
+        (image[pos], image[pos], image[pos])
+    }
+}
+
+fn draw_image(image: &[f32], shape: dataset::Shape, left: f32, top: f32, pixel_size: f32, draw: &nannou::app::Draw) {
+    //Draws an image with its top left corner at (left, top), using a square of pixel_size for each pixel.
+    for y in 0..shape.height {
+        for x in 0..shape.width {
+            let (red, green, blue) = pixel_colour(image, shape, x, y);
+            draw.rect().x_y(pixel_size * x as f32 + left, -pixel_size * y as f32 + top).w_h(pixel_size, pixel_size)
+                            .color(rgb(red, green, blue));
+        }
+    }
+}
 
-    for y in 0..28 {
-        for x in 0..28 {
-            let sq_col = model.images[model.pos_counter][y*28 + x];
-            draw.rect().x_y(2.0 * x as f32 - 200.0, -2.0 * y as f32 + 50.0).w_h(2.0, 2.0)
-                            .color(rgb(sq_col, sq_col, sq_col));
+fn draw_saliency(image: &[f32], shape: dataset::Shape, saliency: &[f32], left: f32, top: f32, pixel_size: f32, draw: &nannou::app::Draw) {
+    //Draws an image like draw_image, tinted red where the saliency is high. saliency should be in [0; 1], like from saliency::normalise.
+    //A pixel of an image with several channels is tinted by its most salient channel.
+    let plane = shape.width * shape.height;
+    for y in 0..shape.height {
+        for x in 0..shape.width {
+            let (red, green, blue) = pixel_colour(image, shape, x, y);
+            let tint = (0..shape.channels).map(|channel| saliency[channel*plane + y*shape.width + x]).fold(0.0, f32::max);
+            draw.rect().x_y(pixel_size * x as f32 + left, -pixel_size * y as f32 + top).w_h(pixel_size, pixel_size)
+                            .color(rgb(red + tint * (1.0 - red), green * (1.0 - tint), blue * (1.0 - tint)));
         }
     }
-    let values = model.network.calculate(&model.images[model.pos_counter]);
-    for y in 0..10 {
-        let cir_col = values[values.len()-1][y];
-        draw.ellipse().x_y(200.0, 12.0 * y as f32).radius(6.0)
-                           .color(rgb(cir_col, cir_col, cir_col));
-        let cir_col = model.labels[model.pos_counter][y];
-        draw.ellipse().x_y(212.0, 12.0 * y as f32).radius(6.0)
-                          .color(rgb(cir_col, cir_col, cir_col));
+}
+
+fn draw_results(model: &Model, draw: &nannou::app::Draw) {
+
+    let (image, label) = model.data.get(model.pos_counter);
+    let image = &image;
+    let shape = model.data.shape();
+    let pixel_size = IMAGE_SPAN / shape.width.max(shape.height) as f32;
+    draw_image(image, shape, -200.0, 50.0, pixel_size, draw);
+    match &model.task {
+        Task::Classifier(network) => {
+            //A circle per output, shrinking when there are too many classes to fit.
+            let values = network.calculate(image).expect("The network couldn't take the image");
+            let outputs = &values[values.len()-1];
+            if let Some(method) = model.saliency_method {
+                let guess = nodes_layers::find_biggest(outputs).1[0];
+                let saliency = saliency::normalise(&saliency::saliency_map(network, image, guess, method).expect("The network couldn't take the image"));
+                draw_saliency(image, shape, &saliency, -200.0, 50.0, pixel_size, draw);
+            }
+            let spacing = (600.0 / outputs.len() as f32).min(12.0);
+            let bottom = -spacing * outputs.len() as f32 / 2.0;
+            for y in 0..outputs.len() {
+                let cir_col = outputs[y];
+                draw.ellipse().x_y(200.0, spacing * y as f32 + bottom).radius(spacing / 2.0)
+                                   .color(rgb(cir_col, cir_col, cir_col));
+                let cir_col = label[y];
+                draw.ellipse().x_y(200.0 + spacing, spacing * y as f32 + bottom).radius(spacing / 2.0)
+                                  .color(rgb(cir_col, cir_col, cir_col));
+            }
+            if let Some(training) = model.adversarial {
+                //The adversarial image goes to the right of the original, and the network's outputs for it go in a column to the right of the others.
+                let adversarial_image = adversarial::attack(network, image, &label, training.attack, training.norm, training.epsilon, training.bounds).expect("The network couldn't take the image");
+                draw_image(&adversarial_image, shape, -130.0, 50.0, pixel_size, draw);
+                let adversarial_outputs = network.predict(&adversarial_image).expect("The network couldn't take the image");
+                for y in 0..adversarial_outputs.len() {
+                    let cir_col = adversarial_outputs[y];
+                    draw.ellipse().x_y(200.0 + 3.0 * spacing, spacing * y as f32 + bottom).radius(spacing / 2.0)
+                                      .color(rgb(cir_col, cir_col, cir_col));
+                }
+            }
+        },
+        Task::Autoencoder(autoencoder) => {
+            //The reconstruction is drawn to the right of the original.
+            draw_image(&autoencoder.reconstruct(image).expect("The autoencoder couldn't take the image"), shape, -130.0, 50.0, pixel_size, draw);
+        },
+        Task::Vae(vae) => {
+            draw_image(&vae.reconstruct(image).expect("The VAE couldn't take the image"), shape, -130.0, 50.0, pixel_size, draw);
+        },
+    }
+}
+
+fn draw_latent_space(app: &App, model: &Model, draw: &nannou::app::Draw) {
+    //Draws the encoded images as dots colored by class, and the digit decoded from the point under the mouse in the top left corner.
+    if let Task::Vae(vae) = &model.task {
+        let class_count = model.data.label_map().class_count();
+        for (latent, class) in &model.latent_points {
+            let shade = *class as f32 / (class_count - 1).max(1) as f32;
+            draw.ellipse().x_y(latent[0] * LATENT_SCALE, latent[1] * LATENT_SCALE).radius(2.0)
+                              .color(rgb(shade, 1.0 - shade, 0.5));
+        }
+        let mut latent = vec![0.0; vae.latent_size];
+        latent[0] = app.mouse.x / LATENT_SCALE;
+        if vae.latent_size > 1 {
+            latent[1] = app.mouse.y / LATENT_SCALE;
+        }
+        draw.ellipse().x_y(app.mouse.x, app.mouse.y).radius(4.0)
+                          .color(rgb(1.0, 1.0, 1.0));
+        let shape = model.data.shape();
+        draw_image(&vae.decode(&latent).expect("The VAE couldn't decode the point"), shape, -340.0, 340.0, 2.0 * IMAGE_SPAN / shape.width.max(shape.height) as f32, draw);
     }
 }
@@ -0,0 +1,639 @@
+extern crate crc32fast;
+extern crate csv;
+#[cfg(feature = "mmap")]
+extern crate memmap2;
+
+use std::fs;
+use std::ops::Range;
+
+use crate::error::NnError;
+use crate::inout;
+use crate::model_file::{self, ModelHeader, ModelMetadata};
+use crate::nodes_layers;
+use crate::scaling;
+
+pub const MAGIC: [u8; 8] = *b"NNMODEL\0";
+pub const FORMAT_VERSION: u32 = 1; //Raised whenever a file written by this version can't be read by an older one.
+
+const HEAD_TAG: [u8; 4] = *b"HEAD";
+const SCALER_TAG: [u8; 4] = *b"SCAL";
+const LAYER_TAG: [u8; 4] = *b"LAYR";
+const MASK_TAG: [u8; 4] = *b"MASK";
+
+//The magic, the version, the number of sections and their checksum.
+const FILE_HEADER_LEN: usize = 20;
+//The tag, the length of the payload and the checksum of the section.
+const SECTION_HEADER_LEN: usize = 16;
+
+//The layout of the file, with every number little-endian:
+//  magic, version (u32), section count (u32), CRC32 of the magic, version and section count (u32)
+//  then each section: tag (4 bytes), payload length (u64), CRC32 of the tag, length and payload (u32), payload, zeros up to a multiple of 4 bytes
+//The file ends right after the padding of the last section.
+//The sections come in this order:
+//  HEAD  the header of a model_file, as .csv lines, without the format line and the parameters line
+//  SCAL  one per scaler in the header, input scaler first, with the lines of the scaler one after another as f32
+//  LAYR  one per layer, with the biases of the nodes as f32 and then their weights, one node after another
+//  MASK  after a layer with pruned weights, a bit per weight that is 1 if it's used, with each node starting on a new byte
+//Sections with unknown tags are skipped, so sections can be added without older versions of read failing on them.
+//Since every payload starts on a multiple of 4 bytes, the values can be used straight from a memory-mapped file, see MappedModel.
+
+pub fn to_bytes(network: &nodes_layers::Network, metadata: &ModelMetadata) -> Result<Vec<u8>, NnError> {
+    //The network and its metadata as the contents of a binary model file.
+    let header = ModelHeader::new(network, metadata);
+    let mut head = Vec::new();
+    {
+        let mut wtr = csv::WriterBuilder::new()
+            .flexible(true)
+            .from_writer(&mut head);
+        header.write_records(&mut wtr)?;
+        wtr.flush()?;
+    }
+    let mut sections = vec![(HEAD_TAG, head)];
+    for scaler in &[&network.input_scaler, &network.target_scaler] {
+        if let Some(scaler) = scaler {
+            let mut values = Vec::new();
+            values.extend(&scaler.offsets);
+            values.extend(&scaler.scales);
+            if let Some(whitening) = &scaler.whitening {
+                values.extend(&whitening.scales);
+                for component in &whitening.components {
+                    values.extend(component);
+                }
+            }
+            sections.push((SCALER_TAG, float_bytes(&values)));
+        }
+    }
+    for layer in &network.layers {
+        let mut values = Vec::new();
+        for node in &layer.nodes {
+            values.push(node.bias);
+        }
+        for node in &layer.nodes {
+            values.extend(&node.weights);
+        }
+        sections.push((LAYER_TAG, float_bytes(&values)));
+        if layer.nodes.iter().any(|node| node.active_weights.is_some()) {
+            let mut mask = Vec::new();
+            for node in &layer.nodes {
+                let mut node_mask = vec![0u8; (node.weights.len() + 7) / 8];
+                match &node.active_weights {
+                    Some(active_weights) => {
+                        for &pos in active_weights {
+                            node_mask[pos / 8] |= 1 << (pos % 8);
+                        }
+                    },
+                    None => {
+                        for pos in 0..node.weights.len() {
+                            node_mask[pos / 8] |= 1 << (pos % 8);
+                        }
+                    },
+                }
+                mask.extend(node_mask);
+            }
+            sections.push((MASK_TAG, mask));
+        }
+    }
+
+    let mut bytes = Vec::new();
+    bytes.extend(&MAGIC);
+    bytes.extend(&FORMAT_VERSION.to_le_bytes());
+    bytes.extend(&(sections.len() as u32).to_le_bytes());
+    let checksum = crc32fast::hash(&bytes);
+    bytes.extend(&checksum.to_le_bytes());
+    for (tag, payload) in sections {
+        let mut section_header = Vec::new();
+        section_header.extend(&tag);
+        section_header.extend(&(payload.len() as u64).to_le_bytes());
+        bytes.extend(&section_header);
+        bytes.extend(&section_checksum(&section_header, &payload).to_le_bytes());
+        bytes.extend(&payload);
+        while bytes.len() % 4 != 0 {
+            bytes.push(0);
+        }
+    }
+    Ok(bytes)
+}
+
+pub fn write(network: &nodes_layers::Network, metadata: &ModelMetadata, file_path: &str) -> Result<(), NnError> {
+    //Writes the network and its metadata to a binary model file.
+    fs::write(file_path, to_bytes(network, metadata)?)?;
+    Ok(())
+}
+
+pub fn from_bytes(bytes: &[u8]) -> Result<(nodes_layers::Network, ModelMetadata), NnError> {
+    //Reads the contents of a binary model file, checking every checksum.
+    let layout = Layout::new(bytes)?;
+    let (input_scaler, target_scaler) = layout.scalers(bytes)?;
+    let mut layers = Vec::new();
+    for num in 0..layout.layers.len() {
+        let layer_header = &layout.header.layers[num];
+        let (biases, weights) = layout.layer_values(bytes, num);
+        let biases = floats(biases);
+        let weights = floats(weights);
+        let mut nodes = Vec::new();
+        for node_num in 0..layer_header.node_count {
+            let start = node_num * layer_header.weight_count;
+            let active_weights = match layout.node_mask(bytes, num, node_num) {
+                Some(node_mask) => {
+                    let active_weights: Vec<usize> = (0..layer_header.weight_count).filter(|&pos| node_mask[pos / 8] & (1 << (pos % 8)) != 0).collect();
+                    if active_weights.len() < layer_header.weight_count {Some(active_weights)} else {None}
+                },
+                None => None,
+            };
+            nodes.push(nodes_layers::Node {
+                bias: biases[node_num],
+                weights: weights[start..start + layer_header.weight_count].to_vec(),
+                personal_pos: node_num,
+                active_weights: active_weights,
+            });
+        }
+        layers.push(layer_header.layer(nodes)?);
+    }
+    layout.header.assemble(layers, input_scaler, target_scaler)
+}
+
+pub fn read(file_path: &str) -> Result<(nodes_layers::Network, ModelMetadata), NnError> {
+    //Reads a network written by write, along with its metadata.
+    from_bytes(&fs::read(file_path)?)
+}
+
+pub fn read_any(file_path: &str) -> Result<(nodes_layers::Network, ModelMetadata), NnError> {
+    //Reads either a binary model file or a .csv one written by model_file::write, telling them apart by the magic.
+    let bytes = fs::read(file_path)?;
+    if bytes.starts_with(&MAGIC) {
+        from_bytes(&bytes)
+    } else {
+        model_file::read(file_path)
+    }
+}
+
+struct LayerLayout {
+    biases: Range<usize>,
+    weights: Range<usize>,
+    mask: Option<Range<usize>>,
+}
+
+struct Layout {
+    //Where the parts of a binary model file are, found once and checked, so they can be read either by copying or in place.
+    header: ModelHeader,
+    scalers: Vec<Range<usize>>, //The payloads of the scalers, in the order of the header.
+    layers: Vec<LayerLayout>,
+}
+
+impl Layout {
+    fn new(bytes: &[u8]) -> Result<Layout, NnError> {
+        if bytes.len() < FILE_HEADER_LEN || bytes[0..8] != MAGIC {
+            return Err(NnError::Format("The file doesn't start like a binary model file".to_string()))
+        }
+        if crc32fast::hash(&bytes[0..16]) != u32_at(bytes, 16) {
+            return Err(NnError::Format("The checksum of the file header doesn't match, so the file is damaged".to_string()))
+        }
+        let version = u32_at(bytes, 8);
+        if version > FORMAT_VERSION {
+            return Err(NnError::Format(format!("The file is version {:?} of the format, but only versions up to {:?} can be read", version, FORMAT_VERSION)))
+        }
+        let section_count = u32_at(bytes, 12) as usize;
+
+        let mut header = None;
+        let mut scalers = Vec::new();
+        let mut layers: Vec<LayerLayout> = Vec::new();
+        let mut pos = FILE_HEADER_LEN;
+        for _ in 0..section_count {
+            if bytes.len() < pos + SECTION_HEADER_LEN {
+                return Err(NnError::Format("The file ends in the middle of a section header".to_string()))
+            }
+            let tag = [bytes[pos], bytes[pos+1], bytes[pos+2], bytes[pos+3]];
+            let length = u64_at(bytes, pos + 4);
+            let checksum = u32_at(bytes, pos + 12);
+            let start = pos + SECTION_HEADER_LEN;
+            if length > (bytes.len() - start) as u64 {
+                return Err(NnError::Format(format!("The {} section is {:?} bytes long, but the file ends before that", String::from_utf8_lossy(&tag), length)))
+            }
+            let end = start + length as usize;
+            if section_checksum(&bytes[pos..pos + 12], &bytes[start..end]) != checksum {
+                return Err(NnError::Format(format!("The checksum of the {} section doesn't match, so the file is damaged", String::from_utf8_lossy(&tag))))
+            }
+            pos = (end + 3) / 4 * 4;
+            if pos > bytes.len() || bytes[end..pos].iter().any(|&byte| byte != 0) {
+                return Err(NnError::Format(format!("The padding after the {} section is damaged", String::from_utf8_lossy(&tag))))
+            }
+
+            if tag == HEAD_TAG {
+                let mut rdr = csv::ReaderBuilder::new()
+                    .has_headers(false)
+                    .flexible(true)
+                    .from_reader(&bytes[start..end]);
+                header = Some(ModelHeader::read_records(&mut rdr.records())?);
+                continue
+            } else if tag != SCALER_TAG && tag != LAYER_TAG && tag != MASK_TAG {
+                continue
+            }
+            let header = match &header {
+                Some(header) => header,
+                None => return Err(NnError::Format("The header section has to come before the parameters".to_string())),
+            };
+            let scaler_headers: Vec<&model_file::ScalerHeader> = header.input_scaler.iter().chain(header.target_scaler.iter()).collect();
+            if tag == SCALER_TAG {
+                if scalers.len() >= scaler_headers.len() || !layers.is_empty() {
+                    return Err(NnError::Format("The file has a scaler section the header doesn't have".to_string()))
+                }
+                check_length("a scaler section", byte_count(scaler_headers[scalers.len()].value_count())?, end - start)?;
+                scalers.push(start..end);
+            } else if tag == LAYER_TAG {
+                if scalers.len() != scaler_headers.len() {
+                    return Err(NnError::Format("The file is missing a scaler section".to_string()))
+                }
+                let layer_header = match header.layers.get(layers.len()) {
+                    Some(layer_header) => layer_header,
+                    None => return Err(NnError::Format("The file has more layer sections than the header has layers".to_string())),
+                };
+                check_length("a layer section", byte_count(layer_header.value_count())?, end - start)?;
+                let weights_start = start + layer_header.node_count * 4;
+                layers.push(LayerLayout {
+                    biases: start..weights_start,
+                    weights: weights_start..end,
+                    mask: None,
+                });
+            } else {
+                let layer_num = layers.len();
+                let layer = match layers.last_mut() {
+                    Some(layer) => layer,
+                    None => return Err(NnError::Format("The file has a mask section before any layer".to_string())),
+                };
+                let layer_header = &header.layers[layer_num - 1];
+                check_length("a mask section", layer_header.node_count * ((layer_header.weight_count + 7) / 8), end - start)?;
+                layer.mask = Some(start..end);
+            }
+        }
+        if pos != bytes.len() {
+            return Err(NnError::Format("The file keeps going after its last section".to_string()))
+        }
+        let header = match header {
+            Some(header) => header,
+            None => return Err(NnError::Format("The file has no header section".to_string())),
+        };
+        if layers.len() != header.layers.len() {
+            return Err(NnError::shape_mismatch("the layer sections", header.layers.len(), layers.len()))
+        }
+        Ok(Layout {
+            header: header,
+            scalers: scalers,
+            layers: layers,
+        })
+    }
+
+    fn scalers(&self, bytes: &[u8]) -> Result<(Option<scaling::Scaler>, Option<scaling::Scaler>), NnError> {
+        //Copies the input and target scalers out of the file. Scalers are small, so they're never used in place.
+        let mut sections = self.scalers.iter();
+        let mut scaler = |scaler_header: &Option<model_file::ScalerHeader>| -> Result<Option<scaling::Scaler>, NnError> {
+            let (scaler_header, section) = match (scaler_header, sections.next()) {
+                (Some(scaler_header), Some(section)) => (scaler_header, section),
+                _ => return Ok(None),
+            };
+            let values = floats(&bytes[section.clone()]);
+            let mut rows = Vec::new();
+            let mut start = 0;
+            for length in scaler_header.line_lengths() {
+                rows.push(values[start..start + length].to_vec());
+                start += length;
+            }
+            Ok(Some(inout::scaler_from_rows(rows)?))
+        };
+        let input_scaler = scaler(&self.header.input_scaler)?;
+        let target_scaler = scaler(&self.header.target_scaler)?;
+        Ok((input_scaler, target_scaler))
+    }
+
+    fn layer_values<'a>(&self, bytes: &'a [u8], num: usize) -> (&'a [u8], &'a [u8]) {
+        let layer = &self.layers[num];
+        (&bytes[layer.biases.clone()], &bytes[layer.weights.clone()])
+    }
+
+    fn node_mask<'a>(&self, bytes: &'a [u8], num: usize, node_num: usize) -> Option<&'a [u8]> {
+        //The mask bytes of a node, if its layer has a mask.
+        let node_len = (self.header.layers[num].weight_count + 7) / 8;
+        self.layers[num].mask.as_ref().map(|mask| &bytes[mask.start + node_num * node_len..mask.start + (node_num + 1) * node_len])
+    }
+}
+
+fn byte_count(value_count: Option<usize>) -> Result<usize, NnError> {
+    //The size of a section of f32 values. The counts come from the file, so they're checked for overflow before they're trusted.
+    match value_count.and_then(|value_count| value_count.checked_mul(4)) {
+        Some(byte_count) => Ok(byte_count),
+        None => Err(NnError::Format("A section of the file is too big to be read".to_string())),
+    }
+}
+
+fn section_checksum(section_header: &[u8], payload: &[u8]) -> u32 {
+    //The checksum covers the tag and the length too, so a damaged one can't make the reader skip or misread a section.
+    let mut hasher = crc32fast::Hasher::new();
+    hasher.update(section_header);
+    hasher.update(payload);
+    hasher.finalize()
+}
+
+fn check_length(what: &str, expected: usize, found: usize) -> Result<(), NnError> {
+    if expected != found {
+        return Err(NnError::shape_mismatch(what, expected, found))
+    }
+    Ok(())
+}
+
+fn u32_at(bytes: &[u8], pos: usize) -> u32 {
+    let mut value = [0u8; 4];
+    value.copy_from_slice(&bytes[pos..pos+4]);
+    u32::from_le_bytes(value)
+}
+
+fn u64_at(bytes: &[u8], pos: usize) -> u64 {
+    let mut value = [0u8; 8];
+    value.copy_from_slice(&bytes[pos..pos+8]);
+    u64::from_le_bytes(value)
+}
+
+fn float_bytes(values: &[f32]) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(values.len() * 4);
+    for value in values {
+        bytes.extend(&value.to_bits().to_le_bytes());
+    }
+    bytes
+}
+
+fn floats(bytes: &[u8]) -> Vec<f32> {
+    let mut values = Vec::with_capacity(bytes.len() / 4);
+    for pos in (0..bytes.len()).step_by(4) {
+        values.push(f32::from_bits(u32_at(bytes, pos)));
+    }
+    values
+}
+
+#[cfg(feature = "mmap")]
+pub struct MappedModel {
+    //A binary model file mapped into memory, so its weights are used where they are in the file instead of being copied.
+    //This makes opening big models nearly free, and several processes using the same file share its memory.
+    //The checksums are checked when it's opened, so the file shouldn't be changed while it's mapped.
+    map: memmap2::Mmap,
+    layout: Layout,
+    input_scaler: Option<scaling::Scaler>,
+    target_scaler: Option<scaling::Scaler>,
+}
+
+#[cfg(feature = "mmap")]
+impl MappedModel {
+    pub fn open(file_path: &str) -> Result<MappedModel, NnError> {
+        //Maps a file written by write. The values are stored little-endian, so they can only be used in place on little-endian machines.
+        if cfg!(target_endian = "big") {
+            return Err(NnError::Config("Memory-mapped models only work on little-endian machines, use read instead".to_string()))
+        }
+        let file = fs::File::open(file_path)?;
+        let map = unsafe {memmap2::Mmap::map(&file)?};
+        if (map.as_ptr() as usize) % 4 != 0 {
+            return Err(NnError::Format("The mapped file isn't aligned to 4 bytes".to_string()))
+        }
+        let layout = Layout::new(&map)?;
+        let (input_scaler, target_scaler) = layout.scalers(&map)?;
+        Ok(MappedModel {
+            map: map,
+            layout: layout,
+            input_scaler: input_scaler,
+            target_scaler: target_scaler,
+        })
+    }
+
+    pub fn metadata(&self) -> &ModelMetadata {
+        &self.layout.header.metadata
+    }
+
+    pub fn header(&self) -> &ModelHeader {
+        &self.layout.header
+    }
+
+    pub fn layer_count(&self) -> usize {
+        self.layout.layers.len()
+    }
+
+    pub fn layer_biases(&self, num: usize) -> &[f32] {
+        //The biases of a layer's nodes, straight from the file.
+        mapped_floats(self.layout.layer_values(&self.map, num).0)
+    }
+
+    pub fn layer_weights(&self, num: usize) -> &[f32] {
+        //The weights of a layer's nodes, one node after another, straight from the file. Pruned weights are 0.0.
+        mapped_floats(self.layout.layer_values(&self.map, num).1)
+    }
+
+    pub fn predict(&self, inputs: &Vec<f32>) -> Result<Vec<f32>, NnError> {
+        //Works like nodes_layers::Network::predict, giving the same outputs without loading the network.
+        let mut values = match &self.input_scaler {
+            Some(input_scaler) => input_scaler.transform(inputs)?,
+            None => inputs.clone(),
+        };
+        for num in 0..self.layer_count() {
+            let layer_header = &self.layout.header.layers[num];
+            if values.len() != layer_header.weight_count {
+                return Err(NnError::shape_mismatch("the values going into a node", layer_header.weight_count, values.len()))
+            }
+            let biases = self.layer_biases(num);
+            let weights = self.layer_weights(num);
+            let mut layer_values = Vec::with_capacity(layer_header.node_count);
+            for node_num in 0..layer_header.node_count {
+                let node_weights = &weights[node_num * layer_header.weight_count..(node_num + 1) * layer_header.weight_count];
+                let mut value = biases[node_num];
+                match self.layout.node_mask(&self.map, num, node_num) {
+                    Some(node_mask) => {
+                        //Pruned weights are skipped, like in nodes_layers::Node::weighted_sum.
+                        for pos in 0..values.len() {
+                            if node_mask[pos / 8] & (1 << (pos % 8)) != 0 {
+                                value += values[pos] * node_weights[pos];
+                            }
+                        }
+                    },
+                    None => {
+                        for pos in 0..values.len() {
+                            value += values[pos] * node_weights[pos];
+                        }
+                    },
+                }
+                layer_values.push(layer_header.activation.apply(value));
+            }
+            values = layer_values;
+        }
+        match &self.target_scaler {
+            Some(target_scaler) => target_scaler.inverse_transform(&values),
+            None => Ok(values),
+        }
+    }
+
+    pub fn to_network(&self) -> Result<(nodes_layers::Network, ModelMetadata), NnError> {
+        //Copies the model into a network, for training it or anything else predict isn't enough for.
+        from_bytes(&self.map)
+    }
+}
+
+#[cfg(feature = "mmap")]
+fn mapped_floats(bytes: &[u8]) -> &[f32] {
+    //Layout::new has checked the lengths, every payload starts on a multiple of 4 bytes from the start of the map,
+    //MappedModel::open has checked that the map is aligned and that the machine is little-endian, and any bits are a valid f32.
+    unsafe {std::slice::from_raw_parts(bytes.as_ptr() as *const f32, bytes.len() / 4)}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::labels;
+
+    fn temp_path(name: &str) -> String {
+        std::env::temp_dir().join(format!("numbers_nn_{}_{}", std::process::id(), name)).to_string_lossy().into_owned()
+    }
+
+    fn sparse_network() -> nodes_layers::Network {
+        //A network with both kinds of scaler, a pruned first layer, a linear output layer and a label map of its own.
+        let rows = vec![vec![0.0, 1.0, 4.0, -2.0], vec![1.0, 3.0, 2.0, 0.5], vec![2.0, 2.0, 0.0, 1.5], vec![5.0, 0.0, 1.0, 0.0]];
+        let mut network = nodes_layers::Network::new(&[3, 3, 2], 0.1);
+        network.layers[0].nodes[0].prune(&[0, 2]).unwrap();
+        network.layers[0].nodes[2].prune(&[1]).unwrap();
+        network.layers[1].activation = nodes_layers::Activation::Linear;
+        network.loss = nodes_layers::Loss::Huber(0.5);
+        network.input_scaler = Some(scaling::Scaler::fit_pca_whitening(&rows, 3, 1e-5).unwrap());
+        network.target_scaler = Some(scaling::Scaler::fit_min_max(&[vec![0.0, 10.0], vec![4.0, -10.0]]).unwrap());
+        let label_map = labels::LabelMap::from_keys(vec!["cat".to_string(), "dog".to_string()]);
+        network.with_label_map(label_map).unwrap()
+    }
+
+    fn metadata() -> ModelMetadata {
+        ModelMetadata::new().with_dataset("test").with_examples_seen(42).with_accuracy(0.75)
+    }
+
+    fn assert_same_network(first: &nodes_layers::Network, second: &nodes_layers::Network) {
+        assert_eq!(first.layer_count, second.layer_count);
+        for (first_layer, second_layer) in first.layers.iter().zip(&second.layers) {
+            assert_eq!(first_layer.activation, second_layer.activation);
+            assert_eq!(first_layer.learning_rate, second_layer.learning_rate);
+            for (first_node, second_node) in first_layer.nodes.iter().zip(&second_layer.nodes) {
+                assert_eq!(first_node.bias, second_node.bias);
+                assert_eq!(first_node.weights, second_node.weights);
+                assert_eq!(first_node.active_weights, second_node.active_weights);
+            }
+        }
+        assert_eq!(first.loss, second.loss);
+        assert_eq!(first.label_map, second.label_map);
+        assert_eq!(first.input_scaler, second.input_scaler);
+        assert_eq!(first.target_scaler, second.target_scaler);
+    }
+
+    fn payloads(bytes: &[u8]) -> Vec<Range<usize>> {
+        //Where the payload of each section is, walked the same way Layout::new does it.
+        let mut payloads = Vec::new();
+        let mut pos = FILE_HEADER_LEN;
+        for _ in 0..u32_at(bytes, 12) {
+            let start = pos + SECTION_HEADER_LEN;
+            let end = start + u64_at(bytes, pos + 4) as usize;
+            payloads.push(start..end);
+            pos = (end + 3) / 4 * 4;
+        }
+        payloads
+    }
+
+    #[test]
+    fn round_trip_keeps_sparse_network_and_scalers() {
+        let network = sparse_network();
+        let file_path = temp_path("round_trip.nnm");
+        write(&network, &metadata(), &file_path).unwrap();
+        let (read_network, read_metadata) = read(&file_path).unwrap();
+        std::fs::remove_file(&file_path).unwrap();
+        assert_same_network(&network, &read_network);
+        assert_eq!(read_metadata, metadata());
+        assert!(read_network.layers[0].is_sparse());
+        assert!(!read_network.layers[1].is_sparse());
+        let inputs = vec![1.0, 2.0, 3.0, 4.0];
+        assert_eq!(network.predict(&inputs).unwrap(), read_network.predict(&inputs).unwrap());
+    }
+
+    #[test]
+    fn payloads_start_on_multiples_of_four() {
+        let bytes = to_bytes(&sparse_network(), &metadata()).unwrap();
+        assert_eq!(bytes.len() % 4, 0);
+        for payload in payloads(&bytes) {
+            assert_eq!(payload.start % 4, 0);
+        }
+    }
+
+    #[test]
+    fn flipped_payload_byte_fails_the_checksum() {
+        let bytes = to_bytes(&sparse_network(), &metadata()).unwrap();
+        let payloads = payloads(&bytes);
+        assert_eq!(payloads.len(), 6); //The header, two scalers, two layers and the mask of the first layer.
+        for payload in payloads {
+            let mut damaged = bytes.clone();
+            damaged[payload.start] ^= 0x10;
+            match from_bytes(&damaged) {
+                Err(NnError::Format(details)) => assert!(details.contains("checksum"), "{}", details),
+                other => panic!("Expected a checksum error, found {:?}", other.map(|_| ())),
+            }
+        }
+    }
+
+    #[test]
+    fn any_flipped_byte_is_an_error() {
+        //The file header, the section headers and the padding are covered as well as the payloads.
+        let bytes = to_bytes(&sparse_network(), &metadata()).unwrap();
+        for pos in 0..bytes.len() {
+            let mut damaged = bytes.clone();
+            damaged[pos] ^= 0x01;
+            assert!(from_bytes(&damaged).is_err(), "Flipping byte {} wasn't noticed", pos);
+        }
+    }
+
+    #[test]
+    fn truncated_file_is_an_error() {
+        let bytes = to_bytes(&sparse_network(), &metadata()).unwrap();
+        for length in 0..bytes.len() {
+            assert!(from_bytes(&bytes[..length]).is_err(), "A file cut to {} bytes was read", length);
+        }
+    }
+
+    #[test]
+    fn newer_version_is_rejected() {
+        let mut bytes = to_bytes(&sparse_network(), &metadata()).unwrap();
+        bytes[8..12].copy_from_slice(&(FORMAT_VERSION + 1).to_le_bytes());
+        let checksum = crc32fast::hash(&bytes[0..16]);
+        bytes[16..20].copy_from_slice(&checksum.to_le_bytes());
+        match from_bytes(&bytes) {
+            Err(NnError::Format(details)) => assert!(details.contains("version"), "{}", details),
+            other => panic!("Expected a version error, found {:?}", other.map(|_| ())),
+        }
+    }
+
+    #[test]
+    fn read_any_falls_back_to_csv() {
+        let network = sparse_network();
+        let file_path = temp_path("read_any.csv");
+        model_file::write(&network, &metadata(), &file_path).unwrap();
+        let (read_network, read_metadata) = read_any(&file_path).unwrap();
+        std::fs::remove_file(&file_path).unwrap();
+        assert_same_network(&network, &read_network);
+        assert_eq!(read_metadata, metadata());
+    }
+
+    #[cfg(feature = "mmap")]
+    #[test]
+    fn mapped_model_matches_network() {
+        let network = sparse_network();
+        let file_path = temp_path("mapped.nnm");
+        write(&network, &metadata(), &file_path).unwrap();
+        let mapped = MappedModel::open(&file_path).unwrap();
+        assert_eq!(mapped.metadata(), &metadata());
+        assert_eq!(mapped.layer_count(), network.layer_count);
+        for num in 0..network.layer_count {
+            let biases: Vec<f32> = network.layers[num].nodes.iter().map(|node| node.bias).collect();
+            let weights: Vec<f32> = network.layers[num].nodes.iter().flat_map(|node| node.weights.clone()).collect();
+            assert_eq!(mapped.layer_biases(num), &biases[..]);
+            assert_eq!(mapped.layer_weights(num), &weights[..]);
+        }
+        let inputs = vec![0.5, -1.0, 2.0, 3.0];
+        assert_eq!(mapped.predict(&inputs).unwrap(), network.predict(&inputs).unwrap());
+        assert_same_network(&network, &mapped.to_network().unwrap().0);
+        drop(mapped);
+        std::fs::remove_file(&file_path).unwrap();
+    }
+}
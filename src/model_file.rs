@@ -0,0 +1,617 @@
+extern crate csv;
+
+use std::io;
+
+use crate::dataset;
+use crate::error::NnError;
+use crate::inout;
+use crate::labels;
+use crate::nodes_layers;
+use crate::preprocess;
+use crate::scaling;
+
+pub const FORMAT_NAME: &str = "numbers_nn model";
+pub const FORMAT_VERSION: u32 = 1; //Raised whenever a file written by this version can't be read by an older one.
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ModelMetadata {
+    //What's known about how a network was made, saved in the same file as it. All of it is optional.
+    pub dataset: Option<String>, //The name of the dataset the network was trained on, like "mnist" or "fashion-mnist".
+    pub shape: Option<dataset::Shape>, //The shape of the inputs, so images can be fitted to the network without knowing the dataset.
+    pub preprocessing: Option<preprocess::Preprocessing>, //How images should be prepared before being classified, if they need more than fitting to the shape.
+    pub examples_seen: usize, //The number of training steps the network has been through.
+    pub accuracy: Option<f32>, //The accuracy measured the last time the network was trained or tested.
+}
+
+impl ModelMetadata {
+    pub fn new() -> ModelMetadata {
+        ModelMetadata {
+            dataset: None,
+            shape: None,
+            preprocessing: None,
+            examples_seen: 0,
+            accuracy: None,
+        }
+    }
+
+    pub fn with_dataset(mut self, dataset: &str) -> ModelMetadata {
+        self.dataset = Some(dataset.to_string());
+        self
+    }
+
+    pub fn with_shape(mut self, shape: dataset::Shape) -> ModelMetadata {
+        self.shape = Some(shape);
+        self
+    }
+
+    pub fn with_preprocessing(mut self, preprocessing: preprocess::Preprocessing) -> ModelMetadata {
+        self.preprocessing = Some(preprocessing);
+        self
+    }
+
+    pub fn with_examples_seen(mut self, examples_seen: usize) -> ModelMetadata {
+        self.examples_seen = examples_seen;
+        self
+    }
+
+    pub fn with_accuracy(mut self, accuracy: f32) -> ModelMetadata {
+        self.accuracy = Some(accuracy);
+        self
+    }
+}
+
+impl Default for ModelMetadata {
+    fn default() -> ModelMetadata {
+        ModelMetadata::new()
+    }
+}
+
+pub fn write(network: &nodes_layers::Network, metadata: &ModelMetadata, file_path: &str) -> Result<(), NnError> {
+    //Writes the network and everything needed to use it to a single .csv file, so read needs nothing but the path.
+    //The file starts with a header, with a line per setting that begins with what the setting is:
+    //  numbers_nn model,<version>
+    //  dataset,<name>                                            if known
+    //  shape,<channels>,<height>,<width>                         if known
+    //  preprocessing,<inversion>,<threshold>,<threshold value>,<box fraction>,<deskew>   if there is any
+    //  examples_seen,<count>
+    //  accuracy,<accuracy>                                       if known
+    //  loss,<loss>,<huber size>
+    //  label,<label>,<name>                                      one per output, in order
+    //  input_scaler,<kind>,<inputs>,<components>                 if there is one, and the same for target_scaler
+    //  layer,dense,<inputs>,<nodes>,<activation>,<learning rate>   one per layer, in order
+    //  parameters
+    //After that come the lines of the scalers, like in inout::write_scaler, and then the nodes of each layer, like in inout::write_layer.
+    let mut wtr = csv::WriterBuilder::new()
+        .flexible(true)
+        .from_path(file_path)
+    ?;
+    wtr.write_record(&[FORMAT_NAME.to_string(), FORMAT_VERSION.to_string()])?;
+    ModelHeader::new(network, metadata).write_records(&mut wtr)?;
+    wtr.write_record(&["parameters"])?;
+    for scaler in &[&network.input_scaler, &network.target_scaler] {
+        if let Some(scaler) = scaler {
+            inout::write_scaler_records(scaler, &mut wtr)?;
+        }
+    }
+    for layer in &network.layers {
+        for node in layer.nodes.clone() {
+            wtr.write_record(node)?;
+        }
+    }
+    wtr.flush()?;
+    Ok(())
+}
+
+pub fn read(file_path: &str) -> Result<(nodes_layers::Network, ModelMetadata), NnError> {
+    //Reads a network written by write, along with its metadata.
+    let mut rdr = csv::ReaderBuilder::new()
+        .has_headers(false)
+        .flexible(true)
+        .from_path(file_path)
+    ?;
+    let mut records = rdr.records();
+    let first = next_record(&mut records, "the format line")?;
+    if first.get(0) != Some(FORMAT_NAME) {
+        return Err(NnError::Format(format!("Expected the file to start with {:?}, found {:?}", FORMAT_NAME, first.get(0).unwrap_or(""))))
+    }
+    let version: u32 = field(&first, 1, "the format version")?.parse()?;
+    if version > FORMAT_VERSION {
+        return Err(NnError::Format(format!("The file is version {:?} of the format, but only versions up to {:?} can be read", version, FORMAT_VERSION)))
+    }
+    let header = ModelHeader::read_records(&mut records)?;
+    let input_scaler = match &header.input_scaler {
+        Some(scaler_header) => Some(read_scaler(&mut records, scaler_header)?),
+        None => None,
+    };
+    let target_scaler = match &header.target_scaler {
+        Some(scaler_header) => Some(read_scaler(&mut records, scaler_header)?),
+        None => None,
+    };
+    let mut layers = Vec::new();
+    for layer_header in &header.layers {
+        let mut nodes = Vec::new();
+        for num in 0..layer_header.node_count {
+            nodes.push(inout::node_from_record(&next_record(&mut records, "a node")?, num)?);
+        }
+        layers.push(layer_header.layer(nodes)?);
+    }
+    if records.next().is_some() {
+        return Err(NnError::Format("The file kept going after the last node".to_string()))
+    }
+    header.assemble(layers, input_scaler, target_scaler)
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct LayerHeader {
+    //What the header says about a layer. Only dense layers exist so far.
+    pub weight_count: usize, //The number of inputs of each node.
+    pub node_count: usize,
+    pub activation: nodes_layers::Activation,
+    pub learning_rate: f32,
+}
+
+impl LayerHeader {
+    pub fn value_count(&self) -> Option<usize> {
+        //The number of biases and weights, or None if it's too big to count, which only a damaged file can give.
+        self.weight_count.checked_add(1)?.checked_mul(self.node_count)
+    }
+
+    pub fn layer(&self, nodes: Vec<nodes_layers::Node>) -> Result<nodes_layers::Layer, NnError> {
+        //Puts together the layer from its nodes, checking that they fit the header.
+        if nodes.len() != self.node_count {
+            return Err(NnError::shape_mismatch("the nodes of the layer", self.node_count, nodes.len()))
+        }
+        for node in &nodes {
+            if node.weights.len() != self.weight_count {
+                return Err(NnError::shape_mismatch("the weights of each node", self.weight_count, node.weights.len()))
+            }
+        }
+        Ok(nodes_layers::Layer {
+            nodes: nodes,
+            node_count: self.node_count,
+            learning_rate: self.learning_rate,
+            activation: self.activation,
+        })
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ScalerHeader {
+    pub is_whitening: bool,
+    pub input_count: usize,
+    pub component_count: usize, //0 unless it whitens.
+}
+
+impl ScalerHeader {
+    pub fn line_count(&self) -> usize {
+        //The offsets and the scales, and then the whitening's scales and a line per component if it whitens.
+        if self.is_whitening {3 + self.component_count} else {2}
+    }
+
+    pub fn value_count(&self) -> Option<usize> {
+        //The number of values on all the lines together, or None if it's too big to count, which only a damaged file can give.
+        let standard_count = self.input_count.checked_mul(2)?;
+        if self.is_whitening {
+            self.component_count.checked_mul(self.input_count)?.checked_add(self.component_count)?.checked_add(standard_count)
+        } else {
+            Some(standard_count)
+        }
+    }
+
+    pub fn line_lengths(&self) -> Vec<usize> {
+        //The number of values on each line, in order. Check value_count first if the header comes from a file.
+        let mut lengths = vec![self.input_count, self.input_count];
+        if self.is_whitening {
+            lengths.push(self.component_count);
+            lengths.extend(vec![self.input_count; self.component_count]);
+        }
+        lengths
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ModelHeader {
+    //Everything in a model file except the parameters, so other formats can store the parameters their own way.
+    pub metadata: ModelMetadata,
+    pub loss: nodes_layers::Loss,
+    pub keys: Vec<String>, //The labels of the outputs, in order.
+    pub names: Vec<String>,
+    pub input_scaler: Option<ScalerHeader>,
+    pub target_scaler: Option<ScalerHeader>,
+    pub layers: Vec<LayerHeader>,
+}
+
+impl ModelHeader {
+    pub fn new(network: &nodes_layers::Network, metadata: &ModelMetadata) -> ModelHeader {
+        let scaler_header = |scaler: &scaling::Scaler| ScalerHeader {
+            is_whitening: scaler.whitening.is_some(),
+            input_count: scaler.offsets.len(),
+            component_count: scaler.whitening.as_ref().map_or(0, |whitening| whitening.components.len()),
+        };
+        let mut layers = Vec::new();
+        for layer in &network.layers {
+            layers.push(LayerHeader {
+                weight_count: layer.nodes.first().map_or(0, |node| node.weights.len()),
+                node_count: layer.node_count,
+                activation: layer.activation,
+                learning_rate: layer.learning_rate,
+            });
+        }
+        ModelHeader {
+            metadata: metadata.clone(),
+            loss: network.loss,
+            keys: network.label_map.keys.clone(),
+            names: network.label_map.names.clone(),
+            input_scaler: network.input_scaler.as_ref().map(scaler_header),
+            target_scaler: network.target_scaler.as_ref().map(scaler_header),
+            layers: layers,
+        }
+    }
+
+    pub fn write_records<W: io::Write>(&self, wtr: &mut csv::Writer<W>) -> Result<(), NnError> {
+        //Writes the header lines described in write, without the first line and the parameters line.
+        let metadata = &self.metadata;
+        if let Some(dataset) = &metadata.dataset {
+            wtr.write_record(&["dataset", dataset])?;
+        }
+        if let Some(shape) = metadata.shape {
+            wtr.write_record(&["shape".to_string(), shape.channels.to_string(), shape.height.to_string(), shape.width.to_string()])?;
+        }
+        if let Some(preprocessing) = &metadata.preprocessing {
+            wtr.write_record(&preprocessing_record(preprocessing))?;
+        }
+        wtr.write_record(&["examples_seen".to_string(), metadata.examples_seen.to_string()])?;
+        if let Some(accuracy) = metadata.accuracy {
+            wtr.write_record(&["accuracy".to_string(), accuracy.to_string()])?;
+        }
+        wtr.write_record(&loss_record(self.loss))?;
+        for num in 0..self.keys.len() {
+            wtr.write_record(&["label", &self.keys[num], &self.names[num]])?;
+        }
+        for (name, scaler) in &[("input_scaler", &self.input_scaler), ("target_scaler", &self.target_scaler)] {
+            if let Some(scaler) = scaler {
+                let kind = if scaler.is_whitening {"whitening"} else {"standard"};
+                wtr.write_record(&[name.to_string(), kind.to_string(), scaler.input_count.to_string(), scaler.component_count.to_string()])?;
+            }
+        }
+        for layer in &self.layers {
+            wtr.write_record(&[
+                "layer".to_string(),
+                "dense".to_string(),
+                layer.weight_count.to_string(),
+                layer.node_count.to_string(),
+                activation_name(layer.activation).to_string(),
+                layer.learning_rate.to_string(),
+            ])?;
+        }
+        Ok(())
+    }
+
+    pub fn read_records<R: io::Read>(records: &mut csv::StringRecordsIter<R>) -> Result<ModelHeader, NnError> {
+        //Reads header lines up to the parameters line or the end. Lines that aren't known are skipped,
+        //so settings can be added to the header without older versions of this function failing on them.
+        let mut header = ModelHeader {
+            metadata: ModelMetadata::new(),
+            loss: nodes_layers::Loss::SquaredError,
+            keys: Vec::new(),
+            names: Vec::new(),
+            input_scaler: None,
+            target_scaler: None,
+            layers: Vec::new(),
+        };
+        for result in records {
+            let record = result?;
+            match record.get(0).unwrap_or("") {
+                "parameters" => break,
+                "dataset" => header.metadata.dataset = Some(field(&record, 1, "the dataset")?.to_string()),
+                "shape" => {
+                    header.metadata.shape = Some(dataset::Shape::image(
+                        field(&record, 1, "the channels")?.parse()?,
+                        field(&record, 2, "the height")?.parse()?,
+                        field(&record, 3, "the width")?.parse()?,
+                    ));
+                },
+                "preprocessing" => header.metadata.preprocessing = Some(read_preprocessing(&record)?),
+                "examples_seen" => header.metadata.examples_seen = field(&record, 1, "the examples seen")?.parse()?,
+                "accuracy" => header.metadata.accuracy = Some(parse_float(field(&record, 1, "the accuracy")?)?),
+                "loss" => header.loss = read_loss(&record)?,
+                "label" => {
+                    header.keys.push(field(&record, 1, "the label")?.to_string());
+                    header.names.push(field(&record, 2, "the class name")?.to_string());
+                },
+                "input_scaler" => header.input_scaler = Some(read_scaler_header(&record)?),
+                "target_scaler" => header.target_scaler = Some(read_scaler_header(&record)?),
+                "layer" => header.layers.push(read_layer_header(&record)?),
+                _ => {},
+            }
+        }
+        if header.layers.is_empty() {
+            return Err(NnError::Format("The file has no layers".to_string()))
+        }
+        for num in 1..header.layers.len() {
+            if header.layers[num].weight_count != header.layers[num-1].node_count {
+                return Err(NnError::shape_mismatch("the inputs of a layer", header.layers[num-1].node_count, header.layers[num].weight_count))
+            }
+        }
+        Ok(header)
+    }
+
+    pub fn assemble(self, layers: Vec<nodes_layers::Layer>, input_scaler: Option<scaling::Scaler>, target_scaler: Option<scaling::Scaler>) -> Result<(nodes_layers::Network, ModelMetadata), NnError> {
+        //Puts together the network from the parameters that were read, checking that the scalers fit the layers.
+        if let Some(input_scaler) = &input_scaler {
+            if input_scaler.output_count() != self.layers[0].weight_count {
+                return Err(NnError::shape_mismatch("the values the input scaler gives", self.layers[0].weight_count, input_scaler.output_count()))
+            }
+        }
+        let mut network = nodes_layers::Network::from_layers(layers);
+        if let Some(target_scaler) = &target_scaler {
+            if target_scaler.offsets.len() != network.output_count() {
+                return Err(NnError::shape_mismatch("the values the target scaler takes", network.output_count(), target_scaler.offsets.len()))
+            }
+        }
+        network.loss = self.loss;
+        network.input_scaler = input_scaler;
+        network.target_scaler = target_scaler;
+        //A file without labels gets numbered outputs, like a network loaded with inout::read_network.
+        if !self.keys.is_empty() {
+            network = network.with_label_map(labels::LabelMap::from_keys(self.keys).with_names(self.names)?)?;
+        }
+        Ok((network, self.metadata))
+    }
+}
+
+fn next_record<R: io::Read>(records: &mut csv::StringRecordsIter<R>, what: &str) -> Result<csv::StringRecord, NnError> {
+    match records.next() {
+        Some(record) => Ok(record?),
+        None => Err(NnError::Format(format!("The file ended before {}", what))),
+    }
+}
+
+fn field<'a>(record: &'a csv::StringRecord, pos: usize, what: &str) -> Result<&'a str, NnError> {
+    record.get(pos).ok_or_else(|| NnError::Format(format!("Expected {} on the {:?} line", what, record.get(0).unwrap_or(""))))
+}
+
+fn parse_float(text: &str) -> Result<f32, NnError> {
+    text.parse().map_err(|_| NnError::Format(format!("Expected a number, found {:?}", text)))
+}
+
+fn activation_name(activation: nodes_layers::Activation) -> &'static str {
+    match activation {
+        nodes_layers::Activation::Sigmoid => "sigmoid",
+        nodes_layers::Activation::Linear => "linear",
+    }
+}
+
+fn read_layer_header(record: &csv::StringRecord) -> Result<LayerHeader, NnError> {
+    let kind = field(record, 1, "the layer type")?;
+    if kind != "dense" {
+        return Err(NnError::Format(format!("Unknown layer type {:?}", kind)))
+    }
+    let activation = match field(record, 4, "the activation")? {
+        "sigmoid" => nodes_layers::Activation::Sigmoid,
+        "linear" => nodes_layers::Activation::Linear,
+        other => return Err(NnError::Format(format!("Unknown activation {:?}", other))),
+    };
+    let header = LayerHeader {
+        weight_count: field(record, 2, "the number of inputs")?.parse()?,
+        node_count: field(record, 3, "the number of nodes")?.parse()?,
+        activation: activation,
+        learning_rate: parse_float(field(record, 5, "the learning rate")?)?,
+    };
+    if header.value_count().is_none() {
+        return Err(NnError::Format(format!("A layer of {:?} nodes with {:?} weights each is too big", header.node_count, header.weight_count)))
+    }
+    Ok(header)
+}
+
+fn loss_record(loss: nodes_layers::Loss) -> Vec<String> {
+    match loss {
+        nodes_layers::Loss::SquaredError => vec!["loss".to_string(), "squared_error".to_string()],
+        nodes_layers::Loss::AbsoluteError => vec!["loss".to_string(), "absolute_error".to_string()],
+        nodes_layers::Loss::Huber(size) => vec!["loss".to_string(), "huber".to_string(), size.to_string()],
+    }
+}
+
+fn read_loss(record: &csv::StringRecord) -> Result<nodes_layers::Loss, NnError> {
+    match field(record, 1, "the loss")? {
+        "squared_error" => Ok(nodes_layers::Loss::SquaredError),
+        "absolute_error" => Ok(nodes_layers::Loss::AbsoluteError),
+        "huber" => Ok(nodes_layers::Loss::Huber(parse_float(field(record, 2, "the size of the Huber loss")?)?)),
+        other => Err(NnError::Format(format!("Unknown loss {:?}", other))),
+    }
+}
+
+fn read_scaler_header(record: &csv::StringRecord) -> Result<ScalerHeader, NnError> {
+    let is_whitening = match field(record, 1, "the kind of scaler")? {
+        "standard" => false,
+        "whitening" => true,
+        other => return Err(NnError::Format(format!("Unknown kind of scaler {:?}", other))),
+    };
+    let header = ScalerHeader {
+        is_whitening: is_whitening,
+        input_count: field(record, 2, "the number of inputs")?.parse()?,
+        component_count: field(record, 3, "the number of components")?.parse()?,
+    };
+    if header.value_count().is_none() {
+        return Err(NnError::Format(format!("A scaler of {:?} inputs and {:?} components is too big", header.input_count, header.component_count)))
+    }
+    if header.component_count > if is_whitening {header.input_count} else {0} {
+        return Err(NnError::Format(format!("A scaler of {:?} inputs can't have {:?} components", header.input_count, header.component_count)))
+    }
+    Ok(header)
+}
+
+fn read_scaler<R: io::Read>(records: &mut csv::StringRecordsIter<R>, header: &ScalerHeader) -> Result<scaling::Scaler, NnError> {
+    let mut rows = Vec::new();
+    for _ in 0..header.line_count() {
+        rows.push(inout::values_from_record(&next_record(records, "the end of a scaler")?)?);
+    }
+    let scaler = inout::scaler_from_rows(rows)?;
+    if scaler.offsets.len() != header.input_count {
+        return Err(NnError::shape_mismatch("the inputs of the scaler", header.input_count, scaler.offsets.len()))
+    }
+    Ok(scaler)
+}
+
+fn preprocessing_record(preprocessing: &preprocess::Preprocessing) -> Vec<String> {
+    let inversion = match preprocessing.inversion {
+        preprocess::Inversion::Never => "never",
+        preprocess::Inversion::Always => "always",
+        preprocess::Inversion::Detect => "detect",
+    };
+    let (threshold, threshold_value) = match preprocessing.threshold {
+        preprocess::Threshold::Nothing => ("nothing", String::new()),
+        preprocess::Threshold::Fixed(value) => ("fixed", value.to_string()),
+        preprocess::Threshold::Otsu => ("otsu", String::new()),
+    };
+    vec![
+        "preprocessing".to_string(),
+        inversion.to_string(),
+        threshold.to_string(),
+        threshold_value,
+        preprocessing.box_fraction.to_string(),
+        preprocessing.deskew.to_string(),
+    ]
+}
+
+fn read_preprocessing(record: &csv::StringRecord) -> Result<preprocess::Preprocessing, NnError> {
+    let inversion = match field(record, 1, "the inversion")? {
+        "never" => preprocess::Inversion::Never,
+        "always" => preprocess::Inversion::Always,
+        "detect" => preprocess::Inversion::Detect,
+        other => return Err(NnError::Format(format!("Unknown inversion {:?}", other))),
+    };
+    let threshold = match field(record, 2, "the threshold")? {
+        "nothing" => preprocess::Threshold::Nothing,
+        "fixed" => preprocess::Threshold::Fixed(parse_float(field(record, 3, "the threshold value")?)?),
+        "otsu" => preprocess::Threshold::Otsu,
+        other => return Err(NnError::Format(format!("Unknown threshold {:?}", other))),
+    };
+    let deskew = match field(record, 5, "whether to deskew")? {
+        "true" => true,
+        "false" => false,
+        other => return Err(NnError::Format(format!("Expected true or false for deskewing, found {:?}", other))),
+    };
+    Ok(preprocess::Preprocessing {
+        inversion: inversion,
+        threshold: threshold,
+        box_fraction: parse_float(field(record, 4, "the box fraction")?)?,
+        deskew: deskew,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_path(name: &str) -> String {
+        std::env::temp_dir().join(format!("numbers_nn_{}_{}", std::process::id(), name)).to_string_lossy().into_owned()
+    }
+
+    fn dense_network() -> nodes_layers::Network {
+        let mut network = nodes_layers::Network::new(&[4, 3, 2], 0.25);
+        network.input_scaler = Some(scaling::Scaler::fit_standard(&[vec![0.0, 1.0, 2.0, 3.0], vec![2.0, 1.0, 0.0, 5.0]]).unwrap());
+        network.target_scaler = Some(scaling::Scaler::fit_min_max(&[vec![-1.0, 0.0], vec![1.0, 10.0]]).unwrap());
+        network.loss = nodes_layers::Loss::AbsoluteError;
+        let label_map = labels::LabelMap::from_keys(vec!["a".to_string(), "b".to_string()]).with_names(vec!["first, with a comma".to_string(), "second".to_string()]).unwrap();
+        network.with_label_map(label_map).unwrap()
+    }
+
+    fn sparse_network() -> nodes_layers::Network {
+        let rows = vec![vec![0.0, 1.0, 4.0], vec![1.0, 3.0, 2.0], vec![2.0, 2.0, 0.0], vec![5.0, 0.0, 1.0]];
+        let mut network = nodes_layers::Network::new(&[2, 4, 3], 0.1);
+        network.layers[0].nodes[1].prune(&[0]).unwrap();
+        network.layers[1].nodes[2].prune(&[1, 2, 3]).unwrap();
+        network.layers[1].activation = nodes_layers::Activation::Linear;
+        network.loss = nodes_layers::Loss::Huber(0.75);
+        network.input_scaler = Some(scaling::Scaler::fit_pca_whitening(&rows, 2, 1e-5).unwrap());
+        network.with_label_map(labels::LabelMap::from_codes(&[3, 5, 7])).unwrap()
+    }
+
+    fn metadata() -> ModelMetadata {
+        ModelMetadata::new()
+            .with_dataset("test")
+            .with_shape(dataset::Shape::image(1, 2, 2))
+            .with_preprocessing(preprocess::Preprocessing {
+                inversion: preprocess::Inversion::Detect,
+                threshold: preprocess::Threshold::Fixed(0.25),
+                box_fraction: 0.8,
+                deskew: true,
+            })
+            .with_examples_seen(1000)
+            .with_accuracy(0.5)
+    }
+
+    fn assert_same_network(first: &nodes_layers::Network, second: &nodes_layers::Network) {
+        assert_eq!(first.layer_count, second.layer_count);
+        for (first_layer, second_layer) in first.layers.iter().zip(&second.layers) {
+            assert_eq!(first_layer.activation, second_layer.activation);
+            assert_eq!(first_layer.learning_rate, second_layer.learning_rate);
+            for (first_node, second_node) in first_layer.nodes.iter().zip(&second_layer.nodes) {
+                assert_eq!(first_node.bias, second_node.bias);
+                assert_eq!(first_node.weights, second_node.weights);
+                assert_eq!(first_node.active_weights, second_node.active_weights);
+            }
+        }
+        assert_eq!(first.loss, second.loss);
+        assert_eq!(first.label_map, second.label_map);
+        assert_eq!(first.input_scaler, second.input_scaler);
+        assert_eq!(first.target_scaler, second.target_scaler);
+    }
+
+    fn read_edited<F: Fn(&mut Vec<String>)>(name: &str, edit: F) -> Result<(nodes_layers::Network, ModelMetadata), NnError> {
+        //Writes the dense network, changes the lines of the file, and reads it back.
+        let file_path = temp_path(name);
+        write(&dense_network(), &metadata(), &file_path).unwrap();
+        let mut lines: Vec<String> = fs_lines(&file_path);
+        edit(&mut lines);
+        std::fs::write(&file_path, lines.join("\n") + "\n").unwrap();
+        let result = read(&file_path);
+        std::fs::remove_file(&file_path).unwrap();
+        result
+    }
+
+    fn fs_lines(file_path: &str) -> Vec<String> {
+        std::fs::read_to_string(file_path).unwrap().lines().map(|line| line.to_string()).collect()
+    }
+
+    #[test]
+    fn round_trips_dense_and_sparse_networks() {
+        for (name, network) in vec![("dense.csv", dense_network()), ("sparse.csv", sparse_network())] {
+            let file_path = temp_path(name);
+            write(&network, &metadata(), &file_path).unwrap();
+            let (read_network, read_metadata) = read(&file_path).unwrap();
+            std::fs::remove_file(&file_path).unwrap();
+            assert_same_network(&network, &read_network);
+            assert_eq!(read_metadata, metadata());
+        }
+    }
+
+    #[test]
+    fn newer_version_is_rejected() {
+        let result = read_edited("newer.csv", |lines| lines[0] = format!("{},{}", FORMAT_NAME, FORMAT_VERSION + 1));
+        match result {
+            Err(NnError::Format(details)) => assert!(details.contains("version"), "{}", details),
+            other => panic!("Expected a version error, found {:?}", other.map(|_| ())),
+        }
+        assert!(read_edited("current.csv", |lines| lines[0] = format!("{},{}", FORMAT_NAME, FORMAT_VERSION)).is_ok());
+    }
+
+    #[test]
+    fn missing_or_extra_layer_lines_are_rejected() {
+        let layer_line = |lines: &Vec<String>, num: usize| lines.iter().enumerate().filter(|(_, line)| line.starts_with("layer,")).nth(num).unwrap().0;
+        //Without the header line of the first layer, the second layer's inputs don't match anything.
+        assert!(read_edited("missing_layer.csv", |lines| {lines.remove(layer_line(lines, 0));}).is_err());
+        assert!(read_edited("extra_layer.csv", |lines| {
+            let pos = layer_line(lines, 1);
+            let line = lines[pos].clone();
+            lines.insert(pos, line);
+        }).is_err());
+        //The last line is the last node of the output layer.
+        assert!(read_edited("missing_node.csv", |lines| {lines.pop();}).is_err());
+        assert!(read_edited("extra_node.csv", |lines| {
+            let line = lines[lines.len() - 1].clone();
+            lines.push(line);
+        }).is_err());
+    }
+}
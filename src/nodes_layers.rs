@@ -2,12 +2,77 @@ extern crate rand;
 
 use rand::Rng;
 
+use crate::dataset;
+use crate::error::NnError;
+use crate::labels;
+use crate::scaling;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Activation {
+    //The function a layer applies to the weighted sums of its nodes.
+    Sigmoid, //Squeezes the value into (0; 1). Used everywhere unless something else is asked for.
+    Linear, //Leaves the value as it is, for outputs that can be any number.
+}
+
+impl Activation {
+    pub fn apply(&self, value: f32) -> f32 {
+        match self {
+            Activation::Sigmoid => 1.0 / (1.0 + (-value).exp()),
+            Activation::Linear => value,
+        }
+    }
+
+    pub fn derivative(&self, activated_value: f32) -> f32 {
+        //The derivative of the function, found from the value it returned rather than the value it was given.
+        match self {
+            Activation::Sigmoid => activated_value * (1.0 - activated_value),
+            Activation::Linear => 1.0,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Loss {
+    //How the difference between an output and its desired value is punished during training.
+    SquaredError, //Half the squared difference. This is what the network has always been trained with.
+    AbsoluteError, //The absolute difference, which is less thrown off by outliers.
+    Huber(f32), //Squared error for differences below the given size, and absolute error above it.
+}
+
+impl Loss {
+    pub fn value(&self, output: f32, desired: f32) -> f32 {
+        let difference = output - desired;
+        match *self {
+            Loss::SquaredError => 0.5 * difference.powi(2),
+            Loss::AbsoluteError => difference.abs(),
+            Loss::Huber(limit) => {
+                if difference.abs() <= limit {
+                    0.5 * difference.powi(2)
+                } else {
+                    limit * (difference.abs() - 0.5 * limit)
+                }
+            },
+        }
+    }
+
+    pub fn derivative(&self, output: f32, desired: f32) -> f32 {
+        //How much the loss changes with the output.
+        let difference = output - desired;
+        match *self {
+            Loss::SquaredError => difference,
+            Loss::AbsoluteError => if difference > 0.0 {1.0} else if difference < 0.0 {-1.0} else {0.0},
+            Loss::Huber(limit) => difference.max(-limit).min(limit),
+        }
+    }
+}
+
 #[derive(Clone)]
 pub struct Node {
     // A node/neuron's bias and the weights of its connections to the previous layer.
     pub bias: f32, //A value to adjust the value by independent of inputs.
     pub weights: Vec<f32>, //For each node in the previous layer, one of these weights correspond to it and adjusts the amount that this nodes value is affected by that node.
     pub personal_pos: usize, //The position within its own layer. Only used in find_delta, but find_delta is used a lot.
+    pub active_weights: Option<Vec<usize>>, //The positions of the weights that haven't been pruned. None means the node is dense and every weight is used.
 }
 
 impl Node {
@@ -28,44 +93,89 @@ impl Node {
             bias: init_bias,
             weights: init_weights, // vec![0.0; number_of_weights],
             personal_pos: personal_pos,
+            active_weights: None,
             // bias_adjust: None,
             // weight_adjusts: Vec::new()
         }
     }
 
-    pub fn calculate(&self, previous_layer_values:&Vec<f32>) -> f32 {
+    pub fn calculate(&self, previous_layer_values:&Vec<f32>, activation: Activation) -> Result<f32, NnError> {
         //Calculates the value of the node based on the values of the previous layer and the node's bias and weights.
+        //The node doesn't know which layer it's in, so it's given the activation of that layer.
+        let value = self.weighted_sum(previous_layer_values)?;
+        Ok(activation.apply(value))
+    }
+
+    pub fn weighted_sum(&self, previous_layer_values:&Vec<f32>) -> Result<f32, NnError> {
+        //The bias plus the values of the previous layer multiplied by their weights, before the activation is applied.
+        //The previous layer must have a value for each weight.
         let mut value = self.bias;
         let previous_layer_len = previous_layer_values.len();
         if self.weights.len() != previous_layer_len {
-            panic!("The number of weights ({}) doesn't match the number of values ({})", self.weights.len(), previous_layer_len);
+            return Err(NnError::shape_mismatch("the values going into a node", self.weights.len(), previous_layer_len))
         }
-        for pos_num in 0..previous_layer_len {
-            value += previous_layer_values[pos_num] * self.weights[pos_num];
+        match &self.active_weights {
+            Some(active_weights) => {
+                //Pruned weights are always 0.0, so they can be skipped entirely.
+                for &pos_num in active_weights {
+                    value += previous_layer_values[pos_num] * self.weights[pos_num];
+                }
+            },
+            None => {
+                for pos_num in 0..previous_layer_len {
+                    value += previous_layer_values[pos_num] * self.weights[pos_num];
+                }
+            },
         }
-        let norm_value = 1.0 / (1.0 + (-value).exp());
-        // if norm_value < 0.0 || norm_value > 1.0 {
-        //     panic!{"Math is broken, the sigmoid functions returns value outside [0; 1]"}
-        // }
-        norm_value
+        Ok(value)
     }
 
-    pub fn find_delta(&self, personal_value:f32, next_layer: &Layer, d_values: &Vec<f32>) -> f32 {
-        //Finds delta and returns it.
+    pub(crate) fn find_delta(&self, personal_value:f32, next_layer: &Layer, d_values: &Vec<f32>, activation: Activation) -> f32 {
+        //Finds delta and returns it. Only used through Network::backpropagate, which checks the sizes of everything first.
         //d_values stands either for desired values, a vector of the desired output values,
         //or for delta values, a vector of the deltas of the next_layer.
+        //activation is the activation of the node's own layer.
         let mut delta = 0.0;
         if next_layer.node_count == 0 {
-            delta = (personal_value - d_values[self.personal_pos]) * personal_value * (1.0 - personal_value)
+            delta = (personal_value - d_values[self.personal_pos]) * activation.derivative(personal_value)
         } else {
             for num in 0..next_layer.node_count {
                 delta += d_values[num] * next_layer.nodes[num].weights[self.personal_pos];
             }
-            delta *= personal_value * (1.0 - personal_value);
+            delta *= activation.derivative(personal_value);
         }
         delta
     }
 
+    pub fn prune(&mut self, weight_positions: &[usize]) -> Result<(), NnError> {
+        //Removes weights from the node by setting them to 0.0 and leaving them out of active_weights.
+        //A dense node is turned sparse the first time one of its weights is pruned.
+        //Every position is checked before anything is pruned, so a bad one leaves the node as it was.
+        if weight_positions.is_empty() {
+            return Ok(())
+        }
+        let weight_count = self.weights.len();
+        if let Some(&pos) = weight_positions.iter().find(|&&pos| pos >= weight_count) {
+            return Err(NnError::Config(format!("There's no weight {:?} to prune in a node with {:?} weights", pos, weight_count)))
+        }
+        let mut is_pruned = vec![false; weight_count];
+        for &pos in weight_positions {
+            is_pruned[pos] = true;
+            self.weights[pos] = 0.0;
+        }
+        let active_weights = self.active_weights.get_or_insert_with(|| (0..weight_count).collect());
+        active_weights.retain(|&pos| pos < weight_count && !is_pruned[pos]);
+        Ok(())
+    }
+
+    pub fn active_weight_count(&self) -> usize {
+        //The number of weights that haven't been pruned.
+        match &self.active_weights {
+            Some(active_weights) => active_weights.len(),
+            None => self.weights.len(),
+        }
+    }
+
     // pub fn single_adjust(&mut self, delta: f32, learning_rate: f32, previous_layer_value: f32, relevant_weight: usize) {
     //     if relevant_weight == self.weights.len() {
     //         self.bias -= delta * learning_rate;
@@ -79,14 +189,25 @@ impl IntoIterator for Node {
     //This trait is implemented so that the nodes can easily be written to file.
     //The bias and weights are turned into strings so that the file writer will accept them.
     //The bias and weights are converted from f32 to u32 before they're converted into strings, so that they're easier to turn back later.
+    //Pruned weights are written as empty fields, which is how inout::read_network knows to load the node as sparse.
     type Item = String;
     type IntoIter = ::std::vec::IntoIter<Self::Item>;
 
     fn into_iter(self) -> Self::IntoIter {
         let mut output = Vec::new();
         output.push(self.bias.to_bits().to_string());
-        for weight in self.weights {
-            output.push(weight.to_bits().to_string())
+        let mut is_active = vec![self.active_weights.is_none(); self.weights.len()];
+        if let Some(active_weights) = &self.active_weights {
+            for &pos in active_weights {
+                is_active[pos] = true;
+            }
+        }
+        for (pos, weight) in self.weights.into_iter().enumerate() {
+            if is_active[pos] {
+                output.push(weight.to_bits().to_string())
+            } else {
+                output.push(String::new())
+            }
         }
         output.into_iter()
     }
@@ -98,6 +219,7 @@ pub struct Layer {
     pub nodes: Vec<Node>, //A Vec of all the nodes in the layer.
     pub node_count: usize, //Should be equal to nodes.len() and shouldn't change.
     pub learning_rate: f32, //Reduces the amount it learns from each training example so that it doesn't fluctuate as wildly, and goes down a bit more steadily.
+    pub activation: Activation, //Applied to the weighted sum of every node in the layer.
 }
 
 impl Layer {
@@ -112,19 +234,53 @@ impl Layer {
             nodes: nodes,
             node_count: node_count,
             learning_rate: learning_rate,
+            activation: Activation::Sigmoid,
         }
     }
 
-    pub fn calculate(&self, previous_layer_values:&Vec<f32>) -> Vec<f32> {
+    pub fn calculate(&self, previous_layer_values:&Vec<f32>) -> Result<Vec<f32>, NnError> {
         //Calculates the values of the nodes based on the values of the previous layer and the nodes' weights and biases.
         let mut values = Vec::new();
-        for node_num in 0..self.node_count {
-            values.push(self.nodes[node_num].calculate(previous_layer_values));
+        for node in &self.nodes {
+            values.push(node.calculate(previous_layer_values, self.activation)?);
         }
-        values
+        Ok(values)
     }
 
-    pub fn find_deltas(&self, values:&Vec<f32>, desired_values:&Vec<f32>, next_layer:&Layer, next_layer_deltas:&Vec<f32>) -> Vec<f32> {
+    pub fn weighted_sums(&self, previous_layer_values:&Vec<f32>) -> Result<Vec<f32>, NnError> {
+        //Calculates the values of the nodes without applying the activation function.
+        let mut values = Vec::new();
+        for node in &self.nodes {
+            values.push(node.weighted_sum(previous_layer_values)?);
+        }
+        Ok(values)
+    }
+
+    pub fn propagate_back(&self, deltas: &Vec<f32>) -> Result<Vec<f32>, NnError> {
+        //Finds how much the loss changes with each value of the previous layer, given the deltas of this layer.
+        //Multiplying the result by the derivative of the previous layer's activation gives that layer's deltas.
+        self.check_deltas(deltas)?;
+        let previous_layer_len = self.input_count()?;
+        let mut gradient = vec![0.0; previous_layer_len];
+        for num in 0..self.node_count {
+            let node = &self.nodes[num];
+            match &node.active_weights {
+                Some(active_weights) => {
+                    for &pos in active_weights {
+                        gradient[pos] += deltas[num] * node.weights[pos];
+                    }
+                },
+                None => {
+                    for pos in 0..previous_layer_len {
+                        gradient[pos] += deltas[num] * node.weights[pos];
+                    }
+                },
+            }
+        }
+        Ok(gradient)
+    }
+
+    pub(crate) fn find_deltas(&self, values:&Vec<f32>, desired_values:&Vec<f32>, next_layer:&Layer, next_layer_deltas:&Vec<f32>) -> Vec<f32> {
         //Finds out how the nodes' weights and biases should be adjusted, based either on a list of desired values, or how the next layer is set to be adjusted.
         //The function does this by calling find_adjusts for each node.
         let d_values = if next_layer.node_count > 0 {
@@ -138,6 +294,7 @@ impl Layer {
                 values[node_num],
                 next_layer,
                 d_values,
+                self.activation,
                 // else {&vec![desired_values[node_num]]}
                 )
             );
@@ -145,17 +302,62 @@ impl Layer {
         deltas
     }
 
-    pub fn adjust(&mut self, deltas: &Vec<f32>, previous_layer_values: &Vec<f32>) {
+    pub fn adjust(&mut self, deltas: &Vec<f32>, previous_layer_values: &Vec<f32>) -> Result<(), NnError> {
         // Adjusts the weights and biases of the nodes based on the deltas and the values of the previous layer.
+        // Pruned weights are skipped, so they stay at 0.0.
+        self.check_deltas(deltas)?;
         let previous_layer_len = previous_layer_values.len();
-        for prev_layer_num in 0..previous_layer_len {
-            for num in 0..self.node_count {
-                self.nodes[num].weights[prev_layer_num] -= deltas[num] * previous_layer_values[prev_layer_num] * self.learning_rate;
-            }
+        if previous_layer_len != self.input_count()? {
+            return Err(NnError::shape_mismatch("the values going into a node", self.input_count()?, previous_layer_len))
         }
         for num in 0..self.node_count {
-            self.nodes[num].bias -= deltas[num] * self.learning_rate;
+            let node = &mut self.nodes[num];
+            match &node.active_weights {
+                Some(active_weights) => {
+                    for &prev_layer_num in active_weights {
+                        node.weights[prev_layer_num] -= deltas[num] * previous_layer_values[prev_layer_num] * self.learning_rate;
+                    }
+                },
+                None => {
+                    for prev_layer_num in 0..previous_layer_len {
+                        node.weights[prev_layer_num] -= deltas[num] * previous_layer_values[prev_layer_num] * self.learning_rate;
+                    }
+                },
+            }
+            node.bias -= deltas[num] * self.learning_rate;
+        }
+        Ok(())
+    }
+
+    fn check_deltas(&self, deltas: &Vec<f32>) -> Result<(), NnError> {
+        if deltas.len() != self.node_count || self.nodes.len() != self.node_count {
+            return Err(NnError::shape_mismatch("the deltas of a layer", self.nodes.len(), deltas.len()))
         }
+        Ok(())
+    }
+
+    fn input_count(&self) -> Result<usize, NnError> {
+        //The number of weights every node has, which must be the same for all of them.
+        let input_count = self.nodes.first().map_or(0, |node| node.weights.len());
+        match self.nodes.iter().find(|node| node.weights.len() != input_count) {
+            Some(node) => Err(NnError::shape_mismatch("the weights of a node", input_count, node.weights.len())),
+            None => Ok(input_count),
+        }
+    }
+
+    pub fn is_sparse(&self) -> bool {
+        //Whether any of the nodes in the layer have had weights pruned.
+        self.nodes.iter().any(|node| node.active_weights.is_some())
+    }
+
+    pub fn weight_count(&self) -> usize {
+        //The total number of weights in the layer, pruned or not.
+        self.nodes.iter().map(|node| node.weights.len()).sum()
+    }
+
+    pub fn active_weight_count(&self) -> usize {
+        //The number of weights in the layer that haven't been pruned.
+        self.nodes.iter().map(|node| node.active_weight_count()).sum()
     }
 }
 
@@ -165,6 +367,10 @@ pub struct Network {
     // The represented layers are the hidden layers and the output layer, since the weights of the connections are stored in the latter of the connected layers, and the input layer doesn't need biases anyway.
     pub layers: Vec<Layer>, //The layers organized in a Vec
     pub layer_count: usize, //Should be equal to layers.len() and shouldn't change.
+    pub label_map: labels::LabelMap, //Which label each position of the output layer stands for. Has as many classes as the output layer has nodes.
+    pub loss: Loss, //What find_make_adjust trains the outputs towards the desired outputs with.
+    pub input_scaler: Option<scaling::Scaler>, //If there is one, inputs are scaled with it before they go into the first layer.
+    pub target_scaler: Option<scaling::Scaler>, //If there is one, the output layer works with scaled targets, and predict scales its outputs back.
     // pub learning_rate: f32, //Reduces the amount it learns from each training example so that it doesn't fluctuate as wildly, and goes down a bit more steadily. Moved to be in each layer instead.
 }
 
@@ -178,52 +384,283 @@ impl Network {
         for layer_num in 1..node_nums.len() {
             layers.push(Layer::new(node_nums[layer_num-1], node_nums[layer_num], learning_rate));
         }
+        Network::from_layers(layers)
+    }
+
+    pub fn from_layers(layers: Vec<Layer>) -> Network {
+        //Puts together a network from layers that already exist, for example ones loaded from files or taken from another network.
+        //The label map starts out numbering the outputs, and can be replaced with with_label_map.
         let layer_count = layers.len();
+        let class_count = layers.last().map_or(0, |layer| layer.node_count);
         Network {
             layers: layers,
             layer_count: layer_count,
+            label_map: labels::LabelMap::numbered(class_count),
+            loss: Loss::SquaredError,
+            input_scaler: None,
+            target_scaler: None,
             // learning_rate: learning_rate,
         }
     }
 
-    pub fn calculate(&self, inputs: &Vec<f32>) -> Vec<Vec<f32>> {
+    pub fn with_label_map(mut self, label_map: labels::LabelMap) -> Result<Network, NnError> {
+        //Sets which label each output stands for. The map must have a class for each node in the output layer.
+        if label_map.class_count() != self.output_count() {
+            return Err(NnError::shape_mismatch("the label map", self.output_count(), label_map.class_count()))
+        }
+        self.label_map = label_map;
+        Ok(self)
+    }
+
+    pub fn input_count(&self) -> usize {
+        //The number of inputs the network takes, like the number of pixels in the images it's made for.
+        //That's the number before input_scaler, which can change it.
+        match &self.input_scaler {
+            Some(input_scaler) => input_scaler.offsets.len(),
+            None => self.layers.first().and_then(|layer| layer.nodes.first()).map_or(0, |node| node.weights.len()),
+        }
+    }
+
+    pub fn output_count(&self) -> usize {
+        //The number of nodes in the output layer.
+        self.layers.last().map_or(0, |layer| layer.node_count)
+    }
+
+    pub fn split_at(&self, layer_num: usize) -> Result<(Network, Network), NnError> {
+        //Splits a copy of the network in two, so that the first network's output layer is layers[layer_num]
+        //and the second network takes the values of that layer as its inputs. Both networks must get at least one layer.
+        if layer_num + 1 >= self.layers.len() {
+            return Err(NnError::Config(format!("Splitting after layer {:?} leaves nothing for the second network in a network of {:?} layers", layer_num, self.layers.len())))
+        }
+        let first = Network::from_layers(self.layers[..=layer_num].to_vec());
+        let second = Network::from_layers(self.layers[layer_num+1..].to_vec());
+        Ok((first, second))
+    }
+
+    pub fn calculate(&self, inputs: &Vec<f32>) -> Result<Vec<Vec<f32>>, NnError> {
         //Calculates the values of all nodes based on the active training data and the weights and biases.
         //The outer vector of the output is the layer, the inner vector is the position in the layer.
         //To get the output layer from values, say "values[values.len() - 1]" or "values[self.layer_count - 1]" if working inside the network struct.
-        let mut values = vec![self.layers[0].calculate(inputs)];
+        //The inputs are scaled with input_scaler first, but the output layer isn't scaled back. Use predict for that.
+        self.feed_forward(&self.scale_inputs(inputs)?)
+    }
+
+    pub fn scale_inputs(&self, inputs: &Vec<f32>) -> Result<Vec<f32>, NnError> {
+        //The inputs as the first layer sees them. This is what adjust expects as inputs.
+        match &self.input_scaler {
+            Some(input_scaler) => input_scaler.transform(inputs),
+            None => Ok(inputs.clone()),
+        }
+    }
+
+    pub fn feed_forward(&self, inputs: &Vec<f32>) -> Result<Vec<Vec<f32>>, NnError> {
+        //Works like calculate, but for inputs that have already been scaled.
+        self.check_layers()?;
+        let mut values = vec![self.layers[0].calculate(inputs)?];
         for num in 1..self.layer_count {
-            values.push(self.layers[num].calculate(&values[num-1]));
+            let layer_values = self.layers[num].calculate(&values[num-1])?;
+            values.push(layer_values);
+        }
+        Ok(values)
+    }
+
+    fn scale_desired_outputs(&self, desired_outputs: &Vec<f32>) -> Result<Vec<f32>, NnError> {
+        //The desired outputs as the output layer is trained towards them.
+        if desired_outputs.len() != self.output_count() {
+            return Err(NnError::shape_mismatch("the desired outputs", self.output_count(), desired_outputs.len()))
+        }
+        match &self.target_scaler {
+            Some(target_scaler) => target_scaler.transform(desired_outputs),
+            None => Ok(desired_outputs.clone()),
         }
-        values
     }
 
-    pub fn find_make_adjust(&mut self, inputs: &Vec<f32>, desired_outputs:&Vec<f32>) {
+    pub fn find_make_adjust(&mut self, inputs: &Vec<f32>, desired_outputs:&Vec<f32>) -> Result<(), NnError> {
     //Finds out the deltas of all the nodes, puts them in delta_matrix, then adjusts the weights and biases of the nodes based on that.
-        let values = self.calculate(inputs);
+        let inputs = self.scale_inputs(inputs)?;
+        let desired_outputs = self.scale_desired_outputs(desired_outputs)?;
+        let values = self.feed_forward(&inputs)?;
+        let output_deltas = self.output_deltas(&values[self.layer_count-1], &desired_outputs)?;
+        let delta_matrix = self.backpropagate(&values, output_deltas)?;
+        self.adjust(&inputs, &values, &delta_matrix)
+    }
+
+    pub fn output_deltas(&self, outputs: &Vec<f32>, desired_outputs: &Vec<f32>) -> Result<Vec<f32>, NnError> {
+        //The deltas of the output layer, found from the loss and the output layer's activation.
+        let activation = self.check_layers()?[self.layer_count-1].activation;
+        if outputs.len() != self.output_count() {
+            return Err(NnError::shape_mismatch("the outputs", self.output_count(), outputs.len()))
+        }
+        if desired_outputs.len() != self.output_count() {
+            return Err(NnError::shape_mismatch("the desired outputs", self.output_count(), desired_outputs.len()))
+        }
+        let mut deltas = Vec::new();
+        for num in 0..outputs.len() {
+            deltas.push(self.loss.derivative(outputs[num], desired_outputs[num]) * activation.derivative(outputs[num]));
+        }
+        Ok(deltas)
+    }
+
+    fn check_layers(&self) -> Result<&[Layer], NnError> {
+        //The layers, if there are any and the counts agree with them.
+        if self.layer_count == 0 {
+            return Err(NnError::Config("The network has no layers".to_string()))
+        }
+        if self.layers.len() != self.layer_count {
+            return Err(NnError::shape_mismatch("the layers of the network", self.layer_count, self.layers.len()))
+        }
+        if let Some(layer) = self.layers.iter().find(|layer| layer.nodes.len() != layer.node_count) {
+            return Err(NnError::shape_mismatch("the nodes of a layer", layer.node_count, layer.nodes.len()))
+        }
+        Ok(&self.layers)
+    }
+
+    fn check_layer_values(&self, what: &str, layer_values: &Vec<Vec<f32>>) -> Result<(), NnError> {
+        //Whether there's a value for every node of every layer, like in the values from calculate or the deltas from backpropagate.
+        self.check_layers()?;
+        if layer_values.len() != self.layer_count {
+            return Err(NnError::shape_mismatch(&format!("the layers of {}", what), self.layer_count, layer_values.len()))
+        }
+        for num in 0..self.layer_count {
+            if layer_values[num].len() != self.layers[num].node_count {
+                return Err(NnError::shape_mismatch(&format!("a layer of {}", what), self.layers[num].node_count, layer_values[num].len()))
+            }
+        }
+        Ok(())
+    }
+
+    pub fn backpropagate(&self, values: &Vec<Vec<f32>>, output_deltas: Vec<f32>) -> Result<Vec<Vec<f32>>, NnError> {
+        //Finds the deltas of every layer, given the values from calculate and the deltas of the output layer.
+        //Taking the output deltas as given lets the network be trained on other losses than the one in find_make_adjust.
+        self.check_layer_values("the values", values)?;
+        if output_deltas.len() != self.output_count() {
+            return Err(NnError::shape_mismatch("the output deltas", self.output_count(), output_deltas.len()))
+        }
+        for num in 1..self.layer_count {
+            let weight_count = self.layers[num].input_count()?;
+            if weight_count != self.layers[num-1].node_count {
+                return Err(NnError::shape_mismatch("the weights of a node", self.layers[num-1].node_count, weight_count))
+            }
+            if let Some(node) = self.layers[num-1].nodes.iter().find(|node| node.personal_pos >= weight_count) {
+                return Err(NnError::Config(format!("A node says it's at position {:?} in a layer of {:?} nodes", node.personal_pos, weight_count)))
+            }
+        }
         let mut delta_matrix = vec![Vec::new(); self.layer_count];
-        delta_matrix[self.layer_count-1] = self.layers[self.layer_count-1].find_deltas(&values[self.layer_count-1], desired_outputs, &Layer::new(0, 0, 0.0), &Vec::new());
+        delta_matrix[self.layer_count-1] = output_deltas;
         for num in (0..self.layer_count-1).rev() {
         //This has to be done in reverse order because the deltas of a hidden layer is based on the deltas of the following layer.
-        //The deltas of the output layer is based on the desired output values and is determined immediately above this for loop.
             delta_matrix[num] = self.layers[num].find_deltas(&values[num], &Vec::new(), &self.layers[num+1], &delta_matrix[num+1]);
         }
+        Ok(delta_matrix)
+    }
 
-        self.layers[0].adjust(&delta_matrix[0], inputs);
+    pub fn adjust(&mut self, inputs: &Vec<f32>, values: &Vec<Vec<f32>>, delta_matrix: &Vec<Vec<f32>>) -> Result<(), NnError> {
+        //Adjusts the weights and biases of every layer based on the deltas from backpropagate.
+        //The inputs must be scaled already, like the ones feed_forward takes.
+        //Everything is checked before anything is adjusted, so a mistake can't leave the network half trained.
+        self.check_layer_values("the values", values)?;
+        self.check_layer_values("the deltas", delta_matrix)?;
+        if inputs.len() != self.layers[0].input_count()? {
+            return Err(NnError::shape_mismatch("the values going into a node", self.layers[0].input_count()?, inputs.len()))
+        }
         for num in 1..self.layer_count {
-            self.layers[num].adjust(&delta_matrix[num], &values[num-1])
+            if self.layers[num].input_count()? != self.layers[num-1].node_count {
+                return Err(NnError::shape_mismatch("the weights of a node", self.layers[num-1].node_count, self.layers[num].input_count()?))
+            }
+        }
+        self.layers[0].adjust(&delta_matrix[0], inputs)?;
+        for num in 1..self.layer_count {
+            self.layers[num].adjust(&delta_matrix[num], &values[num-1])?;
+        }
+        Ok(())
+    }
+
+    pub fn input_gradient(&self, delta_matrix: &Vec<Vec<f32>>) -> Result<Vec<f32>, NnError> {
+        //Finds how much the loss changes with each of the inputs, given the deltas from backpropagate.
+        self.check_layer_values("the deltas", delta_matrix)?;
+        self.layers[0].propagate_back(&delta_matrix[0])
+    }
+
+    pub fn output_gradient(&self, inputs: &Vec<f32>, output_index: usize) -> Result<Vec<f32>, NnError> {
+        //Finds how much a single output changes with each of the inputs, by backpropagating from that output all the way to the inputs.
+        //The gradient is for the inputs before input_scaler and the output after target_scaler, so it matches predict.
+        if output_index >= self.output_count() {
+            return Err(NnError::Config(format!("There's no output {:?} in a network with {:?} outputs", output_index, self.output_count())))
+        }
+        let scaled_inputs = self.scale_inputs(inputs)?;
+        let values = self.feed_forward(&scaled_inputs)?;
+        let outputs = &values[self.layer_count-1];
+        let mut output_deltas = vec![0.0; outputs.len()];
+        output_deltas[output_index] = self.layers[self.layer_count-1].activation.derivative(outputs[output_index]);
+        if let Some(target_scaler) = &self.target_scaler {
+            output_deltas[output_index] *= target_scaler.scales[output_index];
+        }
+        let delta_matrix = self.backpropagate(&values, output_deltas)?;
+        let gradient = self.input_gradient(&delta_matrix)?;
+        Ok(match &self.input_scaler {
+            Some(input_scaler) => input_scaler.gradient_to_inputs(&gradient),
+            None => gradient,
+        })
+    }
+
+    pub fn loss_gradient(&self, inputs: &Vec<f32>, desired_outputs: &Vec<f32>) -> Result<Vec<f32>, NnError> {
+        //Finds how much the training loss for an example changes with each of the inputs, without adjusting anything.
+        //Like output_gradient, the gradient is for the inputs before input_scaler.
+        let scaled_inputs = self.scale_inputs(inputs)?;
+        let desired_outputs = self.scale_desired_outputs(desired_outputs)?;
+        let values = self.feed_forward(&scaled_inputs)?;
+        let output_deltas = self.output_deltas(&values[self.layer_count-1], &desired_outputs)?;
+        let delta_matrix = self.backpropagate(&values, output_deltas)?;
+        let gradient = self.input_gradient(&delta_matrix)?;
+        Ok(match &self.input_scaler {
+            Some(input_scaler) => input_scaler.gradient_to_inputs(&gradient),
+            None => gradient,
+        })
+    }
+
+    pub fn predict(&self, inputs: &Vec<f32>) -> Result<Vec<f32>, NnError> {
+        //Calculates only the values of the output layer, scaled back with target_scaler if there is one.
+        let outputs = &self.calculate(inputs)?[self.layer_count - 1];
+        match &self.target_scaler {
+            Some(target_scaler) => target_scaler.inverse_transform(outputs),
+            None => Ok(outputs.clone()),
+        }
+    }
+
+    pub fn train_epoch(&mut self, dataset: &dyn dataset::Dataset) -> Result<(), NnError> {
+        //Trains on every example of the dataset once, in order.
+        for num in 0..dataset.len() {
+            let (inputs, desired_outputs) = dataset.get(num);
+            self.find_make_adjust(&inputs, &desired_outputs)?;
         }
+        Ok(())
     }
 
-    pub fn compare_success(&self, inputs: &Vec<f32>, desired_outputs:&Vec<f32>, margin_of_error:f32) -> bool {
+    pub fn evaluate(&self, dataset: &dyn dataset::Dataset) -> Result<f32, NnError> {
+        //The fraction of the examples where the biggest output is in the same position as the biggest desired output.
+        let mut successes = 0;
+        for num in 0..dataset.len() {
+            let (inputs, desired_outputs) = dataset.get(num);
+            if same_biggest(&self.predict(&inputs)?, &desired_outputs) {
+                successes += 1;
+            }
+        }
+        Ok(if dataset.is_empty() {0.0} else {successes as f32 / dataset.len() as f32})
+    }
+
+    pub fn compare_success(&self, inputs: &Vec<f32>, desired_outputs:&Vec<f32>, margin_of_error:f32) -> Result<bool, NnError> {
     //Compares whether the all the outputs are correct within a margin of error. Has no effect on the actual training.
-        let output_values = &self.calculate(inputs)[self.layer_count - 1];
+        let output_values = &self.predict(inputs)?;
+        if desired_outputs.len() != output_values.len() {
+            return Err(NnError::shape_mismatch("the desired outputs", output_values.len(), desired_outputs.len()))
+        }
         let mut within_margin = true;
         for num in 0..desired_outputs.len() {
             if (desired_outputs[num] - output_values[num]).powi(2) > margin_of_error.powi(2) {
                 within_margin = false;
             }
         }
-        within_margin
+        Ok(within_margin)
     }
 }
 
@@ -242,3 +679,63 @@ pub fn find_biggest(numbers:&[f32]) -> (f32, Vec<usize>) {
     }
     (biggest_number, biggest_number_positions)
 }
+
+pub fn same_biggest(outputs: &[f32], desired_outputs: &[f32]) -> bool {
+    //Whether the biggest numbers of the two vectors are found in the same positions. This is how a guess is judged to be correct.
+    find_biggest(outputs).1 == find_biggest(desired_outputs).1
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn node_uses_the_activation_it_is_given() {
+        let mut layer = Layer::new(2, 1, 0.1);
+        layer.activation = Activation::Linear;
+        let node = &mut layer.nodes[0];
+        node.bias = 0.5;
+        node.weights = vec![2.0, -1.0];
+        let inputs = vec![3.0, 1.0];
+        assert_eq!(node.calculate(&inputs, Activation::Linear).unwrap(), 5.5);
+        assert_eq!(node.calculate(&inputs, Activation::Sigmoid).unwrap(), Activation::Sigmoid.apply(5.5));
+        assert_eq!(layer.calculate(&inputs).unwrap(), vec![5.5]);
+    }
+
+    #[test]
+    fn split_at_needs_a_layer_on_each_side() {
+        let network = Network::new(&[4, 3, 2, 1], 0.1);
+        let (first, second) = network.split_at(1).unwrap();
+        assert_eq!((first.layer_count, second.layer_count), (2, 1));
+        assert!(network.split_at(2).is_err());
+        assert!(network.split_at(3).is_err());
+    }
+
+    #[test]
+    fn training_steps_check_their_sizes() {
+        let mut network = Network::new(&[3, 2, 2], 0.1);
+        let inputs = vec![0.1, 0.2, 0.3];
+        let values = network.calculate(&inputs).unwrap();
+        assert!(network.output_deltas(&values[1], &vec![1.0]).is_err());
+        assert!(network.backpropagate(&values, vec![0.5]).is_err());
+        assert!(network.backpropagate(&values[..1].to_vec(), vec![0.5, 0.5]).is_err());
+        let delta_matrix = network.backpropagate(&values, vec![0.5, 0.5]).unwrap();
+        assert!(network.adjust(&vec![0.1, 0.2], &values, &delta_matrix).is_err());
+        assert!(network.adjust(&inputs, &values, &vec![vec![0.5], vec![0.5, 0.5]]).is_err());
+        assert!(network.input_gradient(&vec![vec![0.5; 2]]).is_err());
+        assert!(network.layers[0].propagate_back(&vec![0.5; 3]).is_err());
+        assert!(network.layers[1].adjust(&vec![0.5; 2], &vec![0.5; 3]).is_err());
+        assert!(network.adjust(&inputs, &values, &delta_matrix).is_ok());
+        assert_eq!(network.input_gradient(&delta_matrix).unwrap().len(), 3);
+    }
+
+    #[test]
+    fn empty_network_is_an_error() {
+        let network = Network::new(&[3], 0.1);
+        assert_eq!(network.layer_count, 0);
+        assert!(network.calculate(&vec![0.0; 3]).is_err());
+        assert!(network.output_deltas(&Vec::new(), &Vec::new()).is_err());
+        assert!(network.backpropagate(&Vec::new(), Vec::new()).is_err());
+        assert!(network.input_gradient(&Vec::new()).is_err());
+    }
+}
@@ -0,0 +1,264 @@
+use crate::dataset;
+use crate::image_import;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Inversion {
+    //Whether to turn dark digits on a light background into light digits on a dark one, like MNIST's.
+    Never,
+    Always,
+    Detect, //Inverts the image if its border is mostly light, since the border is nearly always background.
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Threshold {
+    //How faint background pixels, like paper texture or scanner noise, are cleared away after any inversion.
+    Nothing,
+    Fixed(f32), //Pixels below the value become 0.0, and the rest are kept as they are.
+    Otsu, //Like Fixed, with the value picked from the image's histogram to best separate the digit from the background.
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Preprocessing {
+    //Makes a photo, scan or drawing of a digit look like the digits in MNIST, which were cropped to the digit,
+    //resized to fit a 20x20 box keeping their aspect ratio, and placed in a 28x28 image with their centre of mass in the middle.
+    pub inversion: Inversion,
+    pub threshold: Threshold,
+    pub box_fraction: f32, //The size of the box the digit is fitted into, as a share of the output's size. 20/28 for MNIST.
+    pub deskew: bool, //Whether to straighten slanted digits before they're resized.
+}
+
+impl Preprocessing {
+    pub fn mnist() -> Preprocessing {
+        //The steps used for MNIST, with inversion detected and the threshold found from each image. Change it with the with_ methods.
+        Preprocessing {
+            inversion: Inversion::Detect,
+            threshold: Threshold::Otsu,
+            box_fraction: 20.0 / 28.0,
+            deskew: true,
+        }
+    }
+
+    pub fn with_inversion(mut self, inversion: Inversion) -> Preprocessing {
+        self.inversion = inversion;
+        self
+    }
+
+    pub fn with_threshold(mut self, threshold: Threshold) -> Preprocessing {
+        self.threshold = threshold;
+        self
+    }
+
+    pub fn with_box_fraction(mut self, box_fraction: f32) -> Preprocessing {
+        self.box_fraction = box_fraction;
+        self
+    }
+
+    pub fn with_deskew(mut self, deskew: bool) -> Preprocessing {
+        self.deskew = deskew;
+        self
+    }
+
+    pub fn apply(&self, image: &image_import::Image, shape: dataset::Shape) -> Vec<f32> {
+        //Turns the image into an input of the given shape, ready for Network::calculate.
+        //The digit is worked on in grey, and copied into each channel if the shape has more than one.
+        let grey = image.to_grey();
+        let (width, height) = (grey.width, grey.height);
+        let mut pixels = grey.pixels;
+        let is_inverted = match self.inversion {
+            Inversion::Never => false,
+            Inversion::Always => true,
+            Inversion::Detect => border_mean(&pixels, width, height) > 0.5,
+        };
+        if is_inverted {
+            for value in pixels.iter_mut() {
+                *value = 1.0 - *value;
+            }
+        }
+        let cutoff = match self.threshold {
+            Threshold::Nothing => 0.0,
+            Threshold::Fixed(cutoff) => cutoff,
+            Threshold::Otsu => otsu_threshold(&pixels),
+        };
+        for value in pixels.iter_mut() {
+            if *value < cutoff {
+                *value = 0.0;
+            }
+        }
+        let plane = match bounding_box(&pixels, width, height) {
+            Some((left, top, right, bottom)) => {
+                let mut digit = crop(&pixels, width, left, top, right, bottom);
+                if self.deskew {
+                    digit = deskew(&digit);
+                }
+                let (left, top, right, bottom) = bounding_box(&digit.pixels, digit.width, digit.height).unwrap_or((0, 0, digit.width - 1, digit.height - 1));
+                let digit = crop(&digit.pixels, digit.width, left, top, right, bottom);
+                place(&digit, shape.width, shape.height, self.box_fraction)
+            },
+            None => vec![0.0; shape.width * shape.height],
+        };
+        let mut input = Vec::new();
+        for _ in 0..shape.channels {
+            input.extend_from_slice(&plane);
+        }
+        input
+    }
+}
+
+fn border_mean(pixels: &[f32], width: usize, height: usize) -> f32 {
+    let mut total = 0.0;
+    let mut count = 0;
+    for y in 0..height {
+        for x in 0..width {
+            if x == 0 || y == 0 || x == width - 1 || y == height - 1 {
+                total += pixels[y * width + x];
+                count += 1;
+            }
+        }
+    }
+    if count == 0 {0.0} else {total / count as f32}
+}
+
+fn otsu_threshold(pixels: &[f32]) -> f32 {
+    //Picks the threshold that gives the biggest variance between the pixels below it and the pixels above it, over 256 levels.
+    let mut histogram = [0usize; 256];
+    for value in pixels {
+        histogram[(value.max(0.0).min(1.0) * 255.0).round() as usize] += 1;
+    }
+    let total: usize = histogram.iter().sum();
+    let total_sum: f32 = (0..256).map(|level| level as f32 * histogram[level] as f32).sum();
+    let mut below_count = 0;
+    let mut below_sum = 0.0;
+    let mut best_level = 0;
+    let mut best_variance = -1.0;
+    for level in 0..256 {
+        below_count += histogram[level];
+        below_sum += level as f32 * histogram[level] as f32;
+        if below_count == 0 || below_count == total {
+            continue
+        }
+        let above_count = total - below_count;
+        let below_mean = below_sum / below_count as f32;
+        let above_mean = (total_sum - below_sum) / above_count as f32;
+        let variance = below_count as f32 * above_count as f32 * (below_mean - above_mean).powi(2);
+        if variance > best_variance {
+            best_variance = variance;
+            best_level = level;
+        }
+    }
+    (best_level as f32 + 0.5) / 255.0
+}
+
+fn bounding_box(pixels: &[f32], width: usize, height: usize) -> Option<(usize, usize, usize, usize)> {
+    //The left, top, right and bottom of the pixels that aren't 0.0, including the edges. None if the image is blank.
+    let mut found: Option<(usize, usize, usize, usize)> = None;
+    for y in 0..height {
+        for x in 0..width {
+            if pixels[y * width + x] > 0.0 {
+                found = Some(match found {
+                    Some((left, top, right, bottom)) => (left.min(x), top.min(y), right.max(x), bottom.max(y)),
+                    None => (x, y, x, y),
+                });
+            }
+        }
+    }
+    found
+}
+
+fn crop(pixels: &[f32], width: usize, left: usize, top: usize, right: usize, bottom: usize) -> image_import::Image {
+    let mut cropped = Vec::new();
+    for y in top..=bottom {
+        cropped.extend_from_slice(&pixels[y * width + left..=y * width + right]);
+    }
+    image_import::Image {
+        width: right - left + 1,
+        height: bottom - top + 1,
+        channels: 1,
+        pixels: cropped,
+    }
+}
+
+fn moments(pixels: &[f32], width: usize, height: usize) -> Option<(f32, f32, f32, f32)> {
+    //The centre of mass of the image, and its variance along y and covariance of x and y. None if the image is blank.
+    let mut total = 0.0;
+    let mut x_sum = 0.0;
+    let mut y_sum = 0.0;
+    for y in 0..height {
+        for x in 0..width {
+            let value = pixels[y * width + x];
+            total += value;
+            x_sum += value * x as f32;
+            y_sum += value * y as f32;
+        }
+    }
+    if total <= 0.0 {
+        return None
+    }
+    let (centre_x, centre_y) = (x_sum / total, y_sum / total);
+    let mut y_variance = 0.0;
+    let mut covariance = 0.0;
+    for y in 0..height {
+        for x in 0..width {
+            let value = pixels[y * width + x];
+            y_variance += value * (y as f32 - centre_y).powi(2);
+            covariance += value * (x as f32 - centre_x) * (y as f32 - centre_y);
+        }
+    }
+    Some((centre_x, centre_y, y_variance / total, covariance / total))
+}
+
+fn deskew(digit: &image_import::Image) -> image_import::Image {
+    //Shears the digit sideways so its main axis is upright. The skew is how far x moves on average for each step in y,
+    //and each row is shifted back by it, measured from the centre of mass. The image is widened so nothing is cut off.
+    let (width, height) = (digit.width, digit.height);
+    let (_, centre_y, y_variance, covariance) = match moments(&digit.pixels, width, height) {
+        Some(moments) if moments.2 > 1e-6 => moments,
+        _ => return digit.clone(),
+    };
+    let skew = covariance / y_variance;
+    let margin = (skew.abs() * height as f32).ceil() as usize;
+    let new_width = width + 2 * margin;
+    let mut pixels = vec![0.0; new_width * height];
+    for y in 0..height {
+        for x in 0..new_width {
+            //Where the pixel came from, with x counted from the left of the original image.
+            let source_x = x as f32 - margin as f32 + skew * (y as f32 - centre_y);
+            let left = source_x.floor();
+            let share = source_x - left;
+            let pixel = |px: f32| if px < 0.0 || px >= width as f32 {0.0} else {digit.pixels[y * width + px as usize]};
+            pixels[y * new_width + x] = pixel(left) * (1.0 - share) + pixel(left + 1.0) * share;
+        }
+    }
+    image_import::Image {
+        width: new_width,
+        height: height,
+        channels: 1,
+        pixels: pixels,
+    }
+}
+
+fn place(digit: &image_import::Image, width: usize, height: usize, box_fraction: f32) -> Vec<f32> {
+    //Resizes the digit so its longer side fits the box, keeping its aspect ratio,
+    //then puts it in a blank image so its centre of mass lands in the middle. Anything pushed past the edges is lost.
+    let box_width = ((width as f32 * box_fraction).round() as usize).max(1);
+    let box_height = ((height as f32 * box_fraction).round() as usize).max(1);
+    let scale = (box_width as f32 / digit.width as f32).min(box_height as f32 / digit.height as f32);
+    let new_width = ((digit.width as f32 * scale).round() as usize).max(1).min(box_width);
+    let new_height = ((digit.height as f32 * scale).round() as usize).max(1).min(box_height);
+    let resized = digit.resize(new_width, new_height);
+    let (centre_x, centre_y) = match moments(&resized.pixels, new_width, new_height) {
+        Some((centre_x, centre_y, _, _)) => (centre_x, centre_y),
+        None => return vec![0.0; width * height],
+    };
+    let left = ((width as f32 - 1.0) / 2.0 - centre_x).round() as isize;
+    let top = ((height as f32 - 1.0) / 2.0 - centre_y).round() as isize;
+    let mut output = vec![0.0; width * height];
+    for y in 0..new_height {
+        for x in 0..new_width {
+            let (out_x, out_y) = (x as isize + left, y as isize + top);
+            if out_x >= 0 && out_y >= 0 && out_x < width as isize && out_y < height as isize {
+                output[out_y as usize * width + out_x as usize] = resized.pixels[y * new_width + x];
+            }
+        }
+    }
+    output
+}
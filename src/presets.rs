@@ -0,0 +1,194 @@
+use std::path::Path;
+
+use crate::cifar;
+use crate::dataset;
+use crate::error::NnError;
+use crate::idx;
+use crate::labels;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum EmnistSplit {
+    //The ways EMNIST is divided into classes, each with its own files.
+    ByClass, //62 classes, digits and upper and lower case letters.
+    ByMerge, //47 classes, where the lower case letters that look like their upper case ones are merged into them.
+    Balanced, //The same 47 classes as ByMerge, with as many images of each.
+    Letters, //26 classes, with upper and lower case merged. Labelled from 1 to 26.
+    Digits, //10 classes, like MNIST but with more images.
+    Mnist, //10 classes, the same size as MNIST.
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Preset {
+    //Benchmark datasets, with what's needed to use them. All but CIFAR come in the same IDX layout as MNIST.
+    Mnist,
+    FashionMnist,
+    Kmnist,
+    Emnist(EmnistSplit),
+    Cifar(cifar::CifarKind),
+}
+
+const FASHION_MNIST_NAMES: [&str; 10] = ["T-shirt/top", "Trouser", "Pullover", "Dress", "Coat", "Sandal", "Shirt", "Sneaker", "Bag", "Ankle boot"];
+const KMNIST_NAMES: [&str; 10] = ["o", "ki", "su", "tsu", "na", "ha", "ma", "ya", "re", "wo"];
+const MERGED_LOWER_CASE: [char; 11] = ['a', 'b', 'd', 'e', 'f', 'g', 'h', 'n', 'q', 'r', 't']; //The lower case letters EMNIST keeps apart in the merged splits.
+
+impl Preset {
+    pub fn from_name(name: &str) -> Option<Preset> {
+        match name {
+            "mnist" => Some(Preset::Mnist),
+            "fashion-mnist" => Some(Preset::FashionMnist),
+            "kmnist" => Some(Preset::Kmnist),
+            "emnist-byclass" => Some(Preset::Emnist(EmnistSplit::ByClass)),
+            "emnist-bymerge" => Some(Preset::Emnist(EmnistSplit::ByMerge)),
+            "emnist-balanced" => Some(Preset::Emnist(EmnistSplit::Balanced)),
+            "emnist-letters" => Some(Preset::Emnist(EmnistSplit::Letters)),
+            "emnist-digits" => Some(Preset::Emnist(EmnistSplit::Digits)),
+            "emnist-mnist" => Some(Preset::Emnist(EmnistSplit::Mnist)),
+            "cifar-10" => Some(Preset::Cifar(cifar::CifarKind::Cifar10)),
+            "cifar-100" => Some(Preset::Cifar(cifar::CifarKind::Cifar100Fine)),
+            "cifar-100-coarse" => Some(Preset::Cifar(cifar::CifarKind::Cifar100Coarse)),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Preset::Mnist => "mnist",
+            Preset::FashionMnist => "fashion-mnist",
+            Preset::Kmnist => "kmnist",
+            Preset::Emnist(EmnistSplit::ByClass) => "emnist-byclass",
+            Preset::Emnist(EmnistSplit::ByMerge) => "emnist-bymerge",
+            Preset::Emnist(EmnistSplit::Balanced) => "emnist-balanced",
+            Preset::Emnist(EmnistSplit::Letters) => "emnist-letters",
+            Preset::Emnist(EmnistSplit::Digits) => "emnist-digits",
+            Preset::Emnist(EmnistSplit::Mnist) => "emnist-mnist",
+            Preset::Cifar(cifar::CifarKind::Cifar10) => "cifar-10",
+            Preset::Cifar(cifar::CifarKind::Cifar100Fine) => "cifar-100",
+            Preset::Cifar(cifar::CifarKind::Cifar100Coarse) => "cifar-100-coarse",
+        }
+    }
+
+    pub fn file_names(&self, is_training: bool) -> Vec<String> {
+        //The names of the files, as they're downloaded. For the IDX datasets, that's the images file and then the labels file.
+        if let Preset::Cifar(kind) = self {
+            return kind.file_names(is_training)
+        }
+        let (prefix, part) = match (self, is_training) {
+            (Preset::Emnist(_), true) => (format!("{}-", self.name()), "train"),
+            (Preset::Emnist(_), false) => (format!("{}-", self.name()), "test"),
+            (_, true) => (String::new(), "train"),
+            (_, false) => (String::new(), "t10k"),
+        };
+        vec![format!("{}{}-images-idx3-ubyte.gz", prefix, part), format!("{}{}-labels-idx1-ubyte.gz", prefix, part)]
+    }
+
+    pub fn label_map(&self) -> labels::LabelMap {
+        //The labels used in the files, and the names of what they stand for.
+        let digits: Vec<String> = (0..10).map(|digit: u8| digit.to_string()).collect();
+        let upper_case: Vec<String> = (b'A'..=b'Z').map(|letter| (letter as char).to_string()).collect();
+        let names = match self {
+            Preset::Cifar(kind) => return kind.label_map(),
+            Preset::Mnist | Preset::Emnist(EmnistSplit::Digits) | Preset::Emnist(EmnistSplit::Mnist) => digits,
+            Preset::FashionMnist => FASHION_MNIST_NAMES.iter().map(|name| name.to_string()).collect(),
+            Preset::Kmnist => KMNIST_NAMES.iter().map(|name| name.to_string()).collect(),
+            Preset::Emnist(EmnistSplit::Letters) => upper_case,
+            Preset::Emnist(EmnistSplit::ByClass) => {
+                let lower_case = (b'a'..=b'z').map(|letter| (letter as char).to_string());
+                digits.into_iter().chain(upper_case).chain(lower_case).collect()
+            },
+            Preset::Emnist(EmnistSplit::ByMerge) | Preset::Emnist(EmnistSplit::Balanced) => {
+                let lower_case = MERGED_LOWER_CASE.iter().map(|letter| letter.to_string());
+                digits.into_iter().chain(upper_case).chain(lower_case).collect()
+            },
+        };
+        //EMNIST letters start from 1, everything else from 0.
+        let first_code = if *self == Preset::Emnist(EmnistSplit::Letters) {1} else {0};
+        let keys = (0..names.len()).map(|num| (num + first_code).to_string()).collect();
+        labels::LabelMap {
+            keys: keys,
+            names: names,
+        }
+    }
+
+    pub fn is_transposed(&self) -> bool {
+        //EMNIST images are stored column by column, so they come out mirrored and turned unless they're transposed.
+        match self {
+            Preset::Emnist(_) => true,
+            _ => false,
+        }
+    }
+
+    pub fn read(&self, folder: &Path, is_training: bool) -> Result<(Vec<Vec<f32>>, Vec<u8>, dataset::Shape), NnError> {
+        //Reads the images and labels from the folder, with the images turned the right way, and the shape of the images.
+        //The IDX files can be gzipped as they're downloaded, or unpacked.
+        if let Preset::Cifar(kind) = self {
+            let (inputs, labels) = cifar::read(folder, *kind, is_training)?;
+            return Ok((inputs, labels, cifar::shape()))
+        }
+        let file_names = self.file_names(is_training);
+        let (images, labels) = idx::read_pair(&find_file(folder, &file_names[0]), &find_file(folder, &file_names[1]))?;
+        let mut inputs = images.inputs();
+        let mut shape = images.item_shape();
+        if self.is_transposed() {
+            for num in 0..inputs.len() {
+                inputs[num] = transpose(&inputs[num], shape);
+            }
+            shape = dataset::Shape::image(shape.channels, shape.width, shape.height);
+        }
+        Ok((inputs, labels, shape))
+    }
+
+    pub fn shape(&self, folder: &Path, is_training: bool) -> Result<dataset::Shape, NnError> {
+        //The shape of the images, the same as read gives, found from only the header of the images file.
+        if let Preset::Cifar(_) = self {
+            return Ok(cifar::shape())
+        }
+        let (_, dimensions) = idx::read_header(&find_file(folder, &self.file_names(is_training)[0]))?;
+        if dimensions.len() < 2 {
+            return Err(NnError::Idx(idx::IdxError::TooFewDimensions {minimum: 2, found: dimensions.len()}))
+        }
+        let shape = idx::item_shape(&dimensions[1..]);
+        Ok(if self.is_transposed() {dataset::Shape::image(shape.channels, shape.width, shape.height)} else {shape})
+    }
+
+    pub fn load(&self, folder: &Path, is_training: bool) -> Result<dataset::InMemoryDataset, NnError> {
+        //Reads the images and labels into a dataset with the preset's classes.
+        let (inputs, labels, shape) = self.read(folder, is_training)?;
+        let label_map = self.label_map();
+        for label in &labels {
+            if label_map.index_of(&label.to_string()).is_none() {
+                return Err(NnError::Idx(idx::IdxError::UnknownLabel(*label)))
+            }
+        }
+        let targets = label_map.to_results(&labels)?;
+        dataset::InMemoryDataset::new(inputs, targets, shape, label_map)
+    }
+}
+
+fn find_file(folder: &Path, archive_name: &str) -> String {
+    //Uses an unpacked file if there is one, either with the name gunzip gives it or with a dot before the idx part like MNIST's files often have.
+    //Otherwise the gzipped file is used.
+    let unpacked_name = &archive_name[..archive_name.len() - ".gz".len()];
+    let candidates = [unpacked_name.to_string(), unpacked_name.replacen("-idx", ".idx", 1)];
+    for candidate in &candidates {
+        let path = folder.join(candidate);
+        if path.exists() {
+            return path.to_string_lossy().into_owned()
+        }
+    }
+    folder.join(archive_name).to_string_lossy().into_owned()
+}
+
+pub fn transpose(image: &[f32], shape: dataset::Shape) -> Vec<f32> {
+    //Swaps the rows and columns of each channel of an image, so the result is shape.height wide and shape.width tall.
+    let (width, height) = (shape.width, shape.height);
+    let mut transposed = vec![0.0; image.len()];
+    for channel in 0..shape.channels {
+        let plane = channel * width * height;
+        for y in 0..height {
+            for x in 0..width {
+                transposed[plane + x * height + y] = image[plane + y * width + x];
+            }
+        }
+    }
+    transposed
+}
@@ -0,0 +1,201 @@
+use crate::dataset;
+use crate::error::NnError;
+use crate::nodes_layers;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PruningScope {
+    //Decides which weights compete with each other when picking the smallest ones to prune.
+    Global, //All the weights in the network are ranked together, so layers with many small weights lose more of them.
+    PerLayer, //Each layer is pruned by the same fraction, ranked only against its own weights.
+}
+
+pub fn sparsity(network: &nodes_layers::Network) -> f32 {
+    //The fraction of the network's weights that have been pruned.
+    let mut weight_count = 0;
+    let mut active_weight_count = 0;
+    for layer in &network.layers {
+        weight_count += layer.weight_count();
+        active_weight_count += layer.active_weight_count();
+    }
+    if weight_count == 0 {
+        0.0
+    } else {
+        1.0 - active_weight_count as f32 / weight_count as f32
+    }
+}
+
+pub fn prune_to_sparsity(network: &mut nodes_layers::Network, target_sparsity: f32, scope: PruningScope) -> Result<(), NnError> {
+    //Prunes the weights with the smallest magnitudes until target_sparsity of the weights (within the scope) have been pruned.
+    //Weights that are already pruned count towards the target, so calling it with an increasing target prunes a bit more each time.
+    let target_sparsity = target_sparsity.max(0.0).min(1.0);
+    match scope {
+        PruningScope::Global => {
+            let layer_nums: Vec<usize> = (0..network.layers.len()).collect();
+            prune_smallest(network, &layer_nums, target_sparsity)
+        },
+        PruningScope::PerLayer => {
+            for layer_num in 0..network.layers.len() {
+                prune_smallest(network, &[layer_num], target_sparsity)?;
+            }
+            Ok(())
+        },
+    }
+}
+
+fn prune_smallest(network: &mut nodes_layers::Network, layer_nums: &[usize], target_sparsity: f32) -> Result<(), NnError> {
+    //Ranks the active weights of the given layers by magnitude and prunes the smallest ones.
+    let mut weight_count = 0;
+    let mut candidates = Vec::new(); //(magnitude, layer, node, weight position) for every weight that is still active.
+    for &layer_num in layer_nums {
+        let layer = &network.layers[layer_num];
+        weight_count += layer.weight_count();
+        for (node_num, node) in layer.nodes.iter().enumerate() {
+            match &node.active_weights {
+                Some(active_weights) => {
+                    for &pos in active_weights {
+                        match node.weights.get(pos) {
+                            Some(weight) => candidates.push((weight.abs(), layer_num, node_num, pos)),
+                            None => return Err(NnError::Config(format!("A node has weight {:?} active, but only {:?} weights", pos, node.weights.len()))),
+                        }
+                    }
+                },
+                None => {
+                    for pos in 0..node.weights.len() {
+                        candidates.push((node.weights[pos].abs(), layer_num, node_num, pos));
+                    }
+                },
+            }
+        }
+    }
+    let already_pruned = weight_count - candidates.len();
+    let desired_pruned = (weight_count as f32 * target_sparsity).round() as usize;
+    if desired_pruned <= already_pruned {
+        return Ok(())
+    }
+    let prune_count = (desired_pruned - already_pruned).min(candidates.len());
+    candidates.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(std::cmp::Ordering::Equal));
+    candidates.truncate(prune_count);
+    //Group the chosen weights by node so each node only has to rebuild its active_weights once.
+    candidates.sort_by_key(|&(_, layer_num, node_num, pos)| (layer_num, node_num, pos));
+    let mut start = 0;
+    while start < candidates.len() {
+        let (_, layer_num, node_num, _) = candidates[start];
+        let mut end = start;
+        while end < candidates.len() && candidates[end].1 == layer_num && candidates[end].2 == node_num {
+            end += 1;
+        }
+        let positions: Vec<usize> = candidates[start..end].iter().map(|candidate| candidate.3).collect();
+        network.layers[layer_num].nodes[node_num].prune(&positions)?;
+        start = end;
+    }
+    Ok(())
+}
+
+pub fn iterative_prune(network: &mut nodes_layers::Network, dataset: &dyn dataset::Dataset, target_sparsity: f32, rounds: usize, scope: PruningScope, fine_tune_samples: usize) -> Result<Vec<f32>, NnError> {
+    //Iterative magnitude pruning: the sparsity is raised towards target_sparsity over a number of rounds,
+    //and after each round the network is fine-tuned on fine_tune_samples training examples so the remaining weights can make up for the pruned ones.
+    //The fine-tuning continues through the dataset from where the previous round left off, wrapping around at the end.
+    //Returns the sparsity the network had after each round.
+    let rounds = rounds.max(1);
+    let mut pos_counter = 0;
+    let mut sparsities = Vec::new();
+    for round in 1..=rounds {
+        let round_sparsity = target_sparsity * round as f32 / rounds as f32;
+        prune_to_sparsity(network, round_sparsity, scope)?;
+        if !dataset.is_empty() {
+            for _ in 0..fine_tune_samples {
+                let (inputs, desired_outputs) = dataset.get(pos_counter);
+                network.find_make_adjust(&inputs, &desired_outputs)?;
+                pos_counter = (pos_counter + 1) % dataset.len();
+            }
+        }
+        sparsities.push(sparsity(network));
+    }
+    Ok(sparsities)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::inout;
+
+    fn pruned_network() -> nodes_layers::Network {
+        let mut network = nodes_layers::Network::new(&[4, 3, 2], 0.5);
+        network.layers[0].nodes[0].prune(&[1, 3]).unwrap();
+        network.layers[0].nodes[2].prune(&[0]).unwrap();
+        network.layers[1].nodes[1].prune(&[2]).unwrap();
+        network
+    }
+
+    fn pruned_positions() -> Vec<(usize, usize, usize)> {
+        vec![(0, 0, 1), (0, 0, 3), (0, 2, 0), (1, 1, 2)]
+    }
+
+    #[test]
+    fn pruned_weights_are_skipped_when_calculating() {
+        let mut network = pruned_network();
+        let inputs = vec![0.3, 0.6, 0.9, 0.2];
+        let before = network.calculate(&inputs).unwrap();
+        //A pruned weight that has somehow been given a value still isn't used.
+        network.layers[0].nodes[0].weights[1] = 100.0;
+        assert_eq!(network.calculate(&inputs).unwrap(), before);
+    }
+
+    #[test]
+    fn pruned_weights_stay_zero_through_training() {
+        let mut network = pruned_network();
+        for _ in 0..50 {
+            network.find_make_adjust(&vec![0.3, 0.6, 0.9, 0.2], &vec![1.0, 0.0]).unwrap();
+            network.find_make_adjust(&vec![0.8, 0.1, 0.4, 0.7], &vec![0.0, 1.0]).unwrap();
+        }
+        for (layer_num, node_num, pos) in pruned_positions() {
+            let node = &network.layers[layer_num].nodes[node_num];
+            assert_eq!(node.weights[pos], 0.0);
+            assert!(!node.active_weights.as_ref().unwrap().contains(&pos));
+        }
+        assert!(network.layers[0].nodes[1].active_weights.is_none());
+        assert!((sparsity(&network) - 4.0 / 18.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn bad_positions_leave_the_node_alone() {
+        let mut network = pruned_network();
+        let node = &mut network.layers[0].nodes[1];
+        let weights = node.weights.clone();
+        assert!(node.prune(&[0, 4]).is_err());
+        assert_eq!(node.weights, weights);
+        assert!(node.active_weights.is_none());
+    }
+
+    #[test]
+    fn pruned_network_saves_and_loads_as_sparse() {
+        let network = pruned_network();
+        let directory = std::env::temp_dir().join(format!("numbers_nn_{}_pruning", std::process::id()));
+        std::fs::create_dir_all(&directory).unwrap();
+        let first_path = directory.join("network1.csv").to_string_lossy().into_owned();
+        let second_path = directory.join("network2.csv").to_string_lossy().into_owned();
+        inout::write_network(network.clone(), vec![&first_path, &second_path]).unwrap();
+        let (read_network, replaced_layers) = inout::read_network(vec![&first_path, &second_path], &[4, 3, 2], 0.5, false).unwrap();
+        std::fs::remove_dir_all(&directory).unwrap();
+        assert!(replaced_layers.is_empty());
+        for layer_num in 0..2 {
+            for node_num in 0..network.layers[layer_num].node_count {
+                let node = &network.layers[layer_num].nodes[node_num];
+                let read_node = &read_network.layers[layer_num].nodes[node_num];
+                assert_eq!(node.weights, read_node.weights);
+                assert_eq!(node.active_weights, read_node.active_weights);
+            }
+        }
+        assert_eq!(sparsity(&read_network), sparsity(&network));
+    }
+
+    #[test]
+    fn prune_to_sparsity_prunes_the_smallest_weights() {
+        let mut network = nodes_layers::Network::new(&[4, 2], 0.1);
+        network.layers[0].nodes[0].weights = vec![0.1, -0.9, 0.05, 0.5];
+        network.layers[0].nodes[1].weights = vec![-0.2, 0.3, 0.8, -0.01];
+        prune_to_sparsity(&mut network, 0.5, PruningScope::Global).unwrap();
+        assert_eq!(network.layers[0].nodes[0].active_weights, Some(vec![1, 3]));
+        assert_eq!(network.layers[0].nodes[1].active_weights, Some(vec![1, 2]));
+    }
+}
@@ -0,0 +1,214 @@
+use std::fmt;
+
+use crate::dataset;
+use crate::error::NnError;
+use crate::nodes_layers;
+use crate::scaling;
+
+#[derive(Clone)]
+pub struct QuantizedNode {
+    //A node whose weights have been rounded to int8. The real weight is the int8 value multiplied by the layer's weight_scale.
+    pub bias: f32, //Kept as f32 since it's only added once per node, after the integer dot product.
+    pub weights: Vec<i8>,
+    pub active_weights: Option<Vec<usize>>, //Carried over from the f32 node, so pruned weights are still skipped.
+}
+
+#[derive(Clone)]
+pub struct QuantizedLayer {
+    pub nodes: Vec<QuantizedNode>,
+    pub node_count: usize,
+    pub weight_scale: f32, //The size of one int8 step for the weights of this layer.
+    pub input_scale: f32, //The size of one int8 step for the values of the previous layer, found during calibration.
+    pub activation: nodes_layers::Activation,
+}
+
+impl QuantizedLayer {
+    pub fn from_layer(layer: &nodes_layers::Layer, max_input: f32) -> QuantizedLayer {
+        //Quantizes a layer with a single scale for all its weights, chosen so the largest weight becomes ±127.
+        //max_input is the largest absolute value the previous layer was seen to produce during calibration.
+        let mut max_weight: f32 = 0.0;
+        for node in &layer.nodes {
+            for weight in &node.weights {
+                max_weight = max_weight.max(weight.abs());
+            }
+        }
+        let weight_scale = if max_weight > 0.0 {max_weight / 127.0} else {1.0};
+        let input_scale = if max_input > 0.0 {max_input / 127.0} else {1.0};
+        let mut nodes = Vec::new();
+        for node in &layer.nodes {
+            nodes.push(QuantizedNode {
+                bias: node.bias,
+                weights: node.weights.iter().map(|weight| quantize_value(*weight, weight_scale)).collect(),
+                active_weights: node.active_weights.clone(),
+            });
+        }
+        QuantizedLayer {
+            node_count: nodes.len(),
+            nodes: nodes,
+            weight_scale: weight_scale,
+            input_scale: input_scale,
+            activation: layer.activation,
+        }
+    }
+
+    pub fn calculate(&self, previous_layer_values: &[f32]) -> Result<Vec<f32>, NnError> {
+        //Quantizes the values of the previous layer, then calculates each node with an integer dot product.
+        //The sum is scaled back to f32 before the bias and the activation function are applied.
+        let quantized_values: Vec<i8> = previous_layer_values.iter().map(|value| quantize_value(*value, self.input_scale)).collect();
+        let combined_scale = self.weight_scale * self.input_scale;
+        let mut values = Vec::new();
+        for node in &self.nodes {
+            if node.weights.len() != quantized_values.len() {
+                return Err(NnError::shape_mismatch("the values of the previous layer", node.weights.len(), quantized_values.len()))
+            }
+            let mut sum: i32 = 0;
+            match &node.active_weights {
+                Some(active_weights) => {
+                    for &pos in active_weights {
+                        sum += node.weights[pos] as i32 * quantized_values[pos] as i32;
+                    }
+                },
+                None => {
+                    for pos in 0..quantized_values.len() {
+                        sum += node.weights[pos] as i32 * quantized_values[pos] as i32;
+                    }
+                },
+            }
+            let value = sum as f32 * combined_scale + node.bias;
+            values.push(self.activation.apply(value));
+        }
+        Ok(values)
+    }
+}
+
+#[derive(Clone)]
+pub struct QuantizedNetwork {
+    //An int8 copy of a nodes_layers::Network, meant only for inference. It can't be trained.
+    pub layers: Vec<QuantizedLayer>,
+    pub layer_count: usize,
+    pub input_scaler: Option<scaling::Scaler>, //Copied from the f32 network.
+    pub target_scaler: Option<scaling::Scaler>, //Copied from the f32 network.
+}
+
+impl QuantizedNetwork {
+    pub fn calculate(&self, inputs: &[f32]) -> Result<Vec<Vec<f32>>, NnError> {
+        //Works like nodes_layers::Network::calculate, returning the values of every layer.
+        let scaled_inputs = match &self.input_scaler {
+            Some(input_scaler) => input_scaler.transform(inputs)?,
+            None => inputs.to_vec(),
+        };
+        let mut values: Vec<Vec<f32>> = Vec::new();
+        for num in 0..self.layer_count {
+            let layer_values = self.layers[num].calculate(if num == 0 {&scaled_inputs} else {&values[num-1]})?;
+            values.push(layer_values);
+        }
+        Ok(values)
+    }
+
+    pub fn predict(&self, inputs: &[f32]) -> Result<Vec<f32>, NnError> {
+        //Calculates only the values of the output layer, scaled back with target_scaler if there is one.
+        let outputs = self.calculate(inputs)?.pop().unwrap_or_default();
+        match &self.target_scaler {
+            Some(target_scaler) => target_scaler.inverse_transform(&outputs),
+            None => Ok(outputs),
+        }
+    }
+
+    pub fn evaluate(&self, dataset: &dyn dataset::Dataset) -> Result<f32, NnError> {
+        //The fraction of the examples where the biggest output is in the same position as the biggest desired output.
+        let mut successes = 0;
+        for num in 0..dataset.len() {
+            let (inputs, desired_outputs) = dataset.get(num);
+            if nodes_layers::same_biggest(&self.predict(&inputs)?, &desired_outputs) {
+                successes += 1;
+            }
+        }
+        Ok(if dataset.is_empty() {0.0} else {successes as f32 / dataset.len() as f32})
+    }
+}
+
+pub fn quantize(network: &nodes_layers::Network, calibration_data: &dyn dataset::Dataset) -> Result<QuantizedNetwork, NnError> {
+    //Post-training quantization of a network to int8 weights with one scale per layer.
+    //The calibration inputs (a sample of the training data, like a subset of the images from inout::unpack_images, works well) are run through the f32 network
+    //to find how large the values going into each layer get, which decides the scale the values are quantized with.
+    let mut max_inputs = vec![0.0f32; network.layer_count];
+    for num in 0..calibration_data.len() {
+        let image = calibration_data.get(num).0;
+        for value in network.scale_inputs(&image)? {
+            max_inputs[0] = max_inputs[0].max(value.abs());
+        }
+        let values = network.calculate(&image)?;
+        for num in 1..network.layer_count {
+            for value in &values[num-1] {
+                max_inputs[num] = max_inputs[num].max(value.abs());
+            }
+        }
+    }
+    if calibration_data.is_empty() {
+        //Without calibration data, assume the values are in [0; 1] like the images and the sigmoid outputs.
+        max_inputs = vec![1.0; network.layer_count];
+    }
+    let mut layers = Vec::new();
+    for num in 0..network.layer_count {
+        layers.push(QuantizedLayer::from_layer(&network.layers[num], max_inputs[num]));
+    }
+    Ok(QuantizedNetwork {
+        layer_count: layers.len(),
+        layers: layers,
+        input_scaler: network.input_scaler.clone(),
+        target_scaler: network.target_scaler.clone(),
+    })
+}
+
+fn quantize_value(value: f32, scale: f32) -> i8 {
+    (value / scale).round().max(-127.0).min(127.0) as i8
+}
+
+#[derive(Clone, Debug)]
+pub struct QuantizationReport {
+    pub float_accuracy: f32, //Accuracy of the original f32 network.
+    pub quantized_accuracy: f32, //Accuracy of the int8 network.
+    pub accuracy_drop: f32, //float_accuracy - quantized_accuracy, so positive when quantization made things worse.
+    pub agreement: f32, //The fraction of the examples where the two networks gave the same answer, right or wrong.
+    pub example_count: usize,
+}
+
+impl fmt::Display for QuantizationReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "Examples: {}", self.example_count)?;
+        writeln!(f, "f32 accuracy: {:.4}", self.float_accuracy)?;
+        writeln!(f, "int8 accuracy: {:.4}", self.quantized_accuracy)?;
+        writeln!(f, "Accuracy drop: {:.4}", self.accuracy_drop)?;
+        write!(f, "Agreement between the two: {:.4}", self.agreement)
+    }
+}
+
+pub fn compare_accuracy(network: &nodes_layers::Network, quantized: &QuantizedNetwork, dataset: &dyn dataset::Dataset) -> Result<QuantizationReport, NnError> {
+    //Runs both networks on the same examples and reports how much accuracy was lost to quantization.
+    let mut float_successes = 0;
+    let mut quantized_successes = 0;
+    let mut agreements = 0;
+    for num in 0..dataset.len() {
+        let (inputs, desired_outputs) = dataset.get(num);
+        let float_outputs = network.predict(&inputs)?;
+        let quantized_outputs = quantized.predict(&inputs)?;
+        if nodes_layers::same_biggest(&float_outputs, &desired_outputs) {
+            float_successes += 1;
+        }
+        if nodes_layers::same_biggest(&quantized_outputs, &desired_outputs) {
+            quantized_successes += 1;
+        }
+        if nodes_layers::same_biggest(&float_outputs, &quantized_outputs) {
+            agreements += 1;
+        }
+    }
+    let example_count = dataset.len();
+    let fraction = |count: usize| if example_count == 0 {0.0} else {count as f32 / example_count as f32};
+    Ok(QuantizationReport {
+        float_accuracy: fraction(float_successes),
+        quantized_accuracy: fraction(quantized_successes),
+        accuracy_drop: fraction(float_successes) - fraction(quantized_successes),
+        agreement: fraction(agreements),
+        example_count: example_count,
+    })
+}
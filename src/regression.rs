@@ -0,0 +1,87 @@
+use std::fmt;
+
+use crate::dataset;
+use crate::error::NnError;
+use crate::nodes_layers;
+use crate::scaling;
+
+pub fn new_network(node_nums: &[usize], learning_rate: f32, loss: nodes_layers::Loss, training_data: &dyn dataset::Dataset) -> Result<nodes_layers::Network, NnError> {
+    //Makes a new network for predicting numbers rather than classes.
+    //The output layer is linear so it isn't stuck in (0; 1), and the inputs and targets are standardised based on the training data given.
+    //The scalers are kept in the network, so it takes and gives values in their original units.
+    if node_nums.len() < 2 {
+        return Err(NnError::Config(format!("A network needs an input layer and at least one more, but the form has {:?} layers", node_nums.len())))
+    }
+    let mut network = nodes_layers::Network::new(node_nums, learning_rate);
+    let output_layer = network.layer_count - 1;
+    network.layers[output_layer].activation = nodes_layers::Activation::Linear;
+    network.loss = loss;
+    let mut inputs = Vec::new();
+    let mut targets = Vec::new();
+    for num in 0..training_data.len() {
+        let (input, target) = training_data.get(num);
+        inputs.push(input);
+        targets.push(target);
+    }
+    network.input_scaler = Some(scaling::Scaler::fit_standard(&inputs)?);
+    network.target_scaler = Some(scaling::Scaler::fit_standard(&targets)?);
+    Ok(network)
+}
+
+#[derive(Clone, Debug)]
+pub struct RegressionReport {
+    pub rmse: f32, //Root mean squared error, in the units of the targets.
+    pub mae: f32, //Mean absolute error, in the units of the targets.
+    pub r_squared: f32, //The fraction of the targets' variance that the network explains. 1.0 is perfect, 0.0 is no better than always guessing the mean.
+    pub example_count: usize,
+}
+
+impl fmt::Display for RegressionReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "Examples: {}", self.example_count)?;
+        writeln!(f, "RMSE: {:.6}", self.rmse)?;
+        writeln!(f, "MAE: {:.6}", self.mae)?;
+        write!(f, "R²: {:.6}", self.r_squared)
+    }
+}
+
+pub fn evaluate(network: &nodes_layers::Network, dataset: &dyn dataset::Dataset) -> Result<RegressionReport, NnError> {
+    //Compares the network's predictions with the targets. When there are several targets per example, every one of them is counted.
+    let mut predictions = Vec::new();
+    let mut targets = Vec::new();
+    for num in 0..dataset.len() {
+        let (input, target) = dataset.get(num);
+        predictions.push(network.predict(&input)?);
+        targets.push(target);
+    }
+    let width = targets.first().map_or(0, |target| target.len());
+    for num in 0..targets.len() {
+        if targets[num].len() != width || predictions[num].len() != width {
+            return Err(NnError::shape_mismatch("the predictions and targets", width, targets[num].len().min(predictions[num].len())))
+        }
+    }
+    let mut means = vec![0.0f64; width];
+    for target in &targets {
+        for pos in 0..width {
+            means[pos] += target[pos] as f64 / targets.len() as f64;
+        }
+    }
+    let mut squared_error = 0.0f64;
+    let mut absolute_error = 0.0f64;
+    let mut total_variance = 0.0f64;
+    for num in 0..targets.len() {
+        for pos in 0..width {
+            let error = (predictions[num][pos] - targets[num][pos]) as f64;
+            squared_error += error.powi(2);
+            absolute_error += error.abs();
+            total_variance += (targets[num][pos] as f64 - means[pos]).powi(2);
+        }
+    }
+    let value_count = (targets.len() * width).max(1) as f64;
+    Ok(RegressionReport {
+        rmse: (squared_error / value_count).sqrt() as f32,
+        mae: (absolute_error / value_count) as f32,
+        r_squared: if total_variance > 0.0 {(1.0 - squared_error / total_variance) as f32} else {0.0},
+        example_count: targets.len(),
+    })
+}
@@ -0,0 +1,74 @@
+extern crate rand;
+
+use rand::Rng;
+use rand::distributions::StandardNormal;
+
+use crate::error::NnError;
+use crate::nodes_layers;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SaliencyMethod {
+    //Ways of deciding how much each input mattered for an output.
+    Vanilla, //The gradient of the output with respect to the inputs.
+    GradientTimesInput, //The gradient multiplied by the inputs, so inputs that are 0.0 count for nothing.
+    SmoothGrad {samples: usize, noise: f32}, //The average gradient over copies of the inputs with Gaussian noise added. noise is the standard deviation, compared to the range of the inputs.
+    IntegratedGradients {steps: usize}, //The gradient averaged along a straight line from a black image to the inputs, multiplied by the inputs.
+}
+
+pub fn saliency_map(network: &nodes_layers::Network, inputs: &Vec<f32>, output_index: usize, method: SaliencyMethod) -> Result<Vec<f32>, NnError> {
+    //Finds a value for each input telling how much it drove the output at output_index.
+    //The values are signed: positive inputs pushed the output up, negative ones pushed it down. Use normalise to draw them.
+    match method {
+        SaliencyMethod::Vanilla => network.output_gradient(inputs, output_index),
+        SaliencyMethod::GradientTimesInput => {
+            let gradient = network.output_gradient(inputs, output_index)?;
+            Ok((0..inputs.len()).map(|num| gradient[num] * inputs[num]).collect())
+        },
+        SaliencyMethod::SmoothGrad {samples, noise} => {
+            let mut rng = rand::thread_rng();
+            let mut minimum = std::f32::INFINITY;
+            let mut maximum = std::f32::NEG_INFINITY;
+            for value in inputs {
+                minimum = minimum.min(*value);
+                maximum = maximum.max(*value);
+            }
+            let standard_deviation = noise * (maximum - minimum).max(std::f32::EPSILON);
+            let mut total = vec![0.0; inputs.len()];
+            let samples = samples.max(1);
+            for _ in 0..samples {
+                let noisy_inputs: Vec<f32> = inputs.iter().map(|value| value + rng.sample(StandardNormal) as f32 * standard_deviation).collect();
+                let gradient = network.output_gradient(&noisy_inputs, output_index)?;
+                for num in 0..total.len() {
+                    total[num] += gradient[num] / samples as f32;
+                }
+            }
+            Ok(total)
+        },
+        SaliencyMethod::IntegratedGradients {steps} => {
+            //The baseline is all zeros, which for images is black. The midpoint of each step is used, which approximates the integral better than either end.
+            let steps = steps.max(1);
+            let mut total = vec![0.0; inputs.len()];
+            for step in 0..steps {
+                let fraction = (step as f32 + 0.5) / steps as f32;
+                let scaled_inputs: Vec<f32> = inputs.iter().map(|value| value * fraction).collect();
+                let gradient = network.output_gradient(&scaled_inputs, output_index)?;
+                for num in 0..total.len() {
+                    total[num] += gradient[num] / steps as f32;
+                }
+            }
+            Ok((0..inputs.len()).map(|num| total[num] * inputs[num]).collect())
+        },
+    }
+}
+
+pub fn normalise(saliency: &[f32]) -> Vec<f32> {
+    //Turns a saliency map into values in [0; 1] by taking the absolute values and dividing by the biggest of them.
+    let mut biggest: f32 = 0.0;
+    for value in saliency {
+        biggest = biggest.max(value.abs());
+    }
+    if biggest == 0.0 {
+        return vec![0.0; saliency.len()]
+    }
+    saliency.iter().map(|value| value.abs() / biggest).collect()
+}
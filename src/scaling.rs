@@ -0,0 +1,501 @@
+use crate::error::NnError;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Scaler {
+    //Moves and stretches each value separately, so that values of very different sizes end up in similar ranges.
+    //A value is scaled as (value - offset) / scale, using the offset and scale at its position.
+    pub offsets: Vec<f32>,
+    pub scales: Vec<f32>,
+    pub whitening: Option<Whitening>, //If there is one, the scaled values are then turned into their principal components.
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Whitening {
+    //Turns a row of values into uncorrelated values with a variance of about 1.0 each, by projecting it onto the principal components
+    //of the rows it was fitted on and dividing each projection by its standard deviation.
+    pub components: Vec<Vec<f32>>, //Unit vectors, one per output value, from the biggest variance to the smallest.
+    pub scales: Vec<f32>, //The standard deviation along each component, with the epsilon added to its variance.
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Standardisation {
+    //Ways of standardising inputs based on a training set, so they're fitted once and then kept with the network.
+    PerPixel, //Each position gets its own mean and standard deviation.
+    Global, //Every position shares the mean and standard deviation of all the values together.
+    PcaWhitening {components: usize, epsilon: f32}, //Centres each position, then keeps the given number of principal components, whitened. The epsilon keeps tiny variances from being blown up.
+}
+
+impl Standardisation {
+    pub fn fit(&self, rows: &[Vec<f32>]) -> Result<Scaler, NnError> {
+        match *self {
+            Standardisation::PerPixel => Scaler::fit_standard(rows),
+            Standardisation::Global => Scaler::fit_global_standard(rows),
+            Standardisation::PcaWhitening {components, epsilon} => Scaler::fit_pca_whitening(rows, components, epsilon),
+        }
+    }
+}
+
+impl Scaler {
+    pub fn fit_min_max(rows: &[Vec<f32>]) -> Result<Scaler, NnError> {
+        //Finds the scaling that puts each position into [0; 1], based on the smallest and biggest value found there.
+        //Positions where every value is the same are only moved, not stretched.
+        let width = row_width(rows)?;
+        let mut minimums = vec![std::f32::INFINITY; width];
+        let mut maximums = vec![std::f32::NEG_INFINITY; width];
+        for row in rows {
+            for pos in 0..width {
+                minimums[pos] = minimums[pos].min(row[pos]);
+                maximums[pos] = maximums[pos].max(row[pos]);
+            }
+        }
+        let mut scales = Vec::new();
+        for pos in 0..width {
+            let range = maximums[pos] - minimums[pos];
+            scales.push(if range > 0.0 {range} else {1.0});
+        }
+        Ok(Scaler {
+            offsets: minimums,
+            scales: scales,
+            whitening: None,
+        })
+    }
+
+    pub fn fit_standard(rows: &[Vec<f32>]) -> Result<Scaler, NnError> {
+        //Finds the scaling that gives each position a mean of 0.0 and a standard deviation of 1.0.
+        //Positions where every value is the same are only moved, not stretched.
+        let width = row_width(rows)?;
+        let count = rows.len().max(1) as f64;
+        let mut sums = vec![0.0f64; width];
+        let mut square_sums = vec![0.0f64; width];
+        for row in rows {
+            for pos in 0..width {
+                sums[pos] += row[pos] as f64;
+                square_sums[pos] += (row[pos] as f64).powi(2);
+            }
+        }
+        let mut offsets = Vec::new();
+        let mut scales = Vec::new();
+        for pos in 0..width {
+            let mean = sums[pos] / count;
+            let variance = (square_sums[pos] / count - mean.powi(2)).max(0.0);
+            offsets.push(mean as f32);
+            scales.push(if variance > 0.0 {variance.sqrt() as f32} else {1.0});
+        }
+        Ok(Scaler {
+            offsets: offsets,
+            scales: scales,
+            whitening: None,
+        })
+    }
+
+    pub fn fit_global_standard(rows: &[Vec<f32>]) -> Result<Scaler, NnError> {
+        //Like fit_standard, but with a single mean and standard deviation for all the values, so the pixels of an image keep their differences.
+        let width = row_width(rows)?;
+        let count = (rows.len() * width).max(1) as f64;
+        let mut sum = 0.0f64;
+        let mut square_sum = 0.0f64;
+        for row in rows {
+            for pos in 0..width {
+                sum += row[pos] as f64;
+                square_sum += (row[pos] as f64).powi(2);
+            }
+        }
+        let mean = sum / count;
+        let variance = (square_sum / count - mean.powi(2)).max(0.0);
+        let scale = if variance > 0.0 {variance.sqrt() as f32} else {1.0};
+        Ok(Scaler {
+            offsets: vec![mean as f32; width],
+            scales: vec![scale; width],
+            whitening: None,
+        })
+    }
+
+    pub fn fit_pca_whitening(rows: &[Vec<f32>], component_count: usize, epsilon: f32) -> Result<Scaler, NnError> {
+        //Centres each position, then finds the principal components from the covariance of the rows.
+        //The covariance has a value for every pair of positions, so fitting takes a while for big inputs. A few thousand rows are usually enough.
+        let centring = Scaler {
+            offsets: Scaler::fit_standard(rows)?.offsets,
+            scales: vec![1.0; row_width(rows)?],
+            whitening: None,
+        };
+        let width = centring.offsets.len();
+        let component_count = component_count.min(width);
+        let mut covariance = vec![vec![0.0f64; width]; width];
+        for row in rows {
+            let centred: Vec<f32> = (0..width).map(|pos| row[pos] - centring.offsets[pos]).collect();
+            for first in 0..width {
+                if centred[first] == 0.0 {
+                    continue
+                }
+                for second in first..width {
+                    covariance[first][second] += centred[first] as f64 * centred[second] as f64;
+                }
+            }
+        }
+        for first in 0..width {
+            for second in first..width {
+                covariance[first][second] /= rows.len().max(1) as f64;
+                covariance[second][first] = covariance[first][second];
+            }
+        }
+        let (variances, vectors) = symmetric_eigen(covariance)?;
+        let mut order: Vec<usize> = (0..width).collect();
+        order.sort_by(|&first, &second| variances[second].partial_cmp(&variances[first]).unwrap_or(std::cmp::Ordering::Equal));
+        let mut components = Vec::new();
+        let mut scales = Vec::new();
+        for &pos in &order[..component_count] {
+            components.push((0..width).map(|num| vectors[num][pos] as f32).collect());
+            scales.push((variances[pos].max(0.0) as f32 + epsilon).sqrt());
+        }
+        Ok(Scaler {
+            whitening: Some(Whitening {
+                components: components,
+                scales: scales,
+            }),
+            ..centring
+        })
+    }
+
+    pub fn output_count(&self) -> usize {
+        //The number of values a scaled row has, which is fewer than it started with if the whitening drops components.
+        match &self.whitening {
+            Some(whitening) => whitening.components.len(),
+            None => self.offsets.len(),
+        }
+    }
+
+    pub fn transform(&self, values: &[f32]) -> Result<Vec<f32>, NnError> {
+        //Scales a row of values.
+        if values.len() != self.offsets.len() {
+            return Err(NnError::shape_mismatch("the values going into a scaler", self.offsets.len(), values.len()))
+        }
+        let scaled: Vec<f32> = (0..values.len()).map(|pos| (values[pos] - self.offsets[pos]) / self.scales[pos]).collect();
+        Ok(match &self.whitening {
+            Some(whitening) => {
+                (0..whitening.components.len()).map(|num| dot(&whitening.components[num], &scaled) / whitening.scales[num]).collect()
+            },
+            None => scaled,
+        })
+    }
+
+    pub fn inverse_transform(&self, values: &[f32]) -> Result<Vec<f32>, NnError> {
+        //Turns a row of scaled values back into the original units. What was lost with dropped components stays lost.
+        if values.len() != self.output_count() {
+            return Err(NnError::shape_mismatch("the values going back through a scaler", self.output_count(), values.len()))
+        }
+        let scaled = match &self.whitening {
+            Some(whitening) => {
+                let mut scaled = vec![0.0; self.offsets.len()];
+                for num in 0..whitening.components.len() {
+                    for pos in 0..scaled.len() {
+                        scaled[pos] += values[num] * whitening.scales[num] * whitening.components[num][pos];
+                    }
+                }
+                scaled
+            },
+            None => values.to_vec(),
+        };
+        Ok((0..scaled.len()).map(|pos| scaled[pos] * self.scales[pos] + self.offsets[pos]).collect())
+    }
+
+    pub fn gradient_to_inputs(&self, gradient: &[f32]) -> Vec<f32> {
+        //Turns a gradient for the scaled values into a gradient for the values before they were scaled.
+        let scaled_gradient = match &self.whitening {
+            Some(whitening) => {
+                let mut scaled_gradient = vec![0.0; self.offsets.len()];
+                for num in 0..whitening.components.len() {
+                    for pos in 0..scaled_gradient.len() {
+                        scaled_gradient[pos] += gradient[num] / whitening.scales[num] * whitening.components[num][pos];
+                    }
+                }
+                scaled_gradient
+            },
+            None => gradient.to_vec(),
+        };
+        (0..scaled_gradient.len()).map(|pos| scaled_gradient[pos] / self.scales[pos]).collect()
+    }
+}
+
+fn row_width(rows: &[Vec<f32>]) -> Result<usize, NnError> {
+    //The length of the rows a scaler is fitted on, which must all be the same.
+    //Every value must be a finite number too, since a single NaN, which a .csv file can easily hold, would spoil every offset and scale it touches.
+    let width = rows.first().map_or(0, |row| row.len());
+    for (row_num, row) in rows.iter().enumerate() {
+        if row.len() != width {
+            return Err(NnError::shape_mismatch("a row the scaler is fitted on", width, row.len()))
+        }
+        if let Some(pos) = row.iter().position(|value| !value.is_finite()) {
+            return Err(NnError::Config(format!("Row {:?} has {:?} at position {:?}, so a scaler can't be fitted on it", row_num, row[pos], pos)))
+        }
+    }
+    Ok(width)
+}
+
+fn dot(first: &[f32], second: &[f32]) -> f32 {
+    (0..first.len()).map(|pos| first[pos] * second[pos]).sum()
+}
+
+fn symmetric_eigen(matrix: Vec<Vec<f64>>) -> Result<(Vec<f64>, Vec<Vec<f64>>), NnError> {
+    //Finds the eigenvalues and eigenvectors of a symmetric matrix. It's first turned tridiagonal with Householder reflections,
+    //then the QL algorithm with implicit shifts finds the eigenvalues of that, the way the JAMA library does it.
+    //The columns of the second matrix are the eigenvectors.
+    //The QL iterations usually take one or two per eigenvalue, so they're given up on after 30 per eigenvalue, which only happens if the matrix is broken.
+    let size = matrix.len();
+    if size == 0 {
+        return Ok((Vec::new(), Vec::new()))
+    }
+    let max_iterations = 30 * size;
+    let mut iterations = 0;
+    let mut vectors = matrix;
+    let mut diagonal: Vec<f64> = vectors[size-1].clone();
+    let mut off_diagonal = vec![0.0f64; size];
+    for i in (1..size).rev() {
+        let mut scale = 0.0;
+        let mut h = 0.0;
+        for k in 0..i {
+            scale += diagonal[k].abs();
+        }
+        if scale == 0.0 {
+            off_diagonal[i] = diagonal[i-1];
+            for j in 0..i {
+                diagonal[j] = vectors[i-1][j];
+                vectors[i][j] = 0.0;
+                vectors[j][i] = 0.0;
+            }
+        } else {
+            for k in 0..i {
+                diagonal[k] /= scale;
+                h += diagonal[k] * diagonal[k];
+            }
+            let mut f = diagonal[i-1];
+            let mut g = if f > 0.0 {-h.sqrt()} else {h.sqrt()};
+            off_diagonal[i] = scale * g;
+            h -= f * g;
+            diagonal[i-1] = f - g;
+            for j in 0..i {
+                off_diagonal[j] = 0.0;
+            }
+            for j in 0..i {
+                f = diagonal[j];
+                vectors[j][i] = f;
+                g = off_diagonal[j] + vectors[j][j] * f;
+                for k in j+1..i {
+                    g += vectors[k][j] * diagonal[k];
+                    off_diagonal[k] += vectors[k][j] * f;
+                }
+                off_diagonal[j] = g;
+            }
+            f = 0.0;
+            for j in 0..i {
+                off_diagonal[j] /= h;
+                f += off_diagonal[j] * diagonal[j];
+            }
+            let hh = f / (h + h);
+            for j in 0..i {
+                off_diagonal[j] -= hh * diagonal[j];
+            }
+            for j in 0..i {
+                f = diagonal[j];
+                g = off_diagonal[j];
+                for k in j..i {
+                    vectors[k][j] -= f * off_diagonal[k] + g * diagonal[k];
+                }
+                diagonal[j] = vectors[i-1][j];
+                vectors[i][j] = 0.0;
+            }
+        }
+        diagonal[i] = h;
+    }
+    //Builds up the reflections into the eigenvectors of the tridiagonal matrix's original.
+    for i in 0..size-1 {
+        vectors[size-1][i] = vectors[i][i];
+        vectors[i][i] = 1.0;
+        let h = diagonal[i+1];
+        if h != 0.0 {
+            for k in 0..=i {
+                diagonal[k] = vectors[k][i+1] / h;
+            }
+            for j in 0..=i {
+                let mut g = 0.0;
+                for k in 0..=i {
+                    g += vectors[k][i+1] * vectors[k][j];
+                }
+                for k in 0..=i {
+                    vectors[k][j] -= g * diagonal[k];
+                }
+            }
+        }
+        for k in 0..=i {
+            vectors[k][i+1] = 0.0;
+        }
+    }
+    for j in 0..size {
+        diagonal[j] = vectors[size-1][j];
+        vectors[size-1][j] = 0.0;
+    }
+    vectors[size-1][size-1] = 1.0;
+    //The QL iterations, which shrink the values off the diagonal until they're negligible.
+    for i in 1..size {
+        off_diagonal[i-1] = off_diagonal[i];
+    }
+    off_diagonal[size-1] = 0.0;
+    let mut f = 0.0;
+    let mut largest = 0.0f64;
+    let epsilon = std::f64::EPSILON;
+    for l in 0..size {
+        largest = largest.max(diagonal[l].abs() + off_diagonal[l].abs());
+        let mut m = l;
+        while m < size - 1 && off_diagonal[m].abs() > epsilon * largest {
+            m += 1;
+        }
+        if m > l {
+            loop {
+                iterations += 1;
+                if iterations > max_iterations {
+                    return Err(NnError::Config(format!("The principal components didn't settle after {:?} iterations", max_iterations)))
+                }
+                let mut g = diagonal[l];
+                let mut p = (diagonal[l+1] - g) / (2.0 * off_diagonal[l]);
+                let mut r = p.hypot(1.0);
+                if p < 0.0 {
+                    r = -r;
+                }
+                diagonal[l] = off_diagonal[l] / (p + r);
+                diagonal[l+1] = off_diagonal[l] * (p + r);
+                let next_diagonal = diagonal[l+1];
+                let mut h = g - diagonal[l];
+                for i in l+2..size {
+                    diagonal[i] -= h;
+                }
+                f += h;
+                p = diagonal[m];
+                let mut c = 1.0;
+                let mut c2 = c;
+                let mut c3 = c;
+                let next_off_diagonal = off_diagonal[l+1];
+                let mut s = 0.0;
+                let mut s2 = 0.0;
+                for i in (l..m).rev() {
+                    c3 = c2;
+                    c2 = c;
+                    s2 = s;
+                    g = c * off_diagonal[i];
+                    h = c * p;
+                    r = p.hypot(off_diagonal[i]);
+                    off_diagonal[i+1] = s * r;
+                    s = off_diagonal[i] / r;
+                    c = p / r;
+                    p = c * diagonal[i] - s * g;
+                    diagonal[i+1] = h + s * (c * g + s * diagonal[i]);
+                    for k in 0..size {
+                        h = vectors[k][i+1];
+                        vectors[k][i+1] = s * vectors[k][i] + c * h;
+                        vectors[k][i] = c * vectors[k][i] - s * h;
+                    }
+                }
+                p = -s * s2 * c3 * next_off_diagonal * off_diagonal[l] / next_diagonal;
+                off_diagonal[l] = s * p;
+                diagonal[l] = c * p;
+                if off_diagonal[l].abs() <= epsilon * largest {
+                    break
+                }
+            }
+        }
+        diagonal[l] += f;
+        off_diagonal[l] = 0.0;
+    }
+    if diagonal.iter().chain(vectors.iter().flatten()).any(|value| !value.is_finite()) {
+        return Err(NnError::Config("The principal components couldn't be found, since the covariance isn't made of finite numbers".to_string()))
+    }
+    Ok((diagonal, vectors))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn covariance_of(rows: &[Vec<f32>]) -> Vec<Vec<f64>> {
+        let width = rows[0].len();
+        let count = rows.len() as f64;
+        let means: Vec<f64> = (0..width).map(|pos| rows.iter().map(|row| row[pos] as f64).sum::<f64>() / count).collect();
+        let mut covariance = vec![vec![0.0; width]; width];
+        for row in rows {
+            for first in 0..width {
+                for second in 0..width {
+                    covariance[first][second] += (row[first] as f64 - means[first]) * (row[second] as f64 - means[second]) / count;
+                }
+            }
+        }
+        covariance
+    }
+
+    fn correlated_rows() -> Vec<Vec<f32>> {
+        //Rows with a different amount of variance in each direction, where every position depends on the others.
+        (0..200).map(|num| {
+            let a = ((num * 37 % 101) as f32 / 101.0) - 0.5;
+            let b = ((num * 53 % 97) as f32 / 97.0) - 0.5;
+            let c = ((num * 71 % 89) as f32 / 89.0) - 0.5;
+            let d = ((num * 29 % 83) as f32 / 83.0) - 0.5;
+            vec![3.0 * a + b, a - 2.0 * b + 0.5 * c, 0.2 * c + a - d, 10.0 + b - c + 0.5 * d]
+        }).collect()
+    }
+
+    #[test]
+    fn eigen_reproduces_known_matrix() {
+        let matrix = vec![
+            vec![4.0, 1.0, -2.0, 0.5],
+            vec![1.0, 3.0, 0.0, 1.5],
+            vec![-2.0, 0.0, 5.0, -1.0],
+            vec![0.5, 1.5, -1.0, 2.0],
+        ];
+        let (values, vectors) = symmetric_eigen(matrix.clone()).unwrap();
+        for first in 0..4 {
+            for second in 0..4 {
+                let rebuilt: f64 = (0..4).map(|num| vectors[first][num] * values[num] * vectors[second][num]).sum();
+                assert!((rebuilt - matrix[first][second]).abs() < 1e-9, "{} != {}", rebuilt, matrix[first][second]);
+                let product: f64 = (0..4).map(|num| vectors[num][first] * vectors[num][second]).sum();
+                assert!((product - if first == second {1.0} else {0.0}).abs() < 1e-9);
+            }
+        }
+    }
+
+    #[test]
+    fn eigen_finds_known_eigenvalues() {
+        let (mut values, _) = symmetric_eigen(vec![vec![2.0, 1.0], vec![1.0, 2.0]]).unwrap();
+        values.sort_by(|first, second| first.partial_cmp(second).unwrap());
+        assert!((values[0] - 1.0).abs() < 1e-12);
+        assert!((values[1] - 3.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn eigen_of_broken_matrix_is_an_error() {
+        let nan = std::f64::NAN;
+        assert!(symmetric_eigen(vec![vec![1.0, nan], vec![nan, 1.0]]).is_err());
+        assert!(symmetric_eigen(vec![vec![1.0, 0.5, nan], vec![0.5, 2.0, 0.5], vec![nan, 0.5, 3.0]]).is_err());
+    }
+
+    #[test]
+    fn whitened_rows_have_identity_covariance() {
+        let rows = correlated_rows();
+        let scaler = Scaler::fit_pca_whitening(&rows, 4, 0.0).unwrap();
+        let whitened: Vec<Vec<f32>> = rows.iter().map(|row| scaler.transform(row).unwrap()).collect();
+        let covariance = covariance_of(&whitened);
+        for first in 0..4 {
+            for second in 0..4 {
+                let expected = if first == second {1.0} else {0.0};
+                assert!((covariance[first][second] - expected).abs() < 1e-3, "{:?}", covariance);
+            }
+        }
+    }
+
+    #[test]
+    fn non_finite_rows_are_rejected() {
+        let mut rows = correlated_rows();
+        rows[5][2] = std::f32::NAN;
+        assert!(Scaler::fit_standard(&rows).is_err());
+        assert!(Scaler::fit_pca_whitening(&rows, 2, 1e-5).is_err());
+        rows[5][2] = std::f32::INFINITY;
+        assert!(Scaler::fit_min_max(&rows).is_err());
+        assert!(Scaler::fit_global_standard(&rows).is_err());
+    }
+}
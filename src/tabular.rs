@@ -0,0 +1,184 @@
+extern crate csv;
+
+use std::cmp::Ordering;
+use std::error::Error;
+use std::fmt;
+use std::io;
+
+use crate::dataset;
+use crate::error::NnError;
+use crate::labels;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Column {
+    //A column of a CSV file, either by its position counting from 0, or by its name in the header.
+    Index(usize),
+    Name(String),
+}
+
+#[derive(Debug)]
+pub enum TabularError {
+    Csv(csv::Error),
+    MissingColumn(Column), //A column that isn't in the header, or is past the end of the rows.
+    NoHeader(Column), //A column was picked by name, but the file is read without a header.
+    InvalidNumber {row: usize, column: usize, value: String}, //Rows are counted from 0, not counting the header.
+    UnknownLabel {row: usize, label: String}, //A label that isn't in the label map the loader was given.
+}
+
+impl fmt::Display for TabularError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TabularError::Csv(error) => write!(f, "Couldn't read the CSV file: {}", error),
+            TabularError::MissingColumn(column) => write!(f, "The CSV file has no column {:?}", column),
+            TabularError::NoHeader(column) => write!(f, "The column {:?} is picked by name, but the CSV file is read without a header", column),
+            TabularError::InvalidNumber {row, column, value} => write!(f, "Expected a number in row {:?}, column {:?}, found {:?}", row, column, value),
+            TabularError::UnknownLabel {row, label} => write!(f, "The label {:?} in row {:?} isn't in the label map", label, row),
+        }
+    }
+}
+
+impl Error for TabularError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            TabularError::Csv(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<csv::Error> for TabularError {
+    fn from(error: csv::Error) -> TabularError {
+        TabularError::Csv(error)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct CsvLoader {
+    //Reads a table with one example per row into a dataset, like the Iris dataset.
+    //The features have to be numbers, while the labels can be anything, like "setosa" or "3".
+    pub label_column: Column,
+    pub feature_columns: Option<Vec<Column>>, //If there are none, every column but the label column is a feature.
+    pub has_header: bool,
+    pub delimiter: u8,
+    pub label_map: Option<labels::LabelMap>, //If there is one, the labels are mapped with it, like when loading a test set for a trained network.
+}
+
+impl CsvLoader {
+    pub fn new(label_column: Column) -> CsvLoader {
+        //A loader for comma separated files with a header. Change it with the with_ methods.
+        CsvLoader {
+            label_column: label_column,
+            feature_columns: None,
+            has_header: true,
+            delimiter: b',',
+            label_map: None,
+        }
+    }
+
+    pub fn with_features(mut self, feature_columns: Vec<Column>) -> CsvLoader {
+        self.feature_columns = Some(feature_columns);
+        self
+    }
+
+    pub fn with_header(mut self, has_header: bool) -> CsvLoader {
+        self.has_header = has_header;
+        self
+    }
+
+    pub fn with_delimiter(mut self, delimiter: u8) -> CsvLoader {
+        self.delimiter = delimiter;
+        self
+    }
+
+    pub fn with_label_map(mut self, label_map: labels::LabelMap) -> CsvLoader {
+        self.label_map = Some(label_map);
+        self
+    }
+
+    pub fn load(&self, file_path: &str) -> Result<dataset::InMemoryDataset, NnError> {
+        let reader = csv::ReaderBuilder::new()
+            .has_headers(self.has_header)
+            .delimiter(self.delimiter)
+            .from_path(file_path)
+            .map_err(TabularError::Csv)?;
+        self.load_from(reader)
+    }
+
+    pub fn load_from<R: io::Read>(&self, mut reader: csv::Reader<R>) -> Result<dataset::InMemoryDataset, NnError> {
+        //Reads every row, then maps the labels. Without a label map, the classes are the distinct labels,
+        //sorted as numbers if they all are numbers, and as text otherwise.
+        //The reader has to be made with the same header setting as the loader.
+        let header: Vec<String> = if self.has_header {
+                reader.headers().map_err(TabularError::Csv)?.iter().map(|name| name.trim().to_string()).collect()
+            } else {
+                Vec::new()
+            };
+        let mut rows = Vec::new();
+        for result in reader.records() {
+            let record = result.map_err(TabularError::Csv)?;
+            rows.push(record.iter().map(|field| field.trim().to_string()).collect::<Vec<String>>());
+        }
+        let column_count = if self.has_header {header.len()} else {rows.first().map_or(0, |row| row.len())};
+        let label_pos = self.find_column(&self.label_column, &header, column_count)?;
+        let feature_positions = match &self.feature_columns {
+            Some(columns) => {
+                let mut positions = Vec::new();
+                for column in columns {
+                    positions.push(self.find_column(column, &header, column_count)?);
+                }
+                positions
+            },
+            None => (0..column_count).filter(|&pos| pos != label_pos).collect(),
+        };
+        let mut inputs = Vec::new();
+        let mut keys = Vec::new();
+        for (row_num, row) in rows.iter().enumerate() {
+            let mut input = Vec::new();
+            for &pos in &feature_positions {
+                let field = row.get(pos).ok_or_else(|| TabularError::MissingColumn(Column::Index(pos)))?;
+                let value = field.parse::<f32>().map_err(|_| TabularError::InvalidNumber {row: row_num, column: pos, value: field.clone()})?;
+                input.push(value);
+            }
+            inputs.push(input);
+            keys.push(row.get(label_pos).ok_or_else(|| TabularError::MissingColumn(Column::Index(label_pos)))?.clone());
+        }
+        let label_map = match &self.label_map {
+            Some(label_map) => label_map.clone(),
+            None => labels::LabelMap::from_keys(distinct_sorted(&keys)),
+        };
+        let mut targets = Vec::new();
+        for (row_num, key) in keys.iter().enumerate() {
+            let mut target = vec![0.0; label_map.class_count()];
+            match label_map.index_of(key) {
+                Some(position) => target[position] = 1.0,
+                None => return Err(NnError::Tabular(TabularError::UnknownLabel {row: row_num, label: key.clone()})),
+            }
+            targets.push(target);
+        }
+        dataset::InMemoryDataset::new(inputs, targets, dataset::Shape::flat(feature_positions.len()), label_map)
+    }
+
+    fn find_column(&self, column: &Column, header: &[String], column_count: usize) -> Result<usize, TabularError> {
+        let pos = match column {
+            Column::Index(pos) => Some(*pos).filter(|&pos| pos < column_count),
+            Column::Name(name) if self.has_header => header.iter().position(|column_name| column_name == name),
+            Column::Name(_) => return Err(TabularError::NoHeader(column.clone())),
+        };
+        pos.ok_or_else(|| TabularError::MissingColumn(column.clone()))
+    }
+}
+
+fn distinct_sorted(keys: &[String]) -> Vec<String> {
+    let mut distinct: Vec<String> = Vec::new();
+    for key in keys {
+        if !distinct.contains(key) {
+            distinct.push(key.clone());
+        }
+    }
+    if distinct.iter().all(|key| key.parse::<f64>().is_ok()) {
+        distinct.sort_by(|first, second| first.parse::<f64>().unwrap().partial_cmp(&second.parse::<f64>().unwrap()).unwrap_or(Ordering::Equal));
+    } else {
+        distinct.sort();
+    }
+    distinct
+}
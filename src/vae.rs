@@ -0,0 +1,204 @@
+extern crate rand;
+
+use rand::Rng;
+use rand::distributions::StandardNormal;
+
+use crate::error::NnError;
+use crate::inout;
+use crate::nodes_layers;
+
+#[derive(Clone)]
+pub struct Vae {
+    //A variational autoencoder. The encoder turns an input into a mean and a log variance for each latent dimension,
+    //a latent point is sampled from that distribution, and the decoder turns the point back into an input.
+    //Since the latent space is pushed towards a standard normal distribution, new inputs can be made by decoding random points.
+    pub encoder: nodes_layers::Network, //The hidden layers of the encoder.
+    pub mean_layer: nodes_layers::Layer, //A linear layer on top of the encoder, giving the mean of each latent dimension.
+    pub log_var_layer: nodes_layers::Layer, //A linear layer on top of the encoder, giving the log variance of each latent dimension.
+    pub decoder: nodes_layers::Network, //Turns a latent point into values in [0; 1], like the images.
+    pub latent_size: usize,
+    pub beta: f32, //How much the KL term counts compared to the reconstruction. 1.0 is a plain VAE.
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct VaeLoss {
+    pub reconstruction: f32, //Binary cross-entropy between the input and its reconstruction, summed over the values.
+    pub kl_divergence: f32, //How far the encoded distribution is from a standard normal distribution.
+}
+
+impl VaeLoss {
+    pub fn total(&self, beta: f32) -> f32 {
+        self.reconstruction + beta * self.kl_divergence
+    }
+}
+
+impl Vae {
+    pub fn new(encoder_nums: &[usize], latent_size: usize, learning_rate: f32) -> Result<Vae, NnError> {
+        //Makes a new VAE with random weights and biases.
+        //encoder_nums works like node_nums for nodes_layers::Network::new, starting with the input layer and ending with the last hidden layer of the encoder.
+        //The decoder is a mirror image of the encoder, starting from latent_size.
+        if encoder_nums.len() < 2 {
+            return Err(NnError::Config(format!("An encoder needs an input layer and a hidden layer, but it has {:?} layers", encoder_nums.len())))
+        }
+        let last_hidden = encoder_nums[encoder_nums.len()-1];
+        let mut decoder_nums = vec![latent_size];
+        for num in (0..encoder_nums.len()-1).rev() {
+            decoder_nums.push(encoder_nums[num]);
+        }
+        Ok(Vae {
+            encoder: nodes_layers::Network::new(encoder_nums, learning_rate),
+            mean_layer: nodes_layers::Layer::new(last_hidden, latent_size, learning_rate),
+            log_var_layer: nodes_layers::Layer::new(last_hidden, latent_size, learning_rate),
+            decoder: nodes_layers::Network::new(&decoder_nums, learning_rate),
+            latent_size: latent_size,
+            beta: 1.0,
+        })
+    }
+
+    pub fn encode(&self, inputs: &Vec<f32>) -> Result<(Vec<f32>, Vec<f32>), NnError> {
+        //The mean and log variance of the latent distribution for the inputs.
+        let hidden = self.encoder.predict(inputs)?;
+        Ok((self.mean_layer.weighted_sums(&hidden)?, self.log_var_layer.weighted_sums(&hidden)?))
+    }
+
+    pub fn decode(&self, latent: &Vec<f32>) -> Result<Vec<f32>, NnError> {
+        //Turns a point in the latent space into values in [0; 1].
+        self.decoder.predict(latent)
+    }
+
+    pub fn reconstruct(&self, inputs: &Vec<f32>) -> Result<Vec<f32>, NnError> {
+        //Decodes the mean of the encoded distribution, which gives the most likely reconstruction without any sampling noise.
+        self.decode(&self.encode(inputs)?.0)
+    }
+
+    pub fn sample(&self) -> Result<Vec<f32>, NnError> {
+        //Makes a new input by decoding a random point from a standard normal distribution.
+        let mut rng = rand::thread_rng();
+        let latent = (0..self.latent_size).map(|_| rng.sample(StandardNormal) as f32).collect();
+        self.decode(&latent)
+    }
+
+    pub fn loss(&self, inputs: &Vec<f32>) -> Result<VaeLoss, NnError> {
+        //The loss for the inputs without training on them. The reconstruction is made from the mean, so it doesn't vary from call to call.
+        let (mean, log_var) = self.encode(inputs)?;
+        let reconstruction = self.decode(&mean)?;
+        if reconstruction.len() != inputs.len() {
+            return Err(NnError::shape_mismatch("the reconstruction", inputs.len(), reconstruction.len()))
+        }
+        let mut loss = VaeLoss {reconstruction: reconstruction_loss(inputs, &reconstruction), kl_divergence: 0.0};
+        for num in 0..self.latent_size {
+            loss.kl_divergence -= 0.5 * (1.0 + log_var[num] - mean[num].powi(2) - log_var[num].exp());
+        }
+        Ok(loss)
+    }
+
+    pub fn train(&mut self, inputs: &Vec<f32>) -> Result<VaeLoss, NnError> {
+        //Trains on a single example and returns the loss from before the adjustment.
+        //The latent point is sampled with the reparameterisation trick, latent = mean + exp(log_var / 2) * noise,
+        //so the gradient can pass through the sampling to the mean and log variance.
+        let mut rng = rand::thread_rng();
+        let encoder_values = self.encoder.calculate(inputs)?;
+        let hidden = &encoder_values[self.encoder.layer_count-1];
+        let mean = self.mean_layer.weighted_sums(hidden)?;
+        let log_var = self.log_var_layer.weighted_sums(hidden)?;
+        let mut noise = Vec::new();
+        let mut latent = Vec::new();
+        for num in 0..self.latent_size {
+            noise.push(rng.sample(StandardNormal) as f32);
+            latent.push(mean[num] + (0.5 * log_var[num]).exp() * noise[num]);
+        }
+        let decoder_values = self.decoder.calculate(&latent)?;
+        let reconstruction = &decoder_values[self.decoder.layer_count-1];
+        if reconstruction.len() != inputs.len() {
+            return Err(NnError::shape_mismatch("the reconstruction", inputs.len(), reconstruction.len()))
+        }
+
+        let mut loss = VaeLoss {reconstruction: reconstruction_loss(inputs, reconstruction), kl_divergence: 0.0};
+        for num in 0..self.latent_size {
+            loss.kl_divergence -= 0.5 * (1.0 + log_var[num] - mean[num].powi(2) - log_var[num].exp());
+        }
+
+        //With a sigmoid output and binary cross-entropy, the output deltas are simply the difference from the input.
+        let output_deltas = (0..inputs.len()).map(|num| reconstruction[num] - inputs[num]).collect();
+        let decoder_deltas = self.decoder.backpropagate(&decoder_values, output_deltas)?;
+        let latent_gradient = self.decoder.input_gradient(&decoder_deltas)?;
+        let mut mean_deltas = Vec::new();
+        let mut log_var_deltas = Vec::new();
+        for num in 0..self.latent_size {
+            let std_dev = (0.5 * log_var[num]).exp();
+            mean_deltas.push(latent_gradient[num] + self.beta * mean[num]);
+            log_var_deltas.push(latent_gradient[num] * noise[num] * 0.5 * std_dev + self.beta * 0.5 * (log_var[num].exp() - 1.0));
+        }
+        let mut hidden_gradient = self.mean_layer.propagate_back(&mean_deltas)?;
+        let log_var_gradient = self.log_var_layer.propagate_back(&log_var_deltas)?;
+        for num in 0..hidden_gradient.len() {
+            hidden_gradient[num] += log_var_gradient[num];
+            hidden_gradient[num] *= hidden[num] * (1.0 - hidden[num]);
+        }
+        let encoder_deltas = self.encoder.backpropagate(&encoder_values, hidden_gradient)?;
+
+        self.decoder.adjust(&latent, &decoder_values, &decoder_deltas)?;
+        self.mean_layer.adjust(&mean_deltas, hidden)?;
+        self.log_var_layer.adjust(&log_var_deltas, hidden)?;
+        self.encoder.adjust(inputs, &encoder_values, &encoder_deltas)?;
+        Ok(loss)
+    }
+
+    pub fn write(&self, file_paths: Vec<&str>) -> Result<(), NnError> {
+        //Writes the VAE with inout::write_layer, one file per layer.
+        //The files are the layers of the encoder, then the mean layer, then the log variance layer, then the layers of the decoder.
+        let layers = self.all_layers();
+        if file_paths.len() < layers.len() {
+            return Err(NnError::Config(format!("There are {:?} file paths for the {:?} layers of the VAE", file_paths.len(), layers.len())))
+        }
+        for num in 0..layers.len() {
+            inout::write_layer(layers[num], file_paths[num])?;
+        }
+        Ok(())
+    }
+
+    pub fn read(file_paths: Vec<&str>, encoder_nums: &[usize], latent_size: usize, learning_rate: f32) -> Result<Vae, NnError> {
+        //Reads a VAE written by write. encoder_nums and latent_size must be the same as when it was made with new.
+        let mut vae = Vae::new(encoder_nums, latent_size, learning_rate)?;
+        let mut shapes = Vec::new();
+        for layer in vae.all_layers() {
+            shapes.push((layer.nodes.first().map_or(0, |node| node.weights.len()), layer.node_count));
+        }
+        if file_paths.len() < shapes.len() {
+            return Err(NnError::Config(format!("There are {:?} file paths for the {:?} layers of the VAE", file_paths.len(), shapes.len())))
+        }
+        let mut layers = Vec::new();
+        for num in 0..shapes.len() {
+            layers.push(inout::read_layer(file_paths[num], shapes[num].0, shapes[num].1, learning_rate)?);
+        }
+        let encoder_layer_count = vae.encoder.layer_count;
+        let decoder_layers = layers.split_off(encoder_layer_count + 2);
+        let mut latent_layers = layers.split_off(encoder_layer_count).into_iter();
+        if let (Some(mean_layer), Some(log_var_layer)) = (latent_layers.next(), latent_layers.next()) {
+            vae.mean_layer = mean_layer;
+            vae.log_var_layer = log_var_layer;
+        }
+        vae.encoder = nodes_layers::Network::from_layers(layers);
+        vae.decoder = nodes_layers::Network::from_layers(decoder_layers);
+        Ok(vae)
+    }
+
+    fn all_layers(&self) -> Vec<&nodes_layers::Layer> {
+        //The layers in the order they're saved in.
+        let mut layers: Vec<&nodes_layers::Layer> = self.encoder.layers.iter().collect();
+        layers.push(&self.mean_layer);
+        layers.push(&self.log_var_layer);
+        layers.extend(self.decoder.layers.iter());
+        layers
+    }
+}
+
+fn reconstruction_loss(inputs: &[f32], reconstruction: &[f32]) -> f32 {
+    //Binary cross-entropy, with the reconstruction kept away from 0.0 and 1.0 so the logarithms stay finite.
+    let mut loss = 0.0;
+    for num in 0..inputs.len() {
+        let value = reconstruction[num].max(1e-7).min(1.0 - 1e-7);
+        loss -= inputs[num] * value.ln() + (1.0 - inputs[num]) * (1.0 - value).ln();
+    }
+    loss
+}